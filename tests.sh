@@ -124,7 +124,7 @@ else
 fi
 echo
 
-# Test 8: VER_SHIM_BUFFER_SIZE=65535 should work
+# Test 9: VER_SHIM_BUFFER_SIZE=65535 should work
 echo "--- Test: VER_SHIM_BUFFER_SIZE=65535 (max u16) works ---"
 if (cd ver-shim-example-objcopy && VER_SHIM_BUFFER_SIZE=65535 cargo build 2>&1); then
     pass "VER_SHIM_BUFFER_SIZE=65535 works"
@@ -133,7 +133,7 @@ else
 fi
 echo
 
-# Test 9: VER_SHIM_BUFFER_SIZE=65536 should fail
+# Test 10: VER_SHIM_BUFFER_SIZE=65536 should fail
 echo "--- Test: VER_SHIM_BUFFER_SIZE=65536 (overflow) fails ---"
 if (cd ver-shim-example-objcopy && VER_SHIM_BUFFER_SIZE=65536 cargo build 2>&1); then
     fail "VER_SHIM_BUFFER_SIZE=65536 should fail"
@@ -142,21 +142,30 @@ else
 fi
 echo
 
-# Test 10: VER_SHIM_BUFFER_SIZE=32 (too small) should fail
-echo "--- Test: VER_SHIM_BUFFER_SIZE=32 (too small) fails ---"
-if (cd ver-shim-example-objcopy && VER_SHIM_BUFFER_SIZE=32 cargo build 2>&1); then
-    fail "VER_SHIM_BUFFER_SIZE=32 should fail (must be > 32)"
+# Test 11: VER_SHIM_BUFFER_SIZE=73 (the size of the header) should fail
+echo "--- Test: VER_SHIM_BUFFER_SIZE=73 (too small) fails ---"
+if (cd ver-shim-example-objcopy && VER_SHIM_BUFFER_SIZE=73 cargo build 2>&1); then
+    fail "VER_SHIM_BUFFER_SIZE=73 should fail (must be > 73, the header size)"
 else
-    pass "VER_SHIM_BUFFER_SIZE=32 correctly fails"
+    pass "VER_SHIM_BUFFER_SIZE=73 correctly fails"
 fi
 echo
 
-# Build a baseline before VER_SHIM_BUILD_TIME tests (test 10 left things in a failed state)
+# Test 12: VER_SHIM_BUFFER_SIZE=74 (one more than the header) should work
+echo "--- Test: VER_SHIM_BUFFER_SIZE=74 (minimum) works ---"
+if (cd ver-shim-example-objcopy && VER_SHIM_BUFFER_SIZE=74 cargo build 2>&1); then
+    pass "VER_SHIM_BUFFER_SIZE=74 works"
+else
+    fail "VER_SHIM_BUFFER_SIZE=74 should work"
+fi
+echo
+
+# Build a baseline before VER_SHIM_BUILD_TIME tests
 echo "--- Building baseline for VER_SHIM_BUILD_TIME tests ---"
 (cd ver-shim-example-objcopy && cargo build 2>&1)
 echo
 
-# Test 11: VER_SHIM_BUILD_TIME with unix timestamp
+# Test 13: VER_SHIM_BUILD_TIME with unix timestamp
 echo "--- Test: VER_SHIM_BUILD_TIME with unix timestamp ---"
 VER_SHIM_BUILD_TIME=1700000000 $VER_SHIM --all-git --all-build-time patch \
     ver-shim-example-objcopy/target/debug/ver-shim-example-objcopy 2>&1
@@ -168,7 +177,7 @@ else
 fi
 echo
 
-# Test 12: VER_SHIM_BUILD_TIME with RFC 3339
+# Test 14: VER_SHIM_BUILD_TIME with RFC 3339
 echo "--- Test: VER_SHIM_BUILD_TIME with RFC 3339 ---"
 VER_SHIM_BUILD_TIME="2024-06-15T12:30:00Z" $VER_SHIM --all-git --all-build-time patch \
     ver-shim-example-objcopy/target/debug/ver-shim-example-objcopy 2>&1
@@ -180,7 +189,7 @@ else
 fi
 echo
 
-# Test 13: VER_SHIM_BUILD_TIME with invalid value should fail
+# Test 15: VER_SHIM_BUILD_TIME with invalid value should fail
 echo "--- Test: VER_SHIM_BUILD_TIME with invalid value fails ---"
 if VER_SHIM_BUILD_TIME="not-a-timestamp" $VER_SHIM --all-git --all-build-time patch \
     ver-shim-example-objcopy/target/debug/ver-shim-example-objcopy 2>&1; then
@@ -190,7 +199,7 @@ else
 fi
 echo
 
-# Test 14: VER_SHIM_IDEMPOTENT skips build time
+# Test 16: VER_SHIM_IDEMPOTENT skips build time
 echo "--- Test: VER_SHIM_IDEMPOTENT skips build time ---"
 VER_SHIM_IDEMPOTENT=1 $VER_SHIM --all-git --all-build-time patch \
     ver-shim-example-objcopy/target/debug/ver-shim-example-objcopy 2>&1
@@ -208,7 +217,7 @@ else
 fi
 echo
 
-# Test 15: Patching updates git info without rebuild
+# Test 17: Patching updates git info without rebuild
 echo "--- Test: Patching updates git info without rebuild ---"
 
 # Get current branch for later comparison
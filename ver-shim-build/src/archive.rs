@@ -0,0 +1,150 @@
+//! Patching static library archives (`.a` / `.lib`).
+//!
+//! A `staticlib` contains the object file for `ver-shim`, which has the version section
+//! with its final size, but zero contents. We overwrite the section contents of each
+//! member object in place. Since no member changes size, the archive index (symbol
+//! table) and the member headers stay valid and don't need to be rewritten.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::Path;
+
+use object::ObjectSection;
+use object::read::archive::ArchiveFile;
+
+use crate::native;
+
+/// Magic bytes at the start of an `ar` archive.
+const AR_MAGIC: &[u8] = b"!<arch>\n";
+
+/// Returns true if the data is an `ar` archive (including `.lib` files on Windows).
+pub fn is_archive(data: &[u8]) -> bool {
+    data.starts_with(AR_MAGIC)
+}
+
+/// Returns true if the file at `path` is an `ar` archive.
+pub fn is_archive_file(path: &Path) -> io::Result<bool> {
+    let mut magic = [0u8; AR_MAGIC.len()];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(is_archive(&magic)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Finds the file ranges (relative to the start of the archive) of the version section
+/// in every member object that has it.
+///
+/// Members which are not object files (like the symbol table) are skipped.
+pub fn find_sections(data: &[u8], custom_name: Option<&str>) -> Result<Vec<Range<usize>>, String> {
+    let archive = ArchiveFile::parse(data).map_err(|e| format!("invalid archive: {}", e))?;
+    if archive.is_thin() {
+        return Err(
+            "thin archives are not supported, since they don't contain the member objects"
+                .to_string(),
+        );
+    }
+
+    let mut ranges = Vec::new();
+    for member in archive.members() {
+        let member = member.map_err(|e| format!("invalid archive member: {}", e))?;
+        let (member_offset, _) = member.file_range();
+        let member_data = member
+            .data(data)
+            .map_err(|e| format!("invalid archive member data: {}", e))?;
+
+        // Not every member is an object file (e.g. rustc adds a `lib.rmeta` member)
+        let Ok(file) = object::File::parse(member_data) else {
+            continue;
+        };
+        let Some(section) = native::version_section(&file, custom_name) else {
+            continue;
+        };
+        let Some((offset, size)) = section.file_range() else {
+            continue;
+        };
+        let start = (member_offset + offset) as usize;
+        ranges.push(start..start + size as usize);
+    }
+    Ok(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, SECTION_SIZE};
+    use crate::{LinkSection, Member, SectionData};
+    use std::fs;
+    use ver_shim::SECTION_NAME;
+
+    fn library() -> Vec<u8> {
+        let section = fixtures::unpatched_section();
+        let with_section =
+            fixtures::elf_object(&[(".text", &[0xc3; 16]), (SECTION_NAME, &section)]);
+        let without_section = fixtures::elf_object(&[(".text", &[0x90; 7])]);
+        fixtures::ar_archive(&[
+            ("ver_shim.o", &with_section),
+            ("lib.rmeta", b"rust metadata"),
+            ("other.o", &without_section),
+            ("ver_shim2.o", &with_section),
+        ])
+    }
+
+    #[test]
+    fn detect_archive() {
+        assert!(is_archive(&library()));
+        assert!(!is_archive(&fixtures::elf_object(&[])));
+        assert!(!is_archive(b"!<thin>\n"));
+    }
+
+    #[test]
+    fn sections_of_object_members() {
+        let data = library();
+        let ranges = find_sections(&data, None).unwrap();
+        assert_eq!(ranges.len(), 2);
+        for range in &ranges {
+            assert_eq!(range.len(), SECTION_SIZE);
+            assert_eq!(&data[range.clone()], &fixtures::unpatched_section()[..]);
+        }
+        assert_eq!(find_sections(&data, Some(".other")).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn thin_archive_is_rejected() {
+        let mut data = library();
+        data[..AR_MAGIC.len()].copy_from_slice(b"!<thin>\n");
+        let err = find_sections(&data, None).unwrap_err();
+        assert!(err.contains("thin archives"), "{}", err);
+    }
+
+    #[test]
+    fn patch_every_member() {
+        let dir = fixtures::temp_dir("archive");
+        let (input, output) = (dir.join("libfoo.a"), dir.join("libfoo_patched.a"));
+        let data = library();
+        fs::write(&input, &data).unwrap();
+
+        LinkSection::new()
+            .with_custom("archived")
+            .patch_into(&input)
+            .try_write_to(&output)
+            .unwrap();
+
+        let patched = fs::read(&output).unwrap();
+        assert_eq!(patched.len(), data.len());
+        let ranges = find_sections(&patched, None).unwrap();
+        assert_eq!(ranges, find_sections(&data, None).unwrap());
+        for range in &ranges {
+            let section = SectionData::parse(&patched[range.clone()]).unwrap();
+            assert_eq!(section.get(Member::Custom), Some("archived"));
+        }
+        // Everything outside the sections is unchanged
+        let mut unpatched = patched.clone();
+        for range in &ranges {
+            unpatched[range.clone()].copy_from_slice(&data[range.clone()]);
+        }
+        assert_eq!(unpatched, data);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
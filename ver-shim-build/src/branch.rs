@@ -0,0 +1,66 @@
+//! Resolving the branch name, also in detached `HEAD` checkouts.
+//!
+//! CI systems usually check out a commit rather than a branch, so `git rev-parse
+//! --abbrev-ref HEAD` only says `HEAD`. The sources are tried in order until one names a
+//! branch, see `LinkSection::with_git_branch_sources()`.
+
+use crate::ci;
+use crate::git_provider::GitProvider;
+
+/// A way to find the branch name, see `LinkSection::with_git_branch_sources()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BranchSource {
+    /// The checked out branch, like `git rev-parse --abbrev-ref HEAD`. This finds
+    /// nothing if `HEAD` is detached.
+    Head,
+    /// The branch which the CI provider says it is building, e.g. `GITHUB_REF_NAME` or
+    /// `CI_COMMIT_REF_NAME`. For a pull or merge request, this is the source branch.
+    Ci,
+    /// A local or remote branch pointing at `HEAD`, like
+    /// `git for-each-ref --points-at HEAD`. Local branches are preferred.
+    PointsAt,
+    /// The closest branch which contains `HEAD`, like `git name-rev HEAD`.
+    NameRev,
+}
+
+/// The sources which are tried by default, in order.
+pub const DEFAULT_SOURCES: [BranchSource; 4] = [
+    BranchSource::Head,
+    BranchSource::Ci,
+    BranchSource::PointsAt,
+    BranchSource::NameRev,
+];
+
+/// Gets the branch name from the first of `sources` which finds one.
+///
+/// If none does, this is `HEAD` if `BranchSource::Head` found a detached `HEAD`, like
+/// `git rev-parse --abbrev-ref HEAD`, and the first failure otherwise.
+pub fn resolve(git: &dyn GitProvider, sources: &[BranchSource]) -> Result<String, String> {
+    let mut detached = false;
+    let mut first_err = None;
+    for source in sources {
+        let branch = match source {
+            BranchSource::Head => git.branch().map(|branch| {
+                detached = branch == "HEAD";
+                (!detached).then_some(branch)
+            }),
+            BranchSource::Ci => Ok(ci::branch()),
+            BranchSource::PointsAt => git
+                .branches_at_head()
+                .map(|branches| branches.into_iter().next()),
+            BranchSource::NameRev => git.nearest_branch().map(Some),
+        };
+        match branch {
+            Ok(Some(branch)) => return Ok(branch),
+            Ok(None) => {}
+            Err(e) => {
+                first_err.get_or_insert(e);
+            }
+        }
+    }
+    match first_err {
+        _ if detached => Ok("HEAD".to_string()),
+        Some(e) => Err(e),
+        None => Err("no branch found".to_string()),
+    }
+}
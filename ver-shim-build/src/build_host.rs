@@ -0,0 +1,46 @@
+//! The hostname, operating system, and architecture of the build machine.
+//!
+//! A build script runs on the build machine, so the operating system and architecture
+//! are those that the build script was compiled for, not those of the target.
+
+use std::fs;
+use std::process::Command;
+
+/// Gets the data of the `BuildHost` member, e.g. `ci-runner-7 (linux x86_64)`.
+///
+/// If the hostname can't be found, this is only the operating system and architecture.
+pub fn detect() -> String {
+    let platform = format!("{} {}", std::env::consts::OS, std::env::consts::ARCH);
+    match hostname() {
+        Some(hostname) => format!("{} ({})", hostname, platform),
+        None => platform,
+    }
+}
+
+/// Gets the hostname from the environment, `/proc` or `/etc/hostname`, or the
+/// `hostname` command, whichever works first.
+fn hostname() -> Option<String> {
+    let sources: [&dyn Fn() -> Option<String>; 3] = [
+        &|| {
+            ["COMPUTERNAME", "HOSTNAME"]
+                .into_iter()
+                .find_map(|var| std::env::var(var).ok())
+        },
+        &|| {
+            ["/proc/sys/kernel/hostname", "/etc/hostname"]
+                .into_iter()
+                .find_map(|path| fs::read_to_string(path).ok())
+        },
+        &|| {
+            let output = Command::new("hostname").output().ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+        },
+    ];
+    sources.into_iter().find_map(|source| {
+        let hostname = source()?.trim().to_string();
+        (!hostname.is_empty()).then_some(hostname)
+    })
+}
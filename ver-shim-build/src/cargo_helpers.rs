@@ -4,6 +4,8 @@ use heck::ToShoutySnakeCase;
 use std::fs;
 use std::path::PathBuf;
 
+use crate::error::VerShimError;
+
 /// Returns true if we're running inside a cargo build script context.
 /// We detect this by checking for the OUT_DIR environment variable.
 pub fn in_build_script() -> bool {
@@ -19,6 +21,20 @@ pub fn cargo_rerun_if(suffix: &str) {
     }
 }
 
+/// Emit a `cargo::rustc-env=key=value` directive if in a build script context.
+pub fn cargo_rustc_env(key: &str, value: &str) {
+    if in_build_script() {
+        println!("cargo::rustc-env={}={}", key, value);
+    }
+}
+
+/// Emit a `cargo::rustc-link-arg-bins=arg` directive if in a build script context.
+pub fn cargo_rustc_link_arg_bins(arg: &str) {
+    if in_build_script() {
+        println!("cargo::rustc-link-arg-bins={}", arg);
+    }
+}
+
 /// Emit a warning. In build script context, emits `cargo::warning=msg`.
 /// Otherwise, prints to stderr with `eprintln!`.
 pub fn cargo_warning(msg: &str) {
@@ -29,12 +45,22 @@ pub fn cargo_warning(msg: &str) {
     }
 }
 
+/// Returns true if the `PROFILE` environment variable is `release`.
+///
+/// Cargo sets `PROFILE` for build scripts to `release` for the release profile and
+/// any custom profile that inherits from it, and `debug` otherwise.
+/// See: https://doc.rust-lang.org/cargo/reference/environment-variables.html#environment-variables-cargo-sets-for-build-scripts
+pub fn is_release_profile() -> bool {
+    std::env::var("PROFILE").is_ok_and(|p| p == "release")
+}
+
 /// Gets OUT_DIR from environment.
-pub fn out_dir() -> PathBuf {
+pub fn out_dir() -> Result<PathBuf, VerShimError> {
     // OUT_DIR is set by Cargo for build scripts to write generated files.
     // See: https://doc.rust-lang.org/cargo/reference/environment-variables.html#environment-variables-cargo-sets-for-build-scripts
-    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR not set - must be run from build.rs");
-    PathBuf::from(out_dir)
+    let out_dir = std::env::var("OUT_DIR")
+        .map_err(|_| VerShimError::Env("OUT_DIR not set - must be run from build.rs".into()))?;
+    Ok(PathBuf::from(out_dir))
 }
 
 /// Gets the target directory (e.g., `target/`).
@@ -46,44 +72,43 @@ pub fn out_dir() -> PathBuf {
 /// [env]
 /// CARGO_TARGET_DIR = { value = "target", relative = true }
 /// ```
-pub fn target_dir() -> PathBuf {
+pub fn target_dir() -> Result<PathBuf, VerShimError> {
     // Check CARGO_TARGET_DIR first (user override)
     if let Ok(target_dir) = std::env::var("CARGO_TARGET_DIR") {
-        return PathBuf::from(target_dir);
+        return Ok(PathBuf::from(target_dir));
     }
 
     // Infer from OUT_DIR (target/debug/build/<pkg>/out -> go up 4 levels)
-    let out_dir = out_dir();
-    out_dir
-        .ancestors()
-        .nth(4)
-        .expect(
-            "ver-shim-build: could not find target dir from OUT_DIR. \
+    let out_dir = out_dir()?;
+    let target_dir = out_dir.ancestors().nth(4).ok_or_else(|| {
+        VerShimError::Env(
+            "could not find target dir from OUT_DIR. \
              Set CARGO_TARGET_DIR in .cargo/config.toml:\n\n\
              [env]\n\
-             CARGO_TARGET_DIR = { value = \"target\", relative = true }",
+             CARGO_TARGET_DIR = { value = \"target\", relative = true }"
+                .into(),
         )
-        .to_path_buf()
+    })?;
+    Ok(target_dir.to_path_buf())
 }
 
 /// Gets the target profile directory (e.g., `target/debug/` or `target/release/`).
 ///
 /// Derives this from OUT_DIR which is like `target/debug/build/<pkg>/out`.
 /// For cross-compilation, it's `target/<triple>/debug/build/<pkg>/out`.
-pub fn target_profile_dir() -> PathBuf {
-    let out_dir = out_dir();
+pub fn target_profile_dir() -> Result<PathBuf, VerShimError> {
+    let out_dir = out_dir()?;
     // OUT_DIR is target/[<triple>/]debug/build/<pkg>/out, go up 3 levels to get target/[<triple>/]debug
-    out_dir
-        .ancestors()
-        .nth(3)
-        .expect("ver-shim-build: could not find target dir from OUT_DIR")
-        .to_path_buf()
+    let profile_dir = out_dir.ancestors().nth(3).ok_or_else(|| {
+        VerShimError::Env("could not find target profile dir from OUT_DIR".into())
+    })?;
+    Ok(profile_dir.to_path_buf())
 }
 
 /// Finds the artifact binary path using cargo's artifact dependency environment variables:
 /// `CARGO_BIN_FILE_<DEP>_<NAME>` and `CARGO_BIN_DIR_<DEP>`.
 /// See: https://doc.rust-lang.org/cargo/reference/unstable.html#artifact-dependencies
-pub fn find_artifact_binary(dep_name: &str, bin_name: &str) -> PathBuf {
+pub fn find_artifact_binary(dep_name: &str, bin_name: &str) -> Result<PathBuf, VerShimError> {
     // Convert dep name to SHOUTY_SNAKE_CASE for env var lookup.
     // Cargo converts dependency names to uppercase with dashes replaced by underscores.
     let dep_upper = dep_name.to_shouty_snake_case();
@@ -94,13 +119,13 @@ pub fn find_artifact_binary(dep_name: &str, bin_name: &str) -> PathBuf {
     if let Ok(path) = std::env::var(&file_env_var_original) {
         let path = PathBuf::from(path);
         if path.exists() {
-            return path;
+            return Ok(path);
         }
-        panic!(
-            "ver-shim-build: {} is set to '{}' but file does not exist",
+        return Err(VerShimError::Env(format!(
+            "{} is set to '{}' but file does not exist",
             file_env_var_original,
             path.display()
-        );
+        )));
     }
 
     // Try CARGO_BIN_FILE_<DEP> (default binary, no name suffix)
@@ -108,13 +133,13 @@ pub fn find_artifact_binary(dep_name: &str, bin_name: &str) -> PathBuf {
     if let Ok(path) = std::env::var(&file_env_var_default) {
         let path = PathBuf::from(path);
         if path.exists() {
-            return path;
+            return Ok(path);
         }
-        panic!(
-            "ver-shim-build: {} is set to '{}' but file does not exist",
+        return Err(VerShimError::Env(format!(
+            "{} is set to '{}' but file does not exist",
             file_env_var_default,
             path.display()
-        );
+        )));
     }
 
     // Try CARGO_BIN_DIR_<DEP> and search for the binary
@@ -129,19 +154,19 @@ pub fn find_artifact_binary(dep_name: &str, bin_name: &str) -> PathBuf {
                 // Match bin_name with underscores (cargo converts - to _)
                 let bin_name_underscore = bin_name.replace('-', "_");
                 if file_name_str.starts_with(&bin_name_underscore) {
-                    return entry.path();
+                    return Ok(entry.path());
                 }
             }
         }
-        panic!(
-            "ver-shim-build: {} is set to '{}' but no binary matching '{}' found in that directory",
+        return Err(VerShimError::Env(format!(
+            "{} is set to '{}' but no binary matching '{}' found in that directory",
             dir_env_var, dir, bin_name
-        );
+        )));
     }
 
     // No env var found
-    panic!(
-        "ver-shim-build: could not find artifact binary for dep='{}', bin='{}'\n\
+    Err(VerShimError::Env(format!(
+        "could not find artifact binary for dep='{}', bin='{}'\n\
          Expected one of:\n\
          - {} (not set)\n\
          - {} (not set)\n\
@@ -151,5 +176,40 @@ pub fn find_artifact_binary(dep_name: &str, bin_name: &str) -> PathBuf {
          [build-dependencies]\n\
          {} = {{ path = \"...\", artifact = \"bin\" }}",
         dep_name, bin_name, file_env_var_original, file_env_var_default, dir_env_var, dep_name
-    );
+    )))
+}
+
+/// Finds all binaries of an artifact dependency, as `(bin_name, path)` pairs sorted by
+/// name, from the `CARGO_BIN_FILE_<DEP>_<NAME>` environment variables.
+pub fn find_all_artifact_binaries(dep_name: &str) -> Result<Vec<(String, PathBuf)>, VerShimError> {
+    let prefix = format!("CARGO_BIN_FILE_{}_", dep_name.to_shouty_snake_case());
+    let mut binaries: Vec<(String, PathBuf)> = std::env::vars_os()
+        .filter_map(|(key, value)| {
+            let bin_name = key.to_str()?.strip_prefix(&prefix)?.to_string();
+            Some((bin_name, PathBuf::from(value)))
+        })
+        .collect();
+    binaries.sort();
+
+    if binaries.is_empty() {
+        return Err(VerShimError::Env(format!(
+            "could not find any artifact binaries for dep='{}', no {}* variables are set\n\
+             \n\
+             Make sure you have an artifact dependency in Cargo.toml:\n\
+             [build-dependencies]\n\
+             {} = {{ path = \"...\", artifact = \"bin\" }}",
+            dep_name, prefix, dep_name
+        )));
+    }
+    for (bin_name, path) in &binaries {
+        if !path.exists() {
+            return Err(VerShimError::Env(format!(
+                "{}{} is set to '{}' but file does not exist",
+                prefix,
+                bin_name,
+                path.display()
+            )));
+        }
+    }
+    Ok(binaries)
 }
@@ -0,0 +1,141 @@
+//! Detecting the CI provider of the build, and the run which is building.
+//!
+//! Each provider sets a variable which identifies it, and variables with the URL and ID
+//! of the run. The data is stored as key-value pairs, see `ver_shim::CiInfo` for the keys.
+
+use std::collections::BTreeMap;
+
+use crate::cargo_helpers::cargo_rerun_if;
+
+/// A CI provider, and how to get the run URL and pipeline ID from its variables.
+struct Provider {
+    /// The name stored as `provider`.
+    name: &'static str,
+    /// The variable which is set to `true` on this provider, or just set if `None`.
+    detect: (&'static str, Option<&'static str>),
+    /// Builds the run URL, from the variables it reads.
+    run_url: fn() -> Option<String>,
+    /// The variable with the run, pipeline, or build ID.
+    pipeline_id: &'static str,
+}
+
+const PROVIDERS: [Provider; 4] = [
+    Provider {
+        name: "github-actions",
+        detect: ("GITHUB_ACTIONS", Some("true")),
+        run_url: || {
+            Some(format!(
+                "{}/{}/actions/runs/{}",
+                var("GITHUB_SERVER_URL")?,
+                var("GITHUB_REPOSITORY")?,
+                var("GITHUB_RUN_ID")?
+            ))
+        },
+        pipeline_id: "GITHUB_RUN_ID",
+    },
+    Provider {
+        name: "gitlab-ci",
+        detect: ("GITLAB_CI", Some("true")),
+        run_url: || var("CI_JOB_URL"),
+        pipeline_id: "CI_PIPELINE_ID",
+    },
+    Provider {
+        name: "buildkite",
+        detect: ("BUILDKITE", Some("true")),
+        run_url: || var("BUILDKITE_BUILD_URL"),
+        pipeline_id: "BUILDKITE_BUILD_NUMBER",
+    },
+    Provider {
+        name: "jenkins",
+        detect: ("JENKINS_URL", None),
+        run_url: || var("BUILD_URL"),
+        pipeline_id: "BUILD_NUMBER",
+    },
+];
+
+/// All variables which are read, for `rerun-if-env-changed`.
+const VARS: [&str; 13] = [
+    "GITHUB_ACTIONS",
+    "GITHUB_SERVER_URL",
+    "GITHUB_REPOSITORY",
+    "GITHUB_RUN_ID",
+    "GITLAB_CI",
+    "CI_JOB_URL",
+    "CI_PIPELINE_ID",
+    "BUILDKITE",
+    "BUILDKITE_BUILD_URL",
+    "BUILDKITE_BUILD_NUMBER",
+    "JENKINS_URL",
+    "BUILD_URL",
+    "BUILD_NUMBER",
+];
+
+/// Detects the CI provider, and gets the data of the `CiInfo` member.
+///
+/// Returns `None` outside of CI. In a build script, this also asks cargo to rerun it
+/// when any of the variables change.
+pub fn detect() -> Option<BTreeMap<String, String>> {
+    for var in VARS {
+        cargo_rerun_if(&format!("env-changed={}", var));
+    }
+    let provider = PROVIDERS.iter().find(|p| match p.detect {
+        (name, Some(value)) => var(name).as_deref() == Some(value),
+        (name, None) => var(name).is_some(),
+    })?;
+    let mut info = BTreeMap::from([("provider".to_string(), provider.name.to_string())]);
+    if let Some(url) = (provider.run_url)() {
+        info.insert("run_url".to_string(), url);
+    }
+    if let Some(id) = var(provider.pipeline_id) {
+        info.insert("pipeline_id".to_string(), id);
+    }
+    Some(info)
+}
+
+/// The variables which name the branch being built, for `rerun-if-env-changed`.
+const BRANCH_VARS: [&str; 10] = [
+    "GITHUB_HEAD_REF",
+    "GITHUB_REF_TYPE",
+    "GITHUB_REF_NAME",
+    "CI_MERGE_REQUEST_SOURCE_BRANCH_NAME",
+    "CI_COMMIT_TAG",
+    "CI_COMMIT_REF_NAME",
+    "BUILDKITE_BRANCH",
+    "JENKINS_URL",
+    "CHANGE_BRANCH",
+    "BRANCH_NAME",
+];
+
+/// Gets the branch being built from the variables of the CI provider.
+///
+/// For a pull or merge request, this is the source branch rather than the merge commit.
+/// Returns `None` outside of CI and for tag builds. In a build script, this also asks
+/// cargo to rerun it when any of the variables change.
+pub fn branch() -> Option<String> {
+    for var in BRANCH_VARS {
+        cargo_rerun_if(&format!("env-changed={}", var));
+    }
+    let github = || match var("GITHUB_HEAD_REF") {
+        Some(head_ref) => Some(head_ref),
+        None if var("GITHUB_REF_TYPE").as_deref() == Some("branch") => var("GITHUB_REF_NAME"),
+        None => None,
+    };
+    let gitlab = || match var("CI_MERGE_REQUEST_SOURCE_BRANCH_NAME") {
+        Some(source) => Some(source),
+        None if var("CI_COMMIT_TAG").is_none() => var("CI_COMMIT_REF_NAME"),
+        None => None,
+    };
+    let jenkins = || {
+        var("JENKINS_URL")?;
+        var("CHANGE_BRANCH").or_else(|| var("BRANCH_NAME"))
+    };
+    github()
+        .or_else(gitlab)
+        .or_else(|| var("BUILDKITE_BRANCH"))
+        .or_else(jenkins)
+}
+
+/// Gets a variable, if it is set and not empty.
+fn var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
@@ -0,0 +1,562 @@
+//! Version data collected once, to be written to multiple destinations.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "signing")]
+use ed25519_dalek::SigningKey;
+use ver_shim::Member;
+
+use crate::diagnostics::Diagnostics;
+use crate::env_file;
+use crate::error::VerShimError;
+use crate::format::SectionFormat;
+use crate::git_env;
+use crate::kv;
+#[cfg(feature = "signing")]
+use crate::signing;
+use crate::update_section::{Image, UpdateSectionCommand};
+use crate::{build_section_buffer, cargo_helpers, golden, header_size, output, section_len};
+
+/// The collected data of each member, in header order.
+type MemberData = [Option<String>; Member::COUNT];
+
+/// Version data collected by `LinkSection::collect()`.
+///
+/// All git commands and environment variable lookups happen once, in `collect()`.
+/// The result can then be written to any number of section files and patched into
+/// any number of binaries, and all of these outputs are guaranteed to carry identical data.
+///
+/// ```ignore
+/// let section = LinkSection::new().with_all_git().with_build_timestamp().collect();
+/// section.write_to_target_dir();
+/// section.patch_into_bin_dep("my-server", "my-server").write_to_target_profile_dir();
+/// section.patch_into_bin_dep("my-cli", "my-cli").write_to_target_profile_dir();
+/// ```
+#[derive(Clone)]
+#[must_use]
+pub struct CollectedSection {
+    pub(crate) member_data: MemberData,
+    pub(crate) buffer_size: usize,
+    pub(crate) section_name: Option<String>,
+    pub(crate) truncate_overflow: Vec<Member>,
+    #[cfg(feature = "signing")]
+    pub(crate) signing_key: Option<SigningKey>,
+    pub(crate) non_atomic: bool,
+    pub(crate) dry_run: bool,
+    pub(crate) diagnostics: Diagnostics,
+}
+
+impl CollectedSection {
+    /// Gets the collected data of a member, if present.
+    pub fn get(&self, member: Member) -> Option<&str> {
+        self.member_data[member as usize].as_deref()
+    }
+
+    /// Builds the section data as bytes.
+    ///
+    /// Panics if the data doesn't fit in the buffer, see `try_build_section_bytes()`.
+    pub fn build_section_bytes(&self) -> Vec<u8> {
+        self.try_build_section_bytes()
+            .unwrap_or_else(|e| self.diagnostics.fail(e))
+    }
+
+    /// Like `build_section_bytes()`, but returns an error instead of panicking.
+    ///
+    /// The bytes are in the latest section format. Binaries built with an older
+    /// `ver-shim` read that as no version data, so patch those with `patch_into()`, which
+    /// writes the format that the binary expects.
+    pub fn try_build_section_bytes(&self) -> Result<Vec<u8>, VerShimError> {
+        self.build_section_bytes_for(self.buffer_size, SectionFormat::LATEST)
+    }
+
+    /// Builds the section data as bytes, for a section of the given size and format.
+    ///
+    /// If the data doesn't fit, the members from `LinkSection::truncate_overflow()` are
+    /// truncated, with a warning.
+    pub(crate) fn build_section_bytes_for(
+        &self,
+        buffer_size: usize,
+        format: SectionFormat,
+    ) -> Result<Vec<u8>, VerShimError> {
+        let (member_data, truncated) = self.fit_and_sign(buffer_size, format);
+        let buffer = build_section_buffer(&member_data, buffer_size, format)?;
+        for (member, len) in truncated {
+            let original_len = self.member_data[member as usize]
+                .as_ref()
+                .map_or(0, String::len);
+            if member == Member::Sbom {
+                self.diagnostics.warning(format!(
+                    "the embedded SBOM is {} bytes and doesn't fit in the {} byte section, \
+                     leaving it out",
+                    original_len, buffer_size
+                ));
+            } else {
+                self.diagnostics.warning(format!(
+                    "{:?} is {} bytes, truncated it to {} to fit the {} byte section",
+                    member, original_len, len, buffer_size
+                ));
+            }
+        }
+        Ok(buffer)
+    }
+
+    /// Like `build_section_bytes_for()`, but without the warnings, for checking what was
+    /// written.
+    pub(crate) fn expected_section_bytes(
+        &self,
+        buffer_size: usize,
+        format: SectionFormat,
+    ) -> Result<Vec<u8>, VerShimError> {
+        build_section_buffer(
+            &self.fit_and_sign(buffer_size, format).0,
+            buffer_size,
+            format,
+        )
+    }
+
+    /// Like `fit()`, but signs the data again if anything was truncated, so that the
+    /// signature from `LinkSection::with_signing_key()` covers what is written.
+    fn fit_and_sign(
+        &self,
+        buffer_size: usize,
+        format: SectionFormat,
+    ) -> (Cow<'_, MemberData>, Vec<(Member, usize)>) {
+        let (member_data, truncated) = self.fit(buffer_size, format);
+        #[cfg(feature = "signing")]
+        if let Some(key) = &self.signing_key
+            && !truncated.is_empty()
+        {
+            let mut member_data = member_data.into_owned();
+            signing::sign(key, &mut member_data);
+            return (Cow::Owned(member_data), truncated);
+        }
+        (member_data, truncated)
+    }
+
+    /// Leaves out the embedded SBOM, then truncates the members from `truncate_overflow`
+    /// in order, until the data fits in a section of the given size and format, or there
+    /// is nothing left to truncate.
+    ///
+    /// Returns the member data, and the new length of each truncated member.
+    fn fit(
+        &self,
+        buffer_size: usize,
+        format: SectionFormat,
+    ) -> (Cow<'_, MemberData>, Vec<(Member, usize)>) {
+        let needed = section_len(&self.member_data, format);
+        let has_sbom = self.member_data[Member::Sbom as usize].is_some();
+        if needed <= buffer_size || (self.truncate_overflow.is_empty() && !has_sbom) {
+            return (Cow::Borrowed(&self.member_data), Vec::new());
+        }
+        let mut member_data = self.member_data.clone();
+        let mut excess = needed - buffer_size;
+        let mut truncated = Vec::new();
+        if let Some(sbom) = member_data[Member::Sbom as usize].take() {
+            excess = excess.saturating_sub(sbom.len());
+            truncated.push((Member::Sbom, 0));
+        }
+        for &member in &self.truncate_overflow {
+            if excess == 0 {
+                break;
+            }
+            if let Some(value) = &mut member_data[member as usize] {
+                if value.is_empty() {
+                    continue;
+                }
+                // Clip at a character boundary, which may take a few more bytes
+                let mut end = value.len().saturating_sub(excess);
+                while !value.is_char_boundary(end) {
+                    end -= 1;
+                }
+                excess = excess.saturating_sub(value.len() - end);
+                value.truncate(end);
+                truncated.push((member, end));
+            }
+        }
+        (Cow::Owned(member_data), truncated)
+    }
+
+    /// Gets the collected data as `KEY=value` pairs, e.g. for `docker build --build-arg`.
+    ///
+    /// Keys are the member names in upper snake case (`GIT_SHA`, `BUILD_DATE`, ...), and
+    /// each entry of a member which holds a map has a key of its own, e.g.
+    /// `CUSTOM_KV_DEPLOY_ENV` for the custom key `deploy_env` (see
+    /// `ver-shim read --format env`). Members which are not present are omitted, as is the
+    /// embedded SBOM, and values with a line break, which can't be passed as a build arg.
+    /// Since these come from the same collection as the section data, a container build
+    /// which can't run git itself still sees exactly the values that are embedded in the
+    /// binary.
+    pub fn build_args(&self) -> Vec<(String, String)> {
+        self.key_values()
+            .into_iter()
+            .filter(|(key, value)| {
+                let has_line_break = value.contains(['\n', '\r']);
+                if has_line_break {
+                    self.diagnostics.warning(format!(
+                        "{} contains a line break, omitting it from build args",
+                        key
+                    ));
+                }
+                !has_line_break
+            })
+            .collect()
+    }
+
+    /// Writes the build args to a file, one `KEY=value` per line.
+    ///
+    /// See `build_args()`. Values which a shell would split or interpret are quoted, e.g.
+    /// `GIT_COMMIT_MSG='Fix the build'`, and unlike in `build_args()`, values with a line
+    /// break are kept. `LinkSection::with_env_file()` reads the file back to the same
+    /// values. Returns the path to the written file.
+    pub fn write_build_args(&self, path: impl AsRef<Path>) -> PathBuf {
+        let pairs = self
+            .key_values()
+            .into_iter()
+            .map(|(key, value)| (key, env_file::quote(&value)))
+            .collect();
+        self.write_key_values(path.as_ref(), pairs, "build args")
+    }
+
+    /// Gets the present members but the SBOM as `KEY=value` pairs, see `build_args()`.
+    fn key_values(&self) -> Vec<(String, String)> {
+        let mut pairs = Vec::new();
+        for member in Member::ALL {
+            match self.get(member) {
+                Some(_) if member == Member::Sbom => {}
+                Some(value) if member.is_map() => {
+                    for (key, value) in kv::decode(value) {
+                        pairs.push((git_env::entry_name(member, &key), value));
+                    }
+                }
+                Some(value) => pairs.push((member.name().to_ascii_uppercase(), value.to_string())),
+                None => {}
+            }
+        }
+        pairs
+    }
+
+    /// Gets the collected data as OCI image annotations, e.g. for `docker build --label`.
+    ///
+    /// These are the [pre-defined annotation keys](https://github.com/opencontainers/image-spec/blob/main/annotations.md)
+    /// which have a counterpart in the version data:
+    ///
+    /// - `org.opencontainers.image.revision`: the git SHA
+    /// - `org.opencontainers.image.version`: the package version, or else the git describe
+    /// - `org.opencontainers.image.created`: the build timestamp, if it is RFC 3339
+    /// - `org.opencontainers.image.source`: the git remote URL
+    /// - `org.opencontainers.image.title`: the package name
+    ///
+    /// Labels whose member is not present are omitted, so that the image carries the
+    /// same values as the binary.
+    pub fn oci_labels(&self) -> Vec<(String, String)> {
+        let created = self
+            .get(Member::BuildTimestamp)
+            .filter(|timestamp| chrono::DateTime::parse_from_rfc3339(timestamp).is_ok());
+        if created.is_none() && self.get(Member::BuildTimestamp).is_some() {
+            self.diagnostics.info(
+                "the build timestamp is not RFC 3339, omitting org.opencontainers.image.created",
+            );
+        }
+        [
+            ("revision", self.get(Member::GitSha)),
+            (
+                "version",
+                self.get(Member::PkgVersion)
+                    .or_else(|| self.get(Member::GitDescribe)),
+            ),
+            ("created", created),
+            ("source", self.get(Member::GitRemote)),
+            ("title", self.get(Member::PkgName)),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let key = format!("org.opencontainers.image.{}", name);
+            let value = value?;
+            if value.contains(['\n', '\r']) {
+                self.diagnostics.warning(format!(
+                    "{} contains a line break, omitting it from the labels",
+                    key
+                ));
+                return None;
+            }
+            Some((key, value.to_string()))
+        })
+        .collect()
+    }
+
+    /// Writes the OCI labels to a file, one `key=value` per line.
+    ///
+    /// See `oci_labels()`. Returns the path to the written file, which can be passed to
+    /// `docker build` with `$(sed 's/^/--label /' labels.env)`.
+    pub fn write_oci_labels(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.write_key_values(path.as_ref(), self.oci_labels(), "OCI labels")
+    }
+
+    /// Writes `key=value` lines to a file, for `write_build_args()` and
+    /// `write_oci_labels()`.
+    fn write_key_values(&self, path: &Path, pairs: Vec<(String, String)>, what: &str) -> PathBuf {
+        let contents: String = pairs
+            .into_iter()
+            .map(|(key, value)| format!("{}={}\n", key, value))
+            .collect();
+        if self.dry_run {
+            self.diagnostics.info(format!(
+                "dry run, would write {} to {}",
+                what,
+                path.display()
+            ));
+            return path.to_path_buf();
+        }
+        output::write(path, contents, !self.non_atomic).unwrap_or_else(|e| {
+            self.diagnostics
+                .error(format!("failed to write {}: {}", path.display(), e))
+        });
+        path.to_path_buf()
+    }
+
+    /// Writes the section data file to the specified path.
+    ///
+    /// See `LinkSection::write_to()`.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.try_write_to(path)
+            .unwrap_or_else(|e| self.diagnostics.fail(e))
+    }
+
+    /// Like `write_to()`, but returns an error instead of panicking.
+    pub fn try_write_to(&self, path: impl AsRef<Path>) -> Result<PathBuf, VerShimError> {
+        self.write_section_to_path(path.as_ref())
+    }
+
+    /// Writes the section data file to `OUT_DIR/ver_shim_data`.
+    ///
+    /// See `LinkSection::write_to_out_dir()`.
+    pub fn write_to_out_dir(&self) -> PathBuf {
+        self.try_write_to_out_dir()
+            .unwrap_or_else(|e| self.diagnostics.fail(e))
+    }
+
+    /// Like `write_to_out_dir()`, but returns an error instead of panicking.
+    pub fn try_write_to_out_dir(&self) -> Result<PathBuf, VerShimError> {
+        let out_dir = cargo_helpers::out_dir()?;
+        self.write_section_to_path(&out_dir)
+    }
+
+    /// Writes the section data file to the `target/` directory.
+    ///
+    /// See `LinkSection::write_to_target_dir()`.
+    pub fn write_to_target_dir(&self) -> PathBuf {
+        self.try_write_to_target_dir()
+            .unwrap_or_else(|e| self.diagnostics.fail(e))
+    }
+
+    /// Like `write_to_target_dir()`, but returns an error instead of panicking.
+    pub fn try_write_to_target_dir(&self) -> Result<PathBuf, VerShimError> {
+        let target_dir = cargo_helpers::target_dir()?;
+        self.write_section_to_path(&target_dir)
+    }
+
+    /// Checks the section data against a checked-in golden file, and panics if it differs.
+    ///
+    /// See `LinkSection::check_golden()`.
+    pub fn check_golden(&self, path: impl AsRef<Path>) {
+        self.try_check_golden(path)
+            .unwrap_or_else(|e| self.diagnostics.fail(e))
+    }
+
+    /// Like `check_golden()`, but returns an error instead of panicking.
+    pub fn try_check_golden(&self, path: impl AsRef<Path>) -> Result<(), VerShimError> {
+        let snapshot = golden::render(&self.try_build_section_bytes()?)?;
+        golden::check(
+            path.as_ref(),
+            &snapshot,
+            !self.non_atomic,
+            &self.diagnostics,
+        )
+    }
+
+    /// Creates an `UpdateSectionCommand` for patching a binary at the given path.
+    ///
+    /// See `LinkSection::patch_into()`.
+    pub fn patch_into(&self, binary_path: impl AsRef<Path>) -> UpdateSectionCommand {
+        UpdateSectionCommand {
+            section: self.clone(),
+            bin_path: binary_path.as_ref().to_path_buf(),
+            new_name: None,
+            debug_path: None,
+            image: Image::Binary,
+            mode: None,
+            atomic: !self.non_atomic,
+            resign_adhoc: false,
+            post_process: Vec::new(),
+            fill: None,
+            preserve_layout: false,
+            page_size: None,
+            objcopy_path: None,
+            verify: true,
+            provenance: None,
+            self_hash: false,
+        }
+    }
+
+    /// Creates an `UpdateSectionCommand` for patching an Intel HEX image.
+    ///
+    /// See `LinkSection::patch_into_ihex()`.
+    pub fn patch_into_ihex(
+        &self,
+        hex_path: impl AsRef<Path>,
+        load_address: u32,
+    ) -> UpdateSectionCommand {
+        let mut command = self.patch_into(hex_path);
+        command.image = Image::IntelHex {
+            address: load_address,
+        };
+        command
+    }
+
+    /// Creates an `UpdateSectionCommand` for patching a Motorola S-record image.
+    ///
+    /// See `LinkSection::patch_into_srec()`.
+    pub fn patch_into_srec(
+        &self,
+        srec_path: impl AsRef<Path>,
+        load_address: u32,
+    ) -> UpdateSectionCommand {
+        let mut command = self.patch_into(srec_path);
+        command.image = Image::Srec {
+            address: load_address,
+        };
+        command
+    }
+
+    /// Creates an `UpdateSectionCommand` for patching a UF2 image.
+    ///
+    /// See `LinkSection::patch_into_uf2()`.
+    pub fn patch_into_uf2(
+        &self,
+        uf2_path: impl AsRef<Path>,
+        load_address: u32,
+    ) -> UpdateSectionCommand {
+        let mut command = self.patch_into(uf2_path);
+        command.image = Image::Uf2 {
+            address: load_address,
+        };
+        command
+    }
+
+    /// Creates an `UpdateSectionCommand` for patching a raw binary image.
+    ///
+    /// See `LinkSection::patch_into_raw()`.
+    pub fn patch_into_raw(
+        &self,
+        image_path: impl AsRef<Path>,
+        offset: u64,
+    ) -> UpdateSectionCommand {
+        let mut command = self.patch_into(image_path);
+        command.image = Image::Raw { offset };
+        command
+    }
+
+    /// Creates an `UpdateSectionCommand` for patching an artifact dependency binary.
+    ///
+    /// See `LinkSection::patch_into_bin_dep()`.
+    pub fn patch_into_bin_dep(&self, dep_name: &str, bin_name: &str) -> UpdateSectionCommand {
+        self.try_patch_into_bin_dep(dep_name, bin_name)
+            .unwrap_or_else(|e| self.diagnostics.fail(e))
+    }
+
+    /// Like `patch_into_bin_dep()`, but returns an error instead of panicking.
+    pub fn try_patch_into_bin_dep(
+        &self,
+        dep_name: &str,
+        bin_name: &str,
+    ) -> Result<UpdateSectionCommand, VerShimError> {
+        let bin_path = cargo_helpers::find_artifact_binary(dep_name, bin_name)?;
+        Ok(self.patch_into(bin_path))
+    }
+
+    /// Creates an `UpdateSectionCommand` for each binary of an artifact dependency.
+    ///
+    /// See `LinkSection::patch_into_all_bin_deps()`.
+    pub fn patch_into_all_bin_deps(&self, dep_name: &str) -> Vec<UpdateSectionCommand> {
+        self.try_patch_into_all_bin_deps(dep_name)
+            .unwrap_or_else(|e| self.diagnostics.fail(e))
+    }
+
+    /// Like `patch_into_all_bin_deps()`, but returns an error instead of panicking.
+    pub fn try_patch_into_all_bin_deps(
+        &self,
+        dep_name: &str,
+    ) -> Result<Vec<UpdateSectionCommand>, VerShimError> {
+        let binaries = cargo_helpers::find_all_artifact_binaries(dep_name)?;
+        Ok(binaries
+            .into_iter()
+            .map(|(_, bin_path)| self.patch_into(bin_path))
+            .collect())
+    }
+
+    /// Reports the size of each member, and how much of the buffer they use, for
+    /// `LinkSection::dry_run()`.
+    pub(crate) fn report_usage(&self) {
+        let diag = &self.diagnostics;
+        let mut used = header_size(Member::COUNT);
+        for member in Member::ALL {
+            if let Some(value) = self.get(member) {
+                diag.info(format!("dry run, {:?}: {} bytes", member, value.len()));
+                used += value.len();
+            }
+        }
+        let present = self.member_data.iter().flatten().count();
+        if used <= self.buffer_size {
+            diag.info(format!(
+                "dry run, {} members use {} of {} bytes (header {} bytes, {} bytes free)",
+                present,
+                used,
+                self.buffer_size,
+                header_size(Member::COUNT),
+                self.buffer_size - used
+            ));
+        } else {
+            diag.info(format!(
+                "dry run, {} members need {} bytes, {} more than the {} byte buffer",
+                present,
+                used,
+                used - self.buffer_size,
+                self.buffer_size
+            ));
+            for (member, len) in self.fit(self.buffer_size, SectionFormat::LATEST).1 {
+                diag.info(format!(
+                    "dry run, would truncate {:?} to {} bytes",
+                    member, len
+                ));
+            }
+        }
+    }
+
+    fn write_section_to_path(&self, path: &Path) -> Result<PathBuf, VerShimError> {
+        let buffer = self.try_build_section_bytes()?;
+
+        // Write to file - if path is a directory, append ver_shim_data
+        let output_path = if path.is_dir() {
+            path.join("ver_shim_data")
+        } else {
+            path.to_path_buf()
+        };
+        if self.dry_run {
+            self.diagnostics.info(format!(
+                "dry run, would write {} bytes to {}",
+                buffer.len(),
+                output_path.display()
+            ));
+            return Ok(output_path);
+        }
+        output::write(&output_path, &buffer, !self.non_atomic).map_err(|e| {
+            VerShimError::io(
+                format!("failed to write section file {}", output_path.display()),
+                e,
+            )
+        })?;
+
+        Ok(output_path)
+    }
+}
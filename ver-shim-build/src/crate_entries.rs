@@ -0,0 +1,194 @@
+//! Key-value entries contributed by library crates in the dependency graph.
+//!
+//! A library crate registers its entries from its own `build.rs`, which writes them to
+//! `target/<profile>/ver_shim_entries/<crate_name>.entries`. The final patch step
+//! merges the files of the crates in its dependency graph into the `CrateEntries`
+//! member. The directory is shared by everything built in the target directory, so it
+//! can also hold files of unrelated crates, or of crates which are no longer depended on.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cargo_helpers::{self, cargo_rerun_if};
+use crate::diagnostics::{Diagnostic, Diagnostics};
+use crate::error::VerShimError;
+use crate::kv;
+use crate::output;
+
+/// Name of the directory (in the target profile dir) holding registered entries.
+const ENTRIES_DIR_NAME: &str = "ver_shim_entries";
+
+/// File extension of registered entry files.
+const ENTRIES_EXT: &str = "entries";
+
+/// Builder for key-value entries that a library crate contributes to the final binary.
+///
+/// Use this in the `build.rs` of a library crate to record things like a schema or
+/// config fingerprint. The application's patch step picks these up with
+/// `LinkSection::with_crate_entries()`, and they can be read at runtime with
+/// `ver_shim::crate_entry(crate_name, key)`.
+///
+/// ```ignore
+/// // build.rs of a library crate
+/// fn main() {
+///     ver_shim_build::CrateEntries::new()
+///         .with_entry("schema_hash", "3f2a9c")
+///         .register();
+/// }
+/// ```
+///
+/// Entries are keyed by the registering crate's name (`CARGO_PKG_NAME`), so different
+/// crates can't clobber each other's entries.
+#[derive(Default)]
+#[must_use]
+pub struct CrateEntries {
+    entries: BTreeMap<String, String>,
+    diagnostics: Diagnostics,
+}
+
+impl CrateEntries {
+    /// Creates a new empty `CrateEntries`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an entry. If the key was already added, the value is replaced.
+    ///
+    /// Keys must not contain `:` or NUL bytes, and values must not contain NUL bytes.
+    pub fn with_entry(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        let value = value.into();
+        if key.contains(':') {
+            self.diagnostics
+                .error(format!("crate entry key must not contain ':': {:?}", key));
+        }
+        kv::check_no_nul(&self.diagnostics, "crate entry key", &key);
+        kv::check_no_nul(&self.diagnostics, "crate entry value", &value);
+        self.entries.insert(key, value);
+        self
+    }
+
+    /// Sends info messages and errors to a callback, like
+    /// `LinkSection::with_diagnostics()`.
+    pub fn with_diagnostics(mut self, f: impl Fn(&Diagnostic) + Send + Sync + 'static) -> Self {
+        self.diagnostics = Diagnostics::new(f);
+        self
+    }
+
+    /// Writes the entries for the current crate, replacing any previously registered ones.
+    ///
+    /// Must be called from a build script. Returns the path to the written file.
+    /// Panics on failure, see `try_register()`.
+    pub fn register(self) -> PathBuf {
+        let diagnostics = self.diagnostics.clone();
+        self.try_register().unwrap_or_else(|e| diagnostics.fail(e))
+    }
+
+    /// Like `register()`, but returns an error instead of panicking.
+    pub fn try_register(self) -> Result<PathBuf, VerShimError> {
+        let crate_name = std::env::var("CARGO_PKG_NAME").map_err(|_| {
+            VerShimError::Env("CARGO_PKG_NAME not set - must be run from build.rs".into())
+        })?;
+        let dir = default_entries_dir()?;
+        fs::create_dir_all(&dir)
+            .map_err(|e| VerShimError::io(format!("failed to create {}", dir.display()), e))?;
+
+        let path = dir.join(format!("{}.{}", crate_name, ENTRIES_EXT));
+        output::write(&path, kv::encode(&self.entries), true)
+            .map_err(|e| VerShimError::io(format!("failed to write {}", path.display()), e))?;
+        self.diagnostics.info(format!(
+            "registered {} entries for {} in {}",
+            self.entries.len(),
+            crate_name,
+            path.display()
+        ));
+        Ok(path)
+    }
+}
+
+/// The directory where entries are registered, in the target profile dir.
+pub(crate) fn default_entries_dir() -> Result<PathBuf, VerShimError> {
+    Ok(cargo_helpers::target_profile_dir()?.join(ENTRIES_DIR_NAME))
+}
+
+/// Reads the registered entries in `dir` and encodes them as `CrateEntries` member data.
+///
+/// If `packages` is set, only the entries of those crates are read, and the others are
+/// skipped with an info message. Keys are prefixed with the crate name, as
+/// `crate_name:key`. Returns `None` if the directory doesn't exist or has no entries.
+pub(crate) fn collect(
+    dir: &Path,
+    packages: Option<&BTreeSet<String>>,
+    diag: &Diagnostics,
+) -> Result<Option<String>, VerShimError> {
+    cargo_rerun_if(&format!("changed={}", dir.display()));
+
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) => {
+            diag.info(format!(
+                "no crate entries found in {}: {}",
+                dir.display(),
+                e
+            ));
+            return Ok(None);
+        }
+    };
+
+    let mut merged = BTreeMap::new();
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some(ENTRIES_EXT) {
+            continue;
+        }
+        let Some(crate_name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if let Some(packages) = packages
+            && !packages.contains(crate_name)
+        {
+            diag.info(format!(
+                "skipping crate entries of {}, which is not in the dependency graph",
+                crate_name
+            ));
+            continue;
+        }
+        let data = fs::read_to_string(&path)
+            .map_err(|e| VerShimError::io(format!("failed to read {}", path.display()), e))?;
+        for (key, value) in kv::decode(&data) {
+            merged.insert(format!("{}:{}", crate_name, key), value);
+        }
+    }
+
+    if merged.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(kv::encode(&merged)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_crates_in_the_graph_are_merged() {
+        let dir =
+            std::env::temp_dir().join(format!("ver_shim_crate_entries_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let entries = |key: &str| kv::encode(&BTreeMap::from([(key.to_string(), "1".to_string())]));
+        fs::write(dir.join("my-lib.entries"), entries("schema_hash")).unwrap();
+        // Registered by a crate which is no longer a dependency, or by another binary
+        fs::write(dir.join("old-lib.entries"), entries("stale")).unwrap();
+
+        let packages = BTreeSet::from(["my-app".to_string(), "my-lib".to_string()]);
+        let merged = collect(&dir, Some(&packages), &Diagnostics::default()).unwrap();
+        let merged = kv::decode(&merged.unwrap());
+        assert_eq!(merged.keys().collect::<Vec<_>>(), ["my-lib:schema_hash"]);
+
+        let merged = collect(&dir, None, &Diagnostics::default()).unwrap();
+        assert_eq!(kv::decode(&merged.unwrap()).len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
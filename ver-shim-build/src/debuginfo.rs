@@ -0,0 +1,85 @@
+//! Split-debuginfo support for patched binaries.
+//!
+//! The GNU build-id is computed by the linker and stored in the `.note.gnu.build-id`
+//! note. Patching the version section doesn't touch that note, so the build-id of the
+//! patched binary is the same as that of the unpatched binary. However, debuginfod and
+//! symbol servers match the stripped binary and the debug file by build-id, so both
+//! must be derived from the *patched* binary, or the debug file will describe stale
+//! section contents. The flow is therefore:
+//!
+//! 1. Patch the version section into the (unstripped) binary.
+//! 2. `llvm-objcopy --only-keep-debug` to extract the debug file.
+//! 3. `llvm-objcopy --strip-debug --add-gnu-debuglink` to strip the binary.
+//! 4. Check that the build-ids of the stripped binary and the debug file match.
+
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::Diagnostics;
+use crate::error::VerShimError;
+use crate::llvm_tools::LlvmTools;
+use crate::native;
+
+/// Returns the path of a debug file relative to a debug directory, in the
+/// `.build-id/xx/yyyy.debug` layout used by gdb, debuginfod, and symbol servers.
+///
+/// Returns `None` if the build-id is too short to be valid.
+pub fn build_id_debug_path(build_id: &str) -> Option<PathBuf> {
+    if build_id.len() < 3 || !build_id.is_char_boundary(2) {
+        return None;
+    }
+    let (dir, file) = build_id.split_at(2);
+    Some(
+        Path::new(".build-id")
+            .join(dir)
+            .join(format!("{}.debug", file)),
+    )
+}
+
+/// Splits the debug info out of an already patched binary.
+///
+/// Writes the debug file to `debug_path`, then strips `bin_path` in place.
+/// Returns an error on failure, or if the build-ids of the two files don't match.
+pub(crate) fn split(
+    llvm: &LlvmTools,
+    bin_path: &Path,
+    debug_path: &Path,
+    diag: &Diagnostics,
+) -> Result<(), VerShimError> {
+    llvm.only_keep_debug(bin_path, debug_path)?;
+    llvm.strip_debug(bin_path, bin_path, debug_path)?;
+
+    match (build_id(bin_path)?, build_id(debug_path)?) {
+        (Some(bin_id), Some(debug_id)) if bin_id == debug_id => {
+            diag.info(format!(
+                "split debug info to {} (build-id {})",
+                debug_path.display(),
+                bin_id
+            ));
+        }
+        (Some(bin_id), Some(debug_id)) => {
+            return Err(VerShimError::Check(format!(
+                "build-id mismatch after splitting debug info: {} has {}, {} has {}",
+                bin_path.display(),
+                bin_id,
+                debug_path.display(),
+                debug_id
+            )));
+        }
+        _ => {
+            diag.warning(format!(
+                "{} has no GNU build-id, debuginfod and symbol servers won't be able to match \
+                 it with {}. Link with `-C link-arg=-Wl,--build-id` to add one.",
+                bin_path.display(),
+                debug_path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Gets the GNU build-id of a file, see `native::build_id()`.
+fn build_id(path: &Path) -> Result<Option<String>, VerShimError> {
+    let data = std::fs::read(path)
+        .map_err(|e| VerShimError::io(format!("failed to read {}", path.display()), e))?;
+    native::build_id(&data)
+}
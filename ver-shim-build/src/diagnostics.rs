@@ -0,0 +1,128 @@
+//! Diagnostics emitted while building and patching version sections.
+//!
+//! By default, info messages go to stderr, warnings become `cargo::warning` directives
+//! (or go to stderr outside of a build script), and errors panic. Tools which embed
+//! `ver-shim-build` outside of `build.rs` can route these into their own logging
+//! with `LinkSection::with_diagnostics()`.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::cargo_helpers::cargo_warning;
+use crate::error::VerShimError;
+
+/// The severity of a [`Diagnostic`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Progress information, such as the values being embedded.
+    Info,
+    /// Something went wrong, but the build can continue (e.g. git is not available).
+    Warning,
+    /// The build can't continue. ver-shim-build panics after reporting this.
+    Error,
+}
+
+/// A message emitted by `ver-shim-build`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The severity of the message.
+    pub level: Level,
+    /// The message, without a `ver-shim-build:` prefix.
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level {
+            Level::Info => write!(f, "ver-shim-build: {}", self.message),
+            Level::Warning => write!(f, "ver-shim-build: warning: {}", self.message),
+            Level::Error => write!(f, "ver-shim-build: error: {}", self.message),
+        }
+    }
+}
+
+/// A user-provided diagnostics callback.
+type Hook = Arc<dyn Fn(&Diagnostic) + Send + Sync>;
+
+/// Where diagnostics are sent.
+#[derive(Clone, Default)]
+pub(crate) struct Diagnostics {
+    hook: Option<Hook>,
+}
+
+impl Diagnostics {
+    pub(crate) fn new(hook: impl Fn(&Diagnostic) + Send + Sync + 'static) -> Self {
+        Self {
+            hook: Some(Arc::new(hook)),
+        }
+    }
+
+    /// Reports progress information.
+    pub(crate) fn info(&self, message: impl Into<String>) {
+        let message = message.into();
+        match self.hook {
+            Some(ref hook) => hook(&Diagnostic {
+                level: Level::Info,
+                message,
+            }),
+            None => eprintln!("ver-shim-build: {}", message),
+        }
+    }
+
+    /// Reports a problem which the build can continue past.
+    pub(crate) fn warning(&self, message: impl Into<String>) {
+        let message = message.into();
+        match self.hook {
+            Some(ref hook) => hook(&Diagnostic {
+                level: Level::Warning,
+                message,
+            }),
+            None => cargo_warning(&format!("ver-shim-build: {}", message)),
+        }
+    }
+
+    /// Reports an error, then panics.
+    ///
+    /// The panicking builder API has no way to return errors, so a hook sees the error
+    /// before the panic unwinds. The `try_*` methods return a `VerShimError` instead.
+    #[track_caller]
+    pub(crate) fn error(&self, message: impl Into<String>) -> ! {
+        let message = message.into();
+        if let Some(ref hook) = self.hook {
+            hook(&Diagnostic {
+                level: Level::Error,
+                message: message.clone(),
+            });
+        }
+        panic!("ver-shim-build: {}", message);
+    }
+
+    /// Reports an error from a `try_*` method, then panics. See `error()`.
+    #[track_caller]
+    pub(crate) fn fail(&self, err: VerShimError) -> ! {
+        self.error(err.to_string())
+    }
+
+    /// Reports a problem at the given level. Returns it as an error if the level is `Error`.
+    pub(crate) fn report(&self, level: Level, err: VerShimError) -> Result<(), VerShimError> {
+        match level {
+            Level::Info => self.info(err.to_string()),
+            Level::Warning => self.warning(err.to_string()),
+            Level::Error => return Err(err),
+        }
+        Ok(())
+    }
+
+    /// Returns the error if `fail_on_error` is set, otherwise reports it as a warning.
+    pub(crate) fn error_or_warning(
+        &self,
+        fail_on_error: bool,
+        err: VerShimError,
+    ) -> Result<(), VerShimError> {
+        if fail_on_error {
+            return Err(err);
+        }
+        self.warning(err.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+//! Checks of the segment layout of ELF binaries, for Android shared libraries.
+//!
+//! The Android dynamic loader maps the `PT_LOAD` segments of a `.so` with `mmap`, straight
+//! from the APK when it is stored uncompressed, so each segment must be aligned to the page
+//! size of the device, in the file as well as in memory. Devices with 16 KiB pages need
+//! libraries linked with `-z max-page-size=16384`.
+//!
+//! llvm-objcopy writes the output file anew, and may move sections around when it does, so
+//! Android libraries are patched in place instead, which can't change the layout.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use object::read::elf::{FileHeader, ProgramHeader};
+use object::{Endianness, FileKind, Object, elf};
+
+/// The section of the note identifying a binary built with the Android NDK.
+const ANDROID_IDENT_SECTION: &str = ".note.android.ident";
+
+/// A `PT_LOAD` program header.
+struct LoadSegment {
+    offset: u64,
+    vaddr: u64,
+    align: u64,
+}
+
+/// Returns true if the data is an ELF binary built for Android.
+pub fn is_android(data: &[u8]) -> bool {
+    object::File::parse(data).is_ok_and(|file| {
+        file.format() == object::BinaryFormat::Elf
+            && file.section_by_name(ANDROID_IDENT_SECTION).is_some()
+    })
+}
+
+/// Returns true if the file at `path` is an ELF binary built for Android.
+pub fn is_android_file(path: &Path) -> io::Result<bool> {
+    Ok(is_android(&fs::read(path)?))
+}
+
+/// Returns true if the data is an ELF file.
+pub fn is_elf(data: &[u8]) -> bool {
+    matches!(FileKind::parse(data), Ok(FileKind::Elf32 | FileKind::Elf64))
+}
+
+fn load_segments(data: &[u8]) -> Result<Vec<LoadSegment>, String> {
+    match FileKind::parse(data).map_err(|e| e.to_string())? {
+        FileKind::Elf32 => load_segments_of::<elf::FileHeader32<Endianness>>(data),
+        FileKind::Elf64 => load_segments_of::<elf::FileHeader64<Endianness>>(data),
+        _ => Err("not an ELF file".to_string()),
+    }
+}
+
+fn load_segments_of<Elf: FileHeader<Endian = Endianness>>(
+    data: &[u8],
+) -> Result<Vec<LoadSegment>, String> {
+    let header = Elf::parse(data).map_err(|e| e.to_string())?;
+    let endian = header.endian().map_err(|e| e.to_string())?;
+    let segments = header
+        .program_headers(endian, data)
+        .map_err(|e| e.to_string())?;
+    Ok(segments
+        .iter()
+        .filter(|segment| segment.p_type(endian) == elf::PT_LOAD)
+        .map(|segment| LoadSegment {
+            offset: segment.p_offset(endian).into(),
+            vaddr: segment.p_vaddr(endian).into(),
+            align: segment.p_align(endian).into(),
+        })
+        .collect())
+}
+
+/// Checks that every `PT_LOAD` segment of an ELF binary can be mapped on a device with
+/// the given page size: it must be aligned to at least `page_size`, and its file offset
+/// and address must be congruent modulo its alignment.
+///
+/// `page_size` must be a power of two.
+pub fn check_alignment(data: &[u8], page_size: u64) -> Result<(), String> {
+    for (index, segment) in load_segments(data)?.iter().enumerate() {
+        if segment.align < page_size {
+            return Err(format!(
+                "LOAD segment {} is aligned to {} bytes, less than the {} byte page size \
+                 (link with -z max-page-size={})",
+                index, segment.align, page_size, page_size
+            ));
+        }
+        if segment.offset % segment.align != segment.vaddr % segment.align {
+            return Err(format!(
+                "LOAD segment {} has file offset {:#x} and address {:#x}, \
+                 which are not congruent modulo its {} byte alignment",
+                index, segment.offset, segment.vaddr, segment.align
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,475 @@
+//! Member values loaded from a file, see `LinkSection::with_env_file()`.
+//!
+//! Two formats are read:
+//!
+//! - dotenv: `KEY=value` lines, as written by `CollectedSection::write_build_args()` or
+//!   `ver-shim read --format env`. Keys are member names in upper snake case, with or
+//!   without a `VER_SHIM_` prefix, and each entry of a member which holds a map has a key
+//!   of its own, see `git_env::entry_name()`. Values which start with a quote are unquoted
+//!   like in a shell, others are taken as they are.
+//! - JSON: an object, as printed by `ver-shim read --json`. Keys are member names in
+//!   snake case, and members which hold a map are objects.
+//!
+//! Files ending in `.json`, or starting with `{`, are JSON.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use heck::ToShoutySnakeCase;
+use ver_shim::Member;
+
+use crate::VerShimError;
+use crate::diagnostics::Diagnostics;
+use crate::git_env;
+use crate::kv;
+
+/// Reads the member values in a file.
+///
+/// Members which hold a map are returned in their encoded form. Keys which aren't
+/// members, and members which `ver-shim-build` computes itself, are skipped with a
+/// warning.
+pub(crate) fn read(path: &Path, diag: &Diagnostics) -> Result<Vec<(Member, String)>, VerShimError> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| VerShimError::io(format!("failed to read {}", path.display()), e))?;
+    let invalid = |message: String| {
+        VerShimError::Config(format!(
+            "{} is not a valid env file: {}",
+            path.display(),
+            message
+        ))
+    };
+    let is_json =
+        path.extension().is_some_and(|ext| ext == "json") || data.trim_start().starts_with('{');
+    let entries = if is_json {
+        parse_json(&data).map_err(invalid)?
+    } else {
+        parse_dotenv(&data).map_err(invalid)?
+    };
+
+    if let Some((key, _)) = entries.iter().find(|(_, value)| value.contains_nul()) {
+        return Err(invalid(format!("{} contains a NUL byte", key)));
+    }
+
+    let mut values = Vec::new();
+    let mut maps: [Option<BTreeMap<String, String>>; Member::COUNT] =
+        [const { None }; Member::COUNT];
+    for (key, value) in entries {
+        let name = key.to_shouty_snake_case();
+        let name = name.strip_prefix("VER_SHIM_").unwrap_or(&name);
+        let member = Member::ALL
+            .into_iter()
+            .find(|member| member.name().to_ascii_uppercase() == name);
+        // In dotenv files, each entry of a map is a variable of its own, e.g.
+        // CUSTOM_KV_DEPLOY_ENV, as written by `ver-shim read --format env`
+        let (member, value) = match (member, value) {
+            (None, Value::String(value)) => {
+                match git_env::parse_entry_name(key.strip_prefix("VER_SHIM_").unwrap_or(&key)) {
+                    Some((member, entry)) => {
+                        (Some(member), Value::Map(BTreeMap::from([(entry, value)])))
+                    }
+                    None => (None, Value::String(value)),
+                }
+            }
+            other => other,
+        };
+        match (member, value) {
+            (Some(Member::Sbom | Member::Signature | Member::SelfHash), _) => {
+                diag.warning(format!(
+                    "{}: {} is computed when patching, ignoring it",
+                    path.display(),
+                    key
+                ));
+            }
+            (Some(member), Value::Map(map)) if member.is_map() => {
+                maps[member as usize].get_or_insert_default().extend(map);
+            }
+            (Some(member), Value::String(value)) if !member.is_map() => {
+                values.push((member, value));
+            }
+            (Some(_), _) => {
+                diag.warning(format!(
+                    "{}: {} has the wrong type of value, ignoring it",
+                    path.display(),
+                    key
+                ));
+            }
+            (None, _) => {
+                diag.warning(format!(
+                    "{}: unknown key {}, ignoring it",
+                    path.display(),
+                    key
+                ));
+            }
+        }
+    }
+    for (member, map) in Member::ALL.into_iter().zip(maps) {
+        if let Some(map) = map {
+            values.push((member, kv::encode(&map)));
+        }
+    }
+    if let Some((_, value)) = values
+        .iter()
+        .find(|(member, value)| *member == Member::GitDirty && value != "true" && value != "false")
+    {
+        return Err(invalid(format!(
+            "git_dirty must be true or false, not {:?}",
+            value
+        )));
+    }
+    Ok(values)
+}
+
+/// A value in an env file.
+enum Value {
+    String(String),
+    Map(BTreeMap<String, String>),
+}
+
+impl Value {
+    /// Checks for NUL bytes, which are used as separators in the section.
+    fn contains_nul(&self) -> bool {
+        match self {
+            Value::String(value) => value.contains('\0'),
+            Value::Map(map) => map
+                .iter()
+                .any(|(key, value)| key.contains('\0') || value.contains('\0')),
+        }
+    }
+}
+
+/// Quotes a value for a dotenv file, if a shell would split or interpret it, e.g.
+/// `'it'\''s'`. `parse_dotenv()` reads the value back as it was, line breaks included.
+pub(crate) fn quote(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"_-.,:/@+=%".contains(&b));
+    if is_plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// Parses `KEY=value` lines, skipping blank lines, comments, and an `export ` prefix.
+fn parse_dotenv(data: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut entries = Vec::new();
+    let mut rest = data;
+    let mut line_no = 0;
+    while !rest.is_empty() {
+        line_no += 1;
+        let (line, next) = rest.split_once('\n').unwrap_or((rest, ""));
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            rest = next;
+            continue;
+        }
+        let Some(eq) = line.find('=') else {
+            return Err(format!("line {} is not KEY=value", line_no));
+        };
+        let key = line[..eq].trim();
+        let key = key
+            .strip_prefix("export ")
+            .unwrap_or(key)
+            .trim()
+            .to_string();
+        // Unquoted values are taken as they are
+        let value = rest[eq + 1..].trim_start_matches([' ', '\t']);
+        if !value.starts_with(['\'', '"']) {
+            let value = line[eq + 1..].trim().to_string();
+            entries.push((key, Value::String(value)));
+            rest = next;
+            continue;
+        }
+        // A quoted value may continue on the following lines. It is unquoted from the
+        // data as it is, so that the spaces and line breaks in it are kept.
+        let (unquoted, len) =
+            unquote(value).ok_or_else(|| format!("unterminated quote on line {}", line_no))?;
+        line_no += value[..len].matches('\n').count().saturating_sub(1);
+        rest = &value[len..];
+        entries.push((key, Value::String(unquoted)));
+    }
+    Ok(entries)
+}
+
+/// Removes shell quoting from a value, e.g. `'it'\''s'`, up to the end of the line it
+/// ends on. Returns the value and the length of the data it took up, including the line
+/// break, or `None` if a quote is unterminated.
+fn unquote(data: &str) -> Option<(String, usize)> {
+    let mut out = String::new();
+    // The length of `out` without trailing unquoted whitespace
+    let mut end = 0;
+    let mut chars = data.char_indices();
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '\n' => {
+                out.truncate(end);
+                return Some((out, pos + 1));
+            }
+            '\'' => loop {
+                match chars.next()?.1 {
+                    '\'' => break,
+                    c => out.push(c),
+                }
+            },
+            '"' => loop {
+                match chars.next()?.1 {
+                    '"' => break,
+                    '\\' => match chars.next()?.1 {
+                        'n' => out.push('\n'),
+                        't' => out.push('\t'),
+                        'r' => out.push('\r'),
+                        c => out.push(c),
+                    },
+                    c => out.push(c),
+                }
+            },
+            '\\' => out.push(chars.next()?.1),
+            c => out.push(c),
+        }
+        if !c.is_whitespace() {
+            end = out.len();
+        }
+    }
+    out.truncate(end);
+    Some((out, data.len()))
+}
+
+/// Parses a JSON object whose values are strings, booleans, numbers, null (which is
+/// skipped), or objects of strings.
+fn parse_json(data: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut parser = JsonParser {
+        data: data.as_bytes(),
+        pos: 0,
+    };
+    let mut entries = Vec::new();
+    parser.object(|parser, key| {
+        let value = match parser.peek() {
+            Some(b'{') => {
+                let mut map = BTreeMap::new();
+                parser.object(|parser, key| {
+                    map.insert(key, parser.scalar()?.ok_or("null in an object")?);
+                    Ok(())
+                })?;
+                Value::Map(map)
+            }
+            _ => match parser.scalar()? {
+                Some(value) => Value::String(value),
+                None => return Ok(()),
+            },
+        };
+        entries.push((key, value));
+        Ok(())
+    })?;
+    if parser.peek().is_some() {
+        return Err("trailing data after the object".into());
+    }
+    Ok(entries)
+}
+
+struct JsonParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    /// Skips whitespace, and returns the next byte.
+    fn peek(&mut self) -> Option<u8> {
+        while self.data.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+        self.data.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() != Some(byte) {
+            return Err(format!("expected '{}' at byte {}", byte as char, self.pos));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Parses an object, calling `entry` with the parser positioned at each value.
+    fn object(
+        &mut self,
+        mut entry: impl FnMut(&mut Self, String) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.expect(b'{')?;
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            let key = self.string()?;
+            self.expect(b':')?;
+            entry(self, key)?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(format!("expected ',' or '}}' at byte {}", self.pos)),
+            }
+        }
+    }
+
+    /// Parses a string, boolean, or number as a string, or null as `None`.
+    fn scalar(&mut self) -> Result<Option<String>, String> {
+        if self.peek() == Some(b'"') {
+            return self.string().map(Some);
+        }
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_alphanumeric() || b"+-.".contains(b))
+        {
+            self.pos += 1;
+        }
+        let token = std::str::from_utf8(&self.data[start..self.pos]).unwrap();
+        match token {
+            "null" => Ok(None),
+            "true" | "false" => Ok(Some(token.to_string())),
+            _ if !token.is_empty() && token.parse::<f64>().is_ok() => Ok(Some(token.to_string())),
+            _ => Err(format!("expected a value at byte {}", start)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut out = Vec::new();
+        loop {
+            let Some(&byte) = self.data.get(self.pos) else {
+                return Err("unterminated string".into());
+            };
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escape) = self.data.get(self.pos) else {
+                        return Err("unterminated string".into());
+                    };
+                    self.pos += 1;
+                    let c = match escape {
+                        b'n' => '\n',
+                        b't' => '\t',
+                        b'r' => '\r',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => self.unicode_escape()?,
+                        b'"' | b'\\' | b'/' => escape as char,
+                        _ => return Err(format!("invalid escape at byte {}", self.pos - 1)),
+                    };
+                    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                _ => out.push(byte),
+            }
+        }
+        String::from_utf8(out).map_err(|_| "invalid UTF-8 in a string".into())
+    }
+
+    /// Parses the digits of a `\u` escape, including a following low surrogate.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if self.data.get(self.pos..self.pos + 2) != Some(b"\\u") {
+                return Err(format!("unpaired surrogate at byte {}", self.pos));
+            }
+            self.pos += 2;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(format!("unpaired surrogate at byte {}", self.pos));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| format!("invalid \\u escape at byte {}", self.pos))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .data
+            .get(self.pos..self.pos + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| format!("invalid \\u escape at byte {}", self.pos))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::{CollectedSection, LinkSection, SectionData};
+
+    fn collect(section: LinkSection) -> CollectedSection {
+        section.with_diagnostics(|_| {}).try_collect().unwrap()
+    }
+
+    /// Collects values which need quoting or escaping, and map keys which only differ in
+    /// punctuation or case.
+    fn collected() -> CollectedSection {
+        collect(
+            LinkSection::new()
+                .with_member_value(Member::GitSha, "0123456789abcdef0123456789abcdef01234567")
+                .with_member_value(Member::GitDirty, "false")
+                .with_member_value(Member::GitBranch, " padded ")
+                .with_member_value(
+                    Member::GitCommitMsg,
+                    "it's \"fixed\"\r\n\n  $HOME `ls` \\ # not a comment\n",
+                )
+                .with_custom_kv("deploy-env", "prod")
+                .with_custom_kv("deploy_env", "")
+                .with_custom_kv("Key", "a\nb"),
+        )
+    }
+
+    /// Checks that loading the file at `path` gives the values of `expected`.
+    fn assert_loads_to(path: &Path, expected: &CollectedSection) {
+        let loaded = collect(LinkSection::new().with_env_file(path));
+        for member in Member::ALL {
+            assert_eq!(loaded.get(member), expected.get(member), "{:?}", member);
+        }
+    }
+
+    #[test]
+    fn dotenv_round_trip() {
+        let dir = fixtures::temp_dir("dotenv_round_trip");
+        let section = collected();
+        let build_args = section.write_build_args(dir.join("build.env"));
+        assert_loads_to(&build_args, &section);
+
+        let data = SectionData::parse(&section.build_section_bytes()).unwrap();
+        let env = dir.join("read.env");
+        fs::write(&env, data.to_env()).unwrap();
+        assert_loads_to(&env, &section);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let dir = fixtures::temp_dir("json_round_trip");
+        let section = collected();
+        let data = SectionData::parse(&section.build_section_bytes()).unwrap();
+        let json = dir.join("read.json");
+        fs::write(&json, data.to_json()).unwrap();
+        assert_loads_to(&json, &section);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("v1.2.3-rc.1+build"), "v1.2.3-rc.1+build");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+
+        let (value, len) = unquote("'a\r\n b' \"c\\td\" \r\nNEXT=1").unwrap();
+        assert_eq!(value, "a\r\n b c\td");
+        assert_eq!(len, "'a\r\n b' \"c\\td\" \r\n".len());
+        assert_eq!(unquote("'a\nb"), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! Errors returned by the fallible `try_*` methods.
+//!
+//! The builder methods without the `try_` prefix report these errors through the
+//! diagnostics hook and then panic, which is usually what a build script wants.
+
+use std::fmt;
+use std::io;
+
+use ver_shim::Member;
+
+/// An error from `ver-shim-build`.
+///
+/// Returned by the `try_*` methods, e.g. `LinkSection::try_collect()` and
+/// `UpdateSectionCommand::try_write_to()`. Problems which the build can continue past
+/// are still reported as warnings through the diagnostics hook, not returned.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum VerShimError {
+    /// A git command failed, with `fail_on_error()` or `reproducible()`.
+    Git(String),
+    /// An environment variable is missing or invalid, e.g. `OUT_DIR` outside of a build
+    /// script, or a `VER_SHIM_BUILD_TIME` which can't be parsed.
+    Env(String),
+    /// `cargo metadata` failed, with `fail_on_error()` or `reproducible()`, see
+    /// `LinkSection::with_workspace_members()` and `LinkSection::with_lockfile_hash()`.
+    Cargo(String),
+    /// The builder is misconfigured, e.g. no version info is enabled.
+    Config(String),
+    /// Neither the LLVM tools nor GNU `objcopy` were found.
+    Objcopy(String),
+    /// `llvm-objcopy` exited with an error.
+    ToolFailed(ToolFailure),
+    /// The version data doesn't fit in the section.
+    BufferOverflow {
+        /// The number of bytes the version data needs.
+        needed: usize,
+        /// The size of the section.
+        buffer_size: usize,
+        /// The byte length of each present member, largest first.
+        members: Vec<(Member, usize)>,
+    },
+    /// The version section exists in the binary, but can't be patched, e.g. because it
+    /// has no data in the file.
+    MissingSection(String),
+    /// The binary or image to patch is malformed, or can't be patched consistently.
+    InvalidBinary(String),
+    /// A check failed: timestamps (see `LinkSection::check_timestamps()`), a golden file,
+    /// the build-ids after splitting debug info, or the segment alignment (see
+    /// `UpdateSectionCommand::check_page_alignment()`).
+    Check(String),
+    /// Reading or writing a file failed.
+    Io {
+        /// What was being done, e.g. `failed to read target/release/my-bin`.
+        context: String,
+        /// The underlying error.
+        source: io::Error,
+    },
+}
+
+impl VerShimError {
+    /// Creates an `Io` error.
+    pub(crate) fn io(context: impl Into<String>, source: io::Error) -> Self {
+        Self::Io {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for VerShimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Git(msg)
+            | Self::Env(msg)
+            | Self::Cargo(msg)
+            | Self::Config(msg)
+            | Self::Objcopy(msg)
+            | Self::MissingSection(msg)
+            | Self::InvalidBinary(msg)
+            | Self::Check(msg) => f.write_str(msg),
+            Self::BufferOverflow {
+                needed,
+                buffer_size,
+                members,
+            } => {
+                write!(
+                    f,
+                    "section data too large ({} bytes, max {})",
+                    needed, buffer_size
+                )?;
+                for (member, len) in members {
+                    write!(f, "\n  {:?}: {} bytes", member, len)?;
+                }
+                write!(
+                    f,
+                    "\nUse with_buffer_size() or set VER_SHIM_BUFFER_SIZE env var to increase, \
+                     or truncate_overflow() to clip members that are not critical."
+                )
+            }
+            Self::ToolFailed(failure) => failure.fmt(f),
+            Self::Io { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for VerShimError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A failed run of `llvm-objcopy`.
+///
+/// This has the full command line and stderr, so that it can be pasted into a bug report
+/// as is, and the most likely cause of the failure if it could be determined.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ToolFailure {
+    /// The command line that was run.
+    pub command: String,
+    /// How the tool exited, e.g. `exit status: 1`.
+    pub status: String,
+    /// What the tool printed to stderr.
+    pub stderr: String,
+    /// The most likely cause, if it could be determined.
+    pub cause: Option<SuspectedCause>,
+}
+
+impl fmt::Display for ToolFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` failed ({})", self.command, self.status)?;
+        let stderr = self.stderr.trim_end();
+        if !stderr.is_empty() {
+            write!(f, "\nstderr:")?;
+            for line in stderr.lines() {
+                write!(f, "\n  {}", line)?;
+            }
+        }
+        if let Some(cause) = self.cause {
+            write!(f, "\nprobable cause: {}", cause)?;
+        }
+        Ok(())
+    }
+}
+
+/// The most likely cause of a `ToolFailure`, guessed from its stderr and the paths involved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SuspectedCause {
+    /// The section to update is not in the input binary.
+    MissingSection,
+    /// The output file or its directory is not writable.
+    ReadOnlyDestination,
+    /// The binary is for an architecture or in a format which the LLVM tools don't support.
+    ArchMismatch,
+}
+
+impl fmt::Display for SuspectedCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::MissingSection => {
+                "the section is not in the input binary. Check that ver-shim is linked in, \
+                 and that the linker didn't discard the section."
+            }
+            Self::ReadOnlyDestination => "the output file or its directory is not writable.",
+            Self::ArchMismatch => {
+                "the binary is for an architecture or in a format which these LLVM tools \
+                 don't support. Check that llvm-tools is installed for the toolchain that \
+                 built it, or set OBJCOPY (or use with_objcopy_path()) to an objcopy which \
+                 supports the target."
+            }
+        })
+    }
+}
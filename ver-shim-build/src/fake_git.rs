@@ -0,0 +1,260 @@
+//! Canned git responses for deterministic tests, from `VER_SHIM_FAKE_GIT`.
+//!
+//! When `VER_SHIM_FAKE_GIT` is set to the path of a file, `git` is never run. Instead,
+//! each git command is answered from that file, which has one response per line:
+//!
+//! ```text
+//! # git args => stdout
+//! rev-parse HEAD => 0123456789abcdef0123456789abcdef01234567
+//! describe --always --dirty => v1.2.0-3-g0123456
+//! rev-parse --abbrev-ref HEAD => !fatal: not a git repository
+//! ```
+//!
+//! A response starting with `!` makes the command fail with that message, as does a
+//! command with no matching line. `\n` in a response stands for a line break.
+//! Blank lines and lines starting with `#` are ignored.
+//!
+//! This lets `LinkSection` behavior (warnings, `fail_on_error()`, truncation, and the
+//! buffer layout) be tested without a real repository.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// The environment variable holding the path of the canned responses file.
+pub const FAKE_GIT_VAR: &str = "VER_SHIM_FAKE_GIT";
+
+/// Returns the path of the canned responses file, if `VER_SHIM_FAKE_GIT` is set.
+pub fn responses_path() -> Option<PathBuf> {
+    std::env::var_os(FAKE_GIT_VAR).map(PathBuf::from)
+}
+
+/// Returns the canned response for a git command, or `None` if `VER_SHIM_FAKE_GIT` is not set.
+///
+/// `Err` holds the failure message, if the command is set up to fail or has no response.
+pub fn response(args: &[&str]) -> Option<Result<String, String>> {
+    let path = responses_path()?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => return Some(Err(format!("failed to read {}: {}", path.display(), e))),
+    };
+
+    let cmd = args.join(" ");
+    for line in contents.lines() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once("=>") else {
+            continue;
+        };
+        if key.trim() != cmd {
+            continue;
+        }
+        let value = value.trim();
+        return Some(match value.strip_prefix('!') {
+            Some(msg) => Err(msg.trim().to_string()),
+            None => Ok(value.replace("\\n", "\n")),
+        });
+    }
+    Some(Err(format!(
+        "no response for 'git {}' in {}",
+        cmd,
+        path.display()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BranchSource, Level, LinkSection, Member, SectionData};
+    use std::sync::{Arc, Mutex};
+
+    /// `VER_SHIM_FAKE_GIT` is process-wide, so tests which set it run one at a time.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    /// Runs `f` with `VER_SHIM_FAKE_GIT` set to a file holding `responses`, passing it a
+    /// `LinkSection` whose warnings are returned along with the result of `f`.
+    fn with_fake_git<T>(
+        name: &str,
+        responses: &str,
+        f: impl FnOnce(LinkSection) -> T,
+    ) -> (T, Vec<String>) {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let path =
+            std::env::temp_dir().join(format!("ver_shim_fake_git_{}_{}", std::process::id(), name));
+        fs::write(&path, responses).unwrap();
+        // SAFETY: ENV_LOCK is held, and no other tests read the environment
+        unsafe { std::env::set_var(FAKE_GIT_VAR, &path) };
+
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let section = LinkSection::new().with_diagnostics({
+            let warnings = warnings.clone();
+            move |diag| {
+                if diag.level == Level::Warning {
+                    warnings.lock().unwrap().push(diag.message.clone());
+                }
+            }
+        });
+        let result = f(section);
+
+        // SAFETY: as above
+        unsafe { std::env::remove_var(FAKE_GIT_VAR) };
+        fs::remove_file(&path).unwrap();
+        let warnings = warnings.lock().unwrap().clone();
+        (result, warnings)
+    }
+
+    #[test]
+    fn responses() {
+        let responses = format!(
+            "# a comment\n\
+             \n\
+             rev-parse HEAD => {SHA}\n\
+             log -1 --format=%an%n%ae => Jane Doe\\njane@example.com\n\
+             rev-parse --abbrev-ref HEAD => !fatal: not a git repository\n"
+        );
+        let (results, _) = with_fake_git("responses", &responses, |_| {
+            [
+                response(&["rev-parse", "HEAD"]),
+                response(&["log", "-1", "--format=%an%n%ae"]),
+                response(&["rev-parse", "--abbrev-ref", "HEAD"]),
+                response(&["status", "--porcelain"]),
+            ]
+        });
+        assert_eq!(results[0], Some(Ok(SHA.to_string())));
+        assert_eq!(
+            results[1],
+            Some(Ok("Jane Doe\njane@example.com".to_string()))
+        );
+        assert_eq!(
+            results[2],
+            Some(Err("fatal: not a git repository".to_string()))
+        );
+        assert!(
+            matches!(&results[3], Some(Err(e)) if e.starts_with("no response for 'git status --porcelain'"))
+        );
+    }
+
+    #[test]
+    fn buffer_layout() {
+        let responses = format!(
+            "rev-parse HEAD => {SHA}\n\
+             rev-parse --abbrev-ref HEAD => main\n"
+        );
+        let (bytes, warnings) = with_fake_git("buffer_layout", &responses, |section| {
+            section
+                .with_git_sha()
+                .with_git_branch()
+                .with_git_branch_sources(&[BranchSource::Head])
+                .with_buffer_size(128)
+                .try_build_section_bytes()
+        });
+        let bytes = bytes.unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(bytes.len(), 128);
+
+        let data = SectionData::parse(&bytes).unwrap();
+        assert_eq!(data.get(Member::GitSha), Some(SHA));
+        assert_eq!(data.get(Member::GitBranch), Some("main"));
+        assert_eq!(data.get(Member::GitDescribe), None);
+        assert_eq!(data.written_buffer_size(), Some(128));
+        // The values are stored right after the header, and the rest is zeroed
+        let header_size = ver_shim::header_size(Member::COUNT);
+        let data_len = SHA.len() + "main".len();
+        assert_eq!(&bytes[header_size..header_size + SHA.len()], SHA.as_bytes());
+        assert!(bytes[header_size + data_len..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn git_failure_is_a_warning() {
+        let responses = format!(
+            "rev-parse HEAD => {SHA}\n\
+             rev-parse --abbrev-ref HEAD => !fatal: not a git repository\n"
+        );
+        let (bytes, warnings) = with_fake_git("git_failure", &responses, |section| {
+            section
+                .with_git_sha()
+                .with_git_branch()
+                .with_git_branch_sources(&[BranchSource::Head])
+                .try_build_section_bytes()
+        });
+        let data = SectionData::parse(&bytes.unwrap()).unwrap();
+        assert_eq!(data.get(Member::GitSha), Some(SHA));
+        assert_eq!(data.get(Member::GitBranch), None);
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("fatal: not a git repository"));
+    }
+
+    #[test]
+    fn fail_on_error() {
+        let responses = format!(
+            "rev-parse HEAD => {SHA}\n\
+             rev-parse --abbrev-ref HEAD => !fatal: not a git repository\n"
+        );
+        let (result, _) = with_fake_git("fail_on_error", &responses, |section| {
+            section
+                .with_git_sha()
+                .with_git_branch()
+                .with_git_branch_sources(&[BranchSource::Head])
+                .fail_on_error()
+                .try_build_section_bytes()
+        });
+        let err = result.unwrap_err();
+        assert!(
+            err.to_string().contains("fatal: not a git repository"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn fallback_with_fail_on_error() {
+        let responses = "rev-parse --abbrev-ref HEAD => !fatal: not a git repository\n";
+        let (bytes, warnings) = with_fake_git("fallback", responses, |section| {
+            section
+                .with_git_branch()
+                .with_git_branch_sources(&[BranchSource::Head])
+                .fail_on_error()
+                .with_fallback(Member::GitBranch, "unknown")
+                .try_build_section_bytes()
+        });
+        let data = SectionData::parse(&bytes.unwrap()).unwrap();
+        assert_eq!(data.get(Member::GitBranch), Some("unknown"));
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    }
+
+    #[test]
+    fn truncation() {
+        let msg = "x".repeat(200);
+        let responses = format!("log -1 --format=%s => {msg}\n");
+        let (bytes, warnings) = with_fake_git("truncation", &responses, |section| {
+            section
+                .with_git_commit_msg()
+                .with_buffer_size(128)
+                .truncate_overflow(Member::GitCommitMsg)
+                .try_build_section_bytes()
+        });
+        let bytes = bytes.unwrap();
+        assert_eq!(bytes.len(), 128);
+        let data = SectionData::parse(&bytes).unwrap();
+        let truncated = data.get(Member::GitCommitMsg).unwrap();
+        assert_eq!(truncated.len(), 128 - ver_shim::header_size(Member::COUNT));
+        assert!(msg.starts_with(truncated));
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    }
+
+    #[test]
+    fn overflow_without_truncation() {
+        let msg = "x".repeat(200);
+        let responses = format!("log -1 --format=%s => {msg}\n");
+        let (result, _) = with_fake_git("overflow", &responses, |section| {
+            section
+                .with_git_commit_msg()
+                .with_buffer_size(128)
+                .try_build_section_bytes()
+        });
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,289 @@
+//! Binaries built by hand for tests, so that they don't need a toolchain for each target.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Size of the version sections in the fixtures.
+pub const SECTION_SIZE: usize = 512;
+
+/// Creates a fresh, empty temporary directory for the test `name`.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("ver_shim_fixture_{}_{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The contents of an unpatched version section, as the `ver-shim` runtime leaves it.
+pub fn unpatched_section() -> Vec<u8> {
+    ver_shim::unpatched_buffer::<SECTION_SIZE>().to_vec()
+}
+
+/// Builds a little-endian 64-bit ELF relocatable object with the given sections, plus
+/// the null section and the section name table.
+pub fn elf_object(sections: &[(&str, &[u8])]) -> Vec<u8> {
+    const EHDR_LEN: usize = 64;
+    const SHDR_LEN: usize = 64;
+    const SHT_PROGBITS: u32 = 1;
+    const SHT_STRTAB: u32 = 3;
+    const SHF_ALLOC: u64 = 2;
+
+    let mut shstrtab = vec![0u8];
+    let mut data = vec![0u8; EHDR_LEN];
+    // (name, type, flags, offset, size)
+    let mut headers = vec![(0, 0, 0, 0, 0)];
+    for (name, contents) in sections {
+        let name_offset = shstrtab.len();
+        shstrtab.extend_from_slice(name.as_bytes());
+        shstrtab.push(0);
+        data.resize(data.len().next_multiple_of(8), 0);
+        headers.push((
+            name_offset,
+            SHT_PROGBITS,
+            SHF_ALLOC,
+            data.len(),
+            contents.len(),
+        ));
+        data.extend_from_slice(contents);
+    }
+    let name_offset = shstrtab.len();
+    shstrtab.extend_from_slice(b".shstrtab\0");
+    headers.push((name_offset, SHT_STRTAB, 0, data.len(), shstrtab.len()));
+    data.extend_from_slice(&shstrtab);
+    data.resize(data.len().next_multiple_of(8), 0);
+
+    let shoff = data.len();
+    for (name, kind, flags, offset, size) in &headers {
+        data.extend_from_slice(&(*name as u32).to_le_bytes());
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(&flags.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes()); // sh_addr
+        data.extend_from_slice(&(*offset as u64).to_le_bytes());
+        data.extend_from_slice(&(*size as u64).to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes()); // sh_link
+        data.extend_from_slice(&0u32.to_le_bytes()); // sh_info
+        data.extend_from_slice(&1u64.to_le_bytes()); // sh_addralign
+        data.extend_from_slice(&0u64.to_le_bytes()); // sh_entsize
+    }
+
+    let mut ehdr = Vec::with_capacity(EHDR_LEN);
+    ehdr.extend_from_slice(b"\x7fELF");
+    ehdr.extend_from_slice(&[2, 1, 1, 0]); // 64-bit, little-endian, version 1, System V
+    ehdr.extend_from_slice(&[0; 8]);
+    ehdr.extend_from_slice(&1u16.to_le_bytes()); // ET_REL
+    ehdr.extend_from_slice(&62u16.to_le_bytes()); // EM_X86_64
+    ehdr.extend_from_slice(&1u32.to_le_bytes());
+    ehdr.extend_from_slice(&0u64.to_le_bytes()); // e_entry
+    ehdr.extend_from_slice(&0u64.to_le_bytes()); // e_phoff
+    ehdr.extend_from_slice(&(shoff as u64).to_le_bytes());
+    ehdr.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+    ehdr.extend_from_slice(&(EHDR_LEN as u16).to_le_bytes());
+    ehdr.extend_from_slice(&0u16.to_le_bytes()); // e_phentsize
+    ehdr.extend_from_slice(&0u16.to_le_bytes()); // e_phnum
+    ehdr.extend_from_slice(&(SHDR_LEN as u16).to_le_bytes());
+    ehdr.extend_from_slice(&(headers.len() as u16).to_le_bytes());
+    ehdr.extend_from_slice(&(headers.len() as u16 - 1).to_le_bytes()); // e_shstrndx
+    data[..EHDR_LEN].copy_from_slice(&ehdr);
+    data
+}
+
+/// Builds an `ar` archive of the given members, with short names and no symbol table.
+pub fn ar_archive(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut data = b"!<arch>\n".to_vec();
+    for (name, contents) in members {
+        let header = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            format!("{}/", name),
+            0,
+            0,
+            0,
+            644,
+            contents.len()
+        );
+        assert_eq!(header.len(), 60, "member name {} is too long", name);
+        data.extend_from_slice(header.as_bytes());
+        data.extend_from_slice(contents);
+        if data.len() % 2 == 1 {
+            data.push(b'\n');
+        }
+    }
+    data
+}
+
+/// CPU type of x86-64 Mach-O binaries.
+pub const CPU_TYPE_X86_64: u32 = 0x0100_0007;
+/// CPU type of arm64 Mach-O binaries.
+pub const CPU_TYPE_ARM64: u32 = 0x0100_000c;
+
+/// Builds a 64-bit Mach-O executable with a `__DATA,__ver_shim_data` section, and a code
+/// signature load command if `signed`.
+pub fn macho(cputype: u32, section: &[u8], signed: bool) -> Vec<u8> {
+    const HEADER_LEN: usize = 32;
+    const SEGMENT_LEN: usize = 72;
+    const SECTION_LEN: usize = 80;
+    const SIGNATURE_LEN: usize = 16;
+
+    let ncmds = if signed { 2 } else { 1 };
+    let sizeofcmds = SEGMENT_LEN + SECTION_LEN + if signed { SIGNATURE_LEN } else { 0 };
+    let section_offset = (HEADER_LEN + sizeofcmds).next_multiple_of(16);
+    let signature_offset = section_offset + section.len();
+
+    let name = |name: &str| {
+        let mut bytes = [0u8; 16];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        bytes
+    };
+    let mut data = Vec::new();
+    data.extend_from_slice(&0xfeed_facfu32.to_le_bytes()); // MH_MAGIC_64
+    data.extend_from_slice(&cputype.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes()); // cpusubtype
+    data.extend_from_slice(&2u32.to_le_bytes()); // MH_EXECUTE
+    data.extend_from_slice(&(ncmds as u32).to_le_bytes());
+    data.extend_from_slice(&(sizeofcmds as u32).to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes()); // flags
+    data.extend_from_slice(&0u32.to_le_bytes()); // reserved
+
+    data.extend_from_slice(&0x19u32.to_le_bytes()); // LC_SEGMENT_64
+    data.extend_from_slice(&((SEGMENT_LEN + SECTION_LEN) as u32).to_le_bytes());
+    data.extend_from_slice(&name("__DATA"));
+    data.extend_from_slice(&0x1000u64.to_le_bytes()); // vmaddr
+    data.extend_from_slice(&(section.len() as u64).to_le_bytes()); // vmsize
+    data.extend_from_slice(&(section_offset as u64).to_le_bytes()); // fileoff
+    data.extend_from_slice(&(section.len() as u64).to_le_bytes()); // filesize
+    data.extend_from_slice(&3u32.to_le_bytes()); // maxprot
+    data.extend_from_slice(&3u32.to_le_bytes()); // initprot
+    data.extend_from_slice(&1u32.to_le_bytes()); // nsects
+    data.extend_from_slice(&0u32.to_le_bytes()); // flags
+
+    data.extend_from_slice(&name("__ver_shim_data"));
+    data.extend_from_slice(&name("__DATA"));
+    data.extend_from_slice(&0x1000u64.to_le_bytes()); // addr
+    data.extend_from_slice(&(section.len() as u64).to_le_bytes());
+    data.extend_from_slice(&(section_offset as u32).to_le_bytes());
+    data.extend_from_slice(&[0; 4 * 7]); // align, relocations, flags, reserved
+
+    if signed {
+        data.extend_from_slice(&0x1du32.to_le_bytes()); // LC_CODE_SIGNATURE
+        data.extend_from_slice(&(SIGNATURE_LEN as u32).to_le_bytes());
+        data.extend_from_slice(&(signature_offset as u32).to_le_bytes());
+        data.extend_from_slice(&16u32.to_le_bytes());
+    }
+
+    data.resize(section_offset, 0);
+    data.extend_from_slice(section);
+    if signed {
+        data.extend_from_slice(&[0xfa; 16]);
+    }
+    data
+}
+
+/// Builds a universal binary of the given architectures, as `(cputype, binary)` pairs.
+pub fn fat_binary(arches: &[(u32, &[u8])]) -> Vec<u8> {
+    const ALIGN: usize = 0x1000;
+
+    let mut data = Vec::new();
+    data.extend_from_slice(&0xcafe_babeu32.to_be_bytes()); // FAT_MAGIC
+    data.extend_from_slice(&(arches.len() as u32).to_be_bytes());
+    let mut offset = ALIGN;
+    for (cputype, binary) in arches {
+        data.extend_from_slice(&cputype.to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes()); // cpusubtype
+        data.extend_from_slice(&(offset as u32).to_be_bytes());
+        data.extend_from_slice(&(binary.len() as u32).to_be_bytes());
+        data.extend_from_slice(&(ALIGN.trailing_zeros()).to_be_bytes());
+        offset = (offset + binary.len()).next_multiple_of(ALIGN);
+    }
+    for (_, binary) in arches {
+        data.resize(data.len().next_multiple_of(ALIGN), 0);
+        data.extend_from_slice(binary);
+    }
+    data
+}
+
+/// Builds a WebAssembly module with a `producers` custom section, and a data section with
+/// an active segment holding the unpatched buffer behind the locator prefix.
+pub fn wasm_module() -> Vec<u8> {
+    let mut segment = ver_shim::LOCATOR_MAGIC.to_vec();
+    segment.extend_from_slice(&(SECTION_SIZE as u16).to_le_bytes());
+    segment.extend_from_slice(&unpatched_section());
+
+    let mut data = vec![1, 0]; // one segment, active in memory 0
+    data.extend_from_slice(&[0x41, 0x80, 0x08, 0x0b]); // i32.const 1024, end
+    data.extend_from_slice(&leb128(segment.len()));
+    data.extend_from_slice(&segment);
+
+    let mut producers = leb128("producers".len());
+    producers.extend_from_slice(b"producers");
+    producers.extend_from_slice(b"\0rustc");
+
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+    for (id, contents) in [(0u8, producers), (11, data)] {
+        module.push(id);
+        module.extend_from_slice(&leb128(contents.len()));
+        module.extend_from_slice(&contents);
+    }
+    module
+}
+
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return out;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Offset of the `CheckSum` field in the PE images of `pe_image()`.
+pub const PE_CHECKSUM_OFFSET: usize = 0x40 + 4 + 20 + 64;
+
+/// Builds a PE32+ image with a `.vershim` section, and `0x1234` as the header checksum.
+pub fn pe_image(section: &[u8]) -> Vec<u8> {
+    const PE_OFFSET: usize = 0x40;
+    const OPTIONAL_HEADER_LEN: usize = 240;
+    const HEADERS_LEN: usize = 0x200;
+
+    let raw_size = section.len().next_multiple_of(0x200);
+    let mut data = vec![0u8; HEADERS_LEN];
+    data[..2].copy_from_slice(b"MZ");
+    data[0x3c..0x40].copy_from_slice(&(PE_OFFSET as u32).to_le_bytes());
+
+    let mut headers = b"PE\0\0".to_vec();
+    headers.extend_from_slice(&0x8664u16.to_le_bytes()); // IMAGE_FILE_MACHINE_AMD64
+    headers.extend_from_slice(&1u16.to_le_bytes()); // NumberOfSections
+    headers.extend_from_slice(&[0; 12]); // time stamp, symbol table
+    headers.extend_from_slice(&(OPTIONAL_HEADER_LEN as u16).to_le_bytes());
+    headers.extend_from_slice(&0x22u16.to_le_bytes()); // executable, large address aware
+
+    let mut optional = vec![0u8; OPTIONAL_HEADER_LEN];
+    optional[..2].copy_from_slice(&0x20bu16.to_le_bytes()); // PE32+
+    optional[24..32].copy_from_slice(&0x1_4000_0000u64.to_le_bytes()); // ImageBase
+    optional[32..36].copy_from_slice(&0x1000u32.to_le_bytes()); // SectionAlignment
+    optional[36..40].copy_from_slice(&0x200u32.to_le_bytes()); // FileAlignment
+    optional[48..50].copy_from_slice(&6u16.to_le_bytes()); // MajorSubsystemVersion
+    let image_size = 0x1000 + raw_size.next_multiple_of(0x1000);
+    optional[56..60].copy_from_slice(&(image_size as u32).to_le_bytes());
+    optional[60..64].copy_from_slice(&(HEADERS_LEN as u32).to_le_bytes());
+    optional[64..68].copy_from_slice(&0x1234u32.to_le_bytes()); // CheckSum
+    optional[68..70].copy_from_slice(&3u16.to_le_bytes()); // IMAGE_SUBSYSTEM_WINDOWS_CUI
+    optional[108..112].copy_from_slice(&16u32.to_le_bytes()); // NumberOfRvaAndSizes
+    headers.extend_from_slice(&optional);
+
+    headers.extend_from_slice(b".vershim");
+    headers.extend_from_slice(&(section.len() as u32).to_le_bytes()); // VirtualSize
+    headers.extend_from_slice(&0x1000u32.to_le_bytes()); // VirtualAddress
+    headers.extend_from_slice(&(raw_size as u32).to_le_bytes()); // SizeOfRawData
+    headers.extend_from_slice(&(HEADERS_LEN as u32).to_le_bytes()); // PointerToRawData
+    headers.extend_from_slice(&[0; 12]); // relocations, line numbers
+    headers.extend_from_slice(&0x4000_0040u32.to_le_bytes()); // initialized, readable
+    data[PE_OFFSET..PE_OFFSET + headers.len()].copy_from_slice(&headers);
+
+    data.extend_from_slice(section);
+    data.resize(HEADERS_LEN + raw_size, 0);
+    data
+}
@@ -0,0 +1,58 @@
+//! Section format versions, and detecting which one a binary expects.
+//!
+//! `ver_shim` initializes its buffer with a version 2 header with zero members, so the
+//! unpatched section tells which format the runtime reads. Older versions of `ver_shim`
+//! initialize it to zeros, and only read version 1. Sections are written in the format
+//! the binary expects, so that a newer `ver-shim-build` can still patch older binaries.
+
+use ver_shim::{FORMAT_VERSION, SECTION_MAGIC};
+
+use crate::error::VerShimError;
+
+/// A version of the section format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectionFormat {
+    /// The number of members, the end offsets, the string data, then the buffer size.
+    V1,
+    /// A zero byte, magic, format version, number of members, buffer size, the end offsets,
+    /// then the string data.
+    V2,
+}
+
+impl SectionFormat {
+    /// The latest format, written when the binary is not at hand.
+    pub const LATEST: Self = Self::V2;
+
+    /// Reads the format version from the header of a section buffer, if it has one.
+    ///
+    /// Returns `Ok(None)` if the buffer has no version 2 header, i.e. it is either
+    /// all zeros or a version 1 section. Returns an error for an unknown format version.
+    pub fn from_header(buffer: &[u8]) -> Result<Option<Self>, String> {
+        if buffer.len() < 6 || buffer[0] != 0 || buffer[1..5] != SECTION_MAGIC {
+            return Ok(None);
+        }
+        match buffer[5] {
+            2 => Ok(Some(Self::V2)),
+            version => Err(format!(
+                "unknown section format version {}, this version of ver-shim-build supports \
+                 versions 1 to {}",
+                version, FORMAT_VERSION
+            )),
+        }
+    }
+
+    /// Detects the format to write to a section, from its current contents.
+    ///
+    /// An unpatched section of a binary built with an older `ver_shim` is all zeros, and
+    /// a section which was already patched for one has a version 1 header, so both mean
+    /// version 1.
+    pub fn detect(existing: &[u8]) -> Result<Self, VerShimError> {
+        match Self::from_header(existing) {
+            Ok(format) => Ok(format.unwrap_or(Self::V1)),
+            Err(e) => Err(VerShimError::InvalidBinary(format!(
+                "{}. Is ver-shim-build older than the ver-shim the binary was built with?",
+                e
+            ))),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Git data from libgit2, with the `git2` feature.
+
+use chrono::{DateTime, FixedOffset, TimeZone};
+use git2::{DescribeFormatOptions, DescribeOptions, Repository, Signature, StatusOptions, Time};
+
+use crate::git_provider::{DescribeOpts, GitProvider, sort_tags};
+
+/// Gets git data using libgit2, so `git` doesn't need to be installed.
+///
+/// The repository is discovered from the current directory, like the `git` command line
+/// tool does. Unlike [`GitCli`](crate::GitCli), this ignores `VER_SHIM_FAKE_GIT`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Git2Provider;
+
+impl Git2Provider {
+    /// Opens the repository containing the current directory.
+    fn repo(&self) -> Result<Repository, String> {
+        Repository::discover(".").map_err(|e| format!("failed to open git repository: {}", e))
+    }
+
+    /// Calls `f` with the commit at `HEAD`.
+    fn with_head<T>(
+        &self,
+        f: impl FnOnce(&git2::Commit) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let repo = self.repo()?;
+        let commit = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(|e| format!("failed to find HEAD commit: {}", e))?;
+        f(&commit)
+    }
+}
+
+impl GitProvider for Git2Provider {
+    fn sha(&self) -> Result<String, String> {
+        self.with_head(|commit| Ok(commit.id().to_string()))
+    }
+
+    fn describe(&self) -> Result<String, String> {
+        self.describe_with(&DescribeOpts::default())
+    }
+
+    fn describe_with(&self, opts: &DescribeOpts) -> Result<String, String> {
+        let repo = self.repo()?;
+        let mut describe_opts = DescribeOptions::new();
+        describe_opts.show_commit_oid_as_fallback(true);
+        if opts.tags {
+            describe_opts.describe_tags();
+        }
+        if let Some(ref pattern) = opts.match_pattern {
+            describe_opts.pattern(pattern);
+        }
+        let mut format_opts = DescribeFormatOptions::new();
+        format_opts.dirty_suffix(opts.dirty_suffix.as_deref().unwrap_or("-dirty"));
+        if let Some(abbrev) = opts.abbrev {
+            format_opts.abbreviated_size(abbrev);
+        }
+        repo.describe(&describe_opts)
+            .and_then(|describe| describe.format(Some(&format_opts)))
+            .map_err(|e| format!("failed to describe HEAD: {}", e))
+    }
+
+    fn branch(&self) -> Result<String, String> {
+        let repo = self.repo()?;
+        let head = repo
+            .head()
+            .map_err(|e| format!("failed to find HEAD: {}", e))?;
+        if !head.is_branch() {
+            return Ok("HEAD".to_string());
+        }
+        head.shorthand()
+            .map(str::to_string)
+            .ok_or_else(|| "branch name is not valid UTF-8".to_string())
+    }
+
+    fn commit_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.with_head(|commit| to_datetime(commit.author().when()))
+    }
+
+    fn commit_msg(&self) -> Result<String, String> {
+        self.with_head(|commit| {
+            commit
+                .summary()
+                .map(str::to_string)
+                .ok_or_else(|| "commit message is not valid UTF-8".to_string())
+        })
+    }
+
+    fn is_dirty(&self) -> Result<bool, String> {
+        let repo = self.repo()?;
+        let statuses = repo
+            .statuses(Some(
+                StatusOptions::new()
+                    .include_untracked(false)
+                    .include_ignored(false),
+            ))
+            .map_err(|e| format!("failed to get git status: {}", e))?;
+        Ok(!statuses.is_empty())
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        let repo = self.repo()?;
+        let head = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(|e| format!("failed to find HEAD commit: {}", e))?
+            .id();
+        let names = repo
+            .tag_names(None)
+            .map_err(|e| format!("failed to list git tags: {}", e))?;
+        let mut tags = Vec::new();
+        for name in names.iter().flatten() {
+            let target = repo
+                .revparse_single(&format!("refs/tags/{}", name))
+                .and_then(|object| object.peel_to_commit());
+            if target.is_ok_and(|commit| commit.id() == head) {
+                tags.push(name.to_string());
+            }
+        }
+        sort_tags(&mut tags);
+        Ok(tags)
+    }
+
+    fn commit_author(&self) -> Result<(String, String), String> {
+        self.with_head(|commit| identity(&commit.author()))
+    }
+
+    fn committer(&self) -> Result<(String, String), String> {
+        self.with_head(|commit| identity(&commit.committer()))
+    }
+
+    fn submodules(&self) -> Result<Vec<(String, String)>, String> {
+        let mut submodules = Vec::new();
+        add_submodules(&self.repo()?, "", &mut submodules)?;
+        Ok(submodules)
+    }
+
+    fn committer_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.with_head(|commit| to_datetime(commit.committer().when()))
+    }
+
+    fn remote_url(&self) -> Result<String, String> {
+        let repo = self.repo()?;
+        let remote = repo
+            .find_remote("origin")
+            .map_err(|e| format!("failed to find the origin remote: {}", e))?;
+        remote
+            .url()
+            .map(str::to_string)
+            .ok_or_else(|| "the origin remote URL is not valid UTF-8".to_string())
+    }
+}
+
+/// Converts a libgit2 time to a chrono DateTime in its original time zone.
+fn to_datetime(time: Time) -> Result<DateTime<FixedOffset>, String> {
+    FixedOffset::east_opt(time.offset_minutes() * 60)
+        .and_then(|offset| offset.timestamp_opt(time.seconds(), 0).single())
+        .ok_or_else(|| format!("invalid git timestamp {}", time.seconds()))
+}
+
+/// Adds the submodules of `repo` to `submodules`, with `prefix` before their paths, and
+/// then those of each submodule which is checked out.
+fn add_submodules(
+    repo: &Repository,
+    prefix: &str,
+    submodules: &mut Vec<(String, String)>,
+) -> Result<(), String> {
+    let list = repo
+        .submodules()
+        .map_err(|e| format!("failed to list submodules: {}", e))?;
+    for submodule in list {
+        let path = format!("{}{}", prefix, submodule.path().display());
+        let Some(sha) = submodule.workdir_id().or(submodule.index_id()) else {
+            continue;
+        };
+        submodules.push((path.clone(), sha.to_string()));
+        if let Ok(sub_repo) = submodule.open() {
+            add_submodules(&sub_repo, &format!("{}/", path), submodules)?;
+        }
+    }
+    Ok(())
+}
+
+/// Gets the name and email of a signature.
+fn identity(signature: &Signature) -> Result<(String, String), String> {
+    match (signature.name(), signature.email()) {
+        (Some(name), Some(email)) => Ok((name.to_string(), email.to_string())),
+        _ => Err("commit identity is not valid UTF-8".to_string()),
+    }
+}
@@ -0,0 +1,282 @@
+//! Git data from environment variables, for builds without a repository.
+//!
+//! When building from a source tarball there is no `.git`, so distro packagers can set
+//! `VER_SHIM_GIT_SHA`, `VER_SHIM_GIT_DESCRIBE`, etc. to the known values instead. A
+//! variable that is set takes precedence over the git provider, which is then not asked
+//! for that member at all.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, FixedOffset};
+use ver_shim::Member;
+
+use crate::branch::{self, BranchSource};
+use crate::cargo_helpers::cargo_rerun_if;
+use crate::diagnostics::Diagnostics;
+use crate::git_provider::{DescribeOpts, GitProvider};
+
+/// The members which can be set from environment variables.
+///
+/// The commit date is not here, since it is derived from the commit timestamp.
+const MEMBERS: [Member; 10] = [
+    Member::GitSha,
+    Member::GitDescribe,
+    Member::GitBranch,
+    Member::GitTag,
+    Member::GitDirty,
+    Member::GitCommitTimestamp,
+    Member::GitCommitMsg,
+    Member::GitCommitAuthor,
+    Member::GitCommitter,
+    Member::GitRemote,
+];
+
+/// Gets the environment variable for a member, e.g. `VER_SHIM_GIT_SHA`.
+///
+/// This is also the name used by `LinkSection::also_emit_rustc_env()`.
+pub fn var_name(member: Member) -> String {
+    format!("VER_SHIM_{}", member.name().to_ascii_uppercase())
+}
+
+/// Gets the name of a variable for an entry of a member which holds a map, without the
+/// `VER_SHIM_` prefix, e.g. `CUSTOM_KV_DEPLOY_ENV` for the custom key `deploy_env`.
+///
+/// Lowercase letters of the key are written in upper case, and digits and `_` are kept.
+/// Every other byte, uppercase letters included, is written as `x` and two hex digits,
+/// e.g. `x2D` for `-`. Nothing else in the name is lowercase, so distinct keys get
+/// distinct names, and `parse_entry_name()` recovers the key.
+pub fn entry_name(member: Member, key: &str) -> String {
+    let mut name = member.name().to_ascii_uppercase();
+    name.push('_');
+    for byte in key.bytes() {
+        match byte {
+            b'a'..=b'z' => name.push(byte.to_ascii_uppercase() as char),
+            b'0'..=b'9' | b'_' => name.push(byte as char),
+            _ => write!(name, "x{:02X}", byte).unwrap(),
+        }
+    }
+    name
+}
+
+/// Parses a name written by `entry_name()` into the member and the key.
+pub fn parse_entry_name(name: &str) -> Option<(Member, String)> {
+    let (member, encoded) = Member::ALL
+        .into_iter()
+        .filter(|m| m.is_map())
+        .find_map(|m| {
+            let rest = name.strip_prefix(&m.name().to_ascii_uppercase())?;
+            Some((m, rest.strip_prefix('_')?))
+        })?;
+    let mut key = Vec::new();
+    let mut bytes = encoded.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'A'..=b'Z' => key.push(byte.to_ascii_lowercase()),
+            b'0'..=b'9' | b'_' => key.push(byte),
+            b'x' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                if !hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'A'..=b'F')) {
+                    return None;
+                }
+                key.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            _ => return None,
+        }
+    }
+    Some((member, String::from_utf8(key).ok()?))
+}
+
+/// Emits `rerun-if-env-changed` directives for all the variables.
+pub fn emit_rerun_if_env_changed() {
+    for member in MEMBERS {
+        cargo_rerun_if(&format!("env-changed={}", var_name(member)));
+    }
+}
+
+/// A git provider which answers from environment variables where they are set, and
+/// asks `inner` otherwise.
+pub struct GitEnv<'a> {
+    pub inner: &'a dyn GitProvider,
+    pub diag: &'a Diagnostics,
+}
+
+impl GitEnv<'_> {
+    /// Gets the value of the variable for `member`, if it is set.
+    fn var(&self, member: Member) -> Option<String> {
+        let var = var_name(member);
+        let value = std::env::var(&var).ok()?;
+        self.diag.info(format!("using {}={}", var, value));
+        Some(value)
+    }
+
+    /// Gets the branch from the first of `sources` which finds one, unless its variable
+    /// is set.
+    pub fn resolve_branch(&self, sources: &[BranchSource]) -> Result<String, String> {
+        self.var(Member::GitBranch)
+            .map_or_else(|| branch::resolve(self.inner, sources), Ok)
+    }
+
+    /// Gets a `Name <email>` identity from the variable for `member`.
+    fn identity(&self, member: Member) -> Option<Result<(String, String), String>> {
+        let value = self.var(member)?;
+        Some(
+            value
+                .strip_suffix('>')
+                .and_then(|rest| rest.split_once(" <"))
+                .map(|(name, email)| (name.to_string(), email.to_string()))
+                .ok_or_else(|| {
+                    format!(
+                        "{} '{}' is not of the form 'Name <email>'",
+                        var_name(member),
+                        value
+                    )
+                }),
+        )
+    }
+
+    /// Gets the commit timestamp from its variable, whichever date of `HEAD` it is for.
+    fn timestamp(&self) -> Option<Result<DateTime<FixedOffset>, String>> {
+        let value = self.var(Member::GitCommitTimestamp)?;
+        Some(DateTime::parse_from_rfc3339(&value).map_err(|e| {
+            format!(
+                "{} '{}' is not an RFC 3339 datetime: {}",
+                var_name(Member::GitCommitTimestamp),
+                value,
+                e
+            )
+        }))
+    }
+}
+
+impl GitProvider for GitEnv<'_> {
+    fn sha(&self) -> Result<String, String> {
+        self.var(Member::GitSha)
+            .map_or_else(|| self.inner.sha(), Ok)
+    }
+
+    fn describe(&self) -> Result<String, String> {
+        self.var(Member::GitDescribe)
+            .map_or_else(|| self.inner.describe(), Ok)
+    }
+
+    fn describe_with(&self, opts: &DescribeOpts) -> Result<String, String> {
+        self.var(Member::GitDescribe)
+            .map_or_else(|| self.inner.describe_with(opts), Ok)
+    }
+
+    fn branch(&self) -> Result<String, String> {
+        self.var(Member::GitBranch)
+            .map_or_else(|| self.inner.branch(), Ok)
+    }
+
+    fn commit_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.timestamp()
+            .unwrap_or_else(|| self.inner.commit_timestamp())
+    }
+
+    fn commit_msg(&self) -> Result<String, String> {
+        self.var(Member::GitCommitMsg)
+            .map_or_else(|| self.inner.commit_msg(), Ok)
+    }
+
+    fn is_dirty(&self) -> Result<bool, String> {
+        match self.var(Member::GitDirty) {
+            Some(value) => match value.as_str() {
+                "true" | "1" => Ok(true),
+                "false" | "0" => Ok(false),
+                _ => Err(format!(
+                    "{} '{}' is not 'true' or 'false'",
+                    var_name(Member::GitDirty),
+                    value
+                )),
+            },
+            None => self.inner.is_dirty(),
+        }
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        // An empty value means that no tag points at HEAD
+        match self.var(Member::GitTag) {
+            Some(tag) => Ok(if tag.is_empty() { vec![] } else { vec![tag] }),
+            None => self.inner.tags(),
+        }
+    }
+
+    fn commit_author(&self) -> Result<(String, String), String> {
+        self.identity(Member::GitCommitAuthor)
+            .unwrap_or_else(|| self.inner.commit_author())
+    }
+
+    fn committer(&self) -> Result<(String, String), String> {
+        self.identity(Member::GitCommitter)
+            .unwrap_or_else(|| self.inner.committer())
+    }
+
+    fn committer_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.timestamp()
+            .unwrap_or_else(|| self.inner.committer_timestamp())
+    }
+
+    fn branches_at_head(&self) -> Result<Vec<String>, String> {
+        self.inner.branches_at_head()
+    }
+
+    fn nearest_branch(&self) -> Result<String, String> {
+        self.inner.nearest_branch()
+    }
+
+    fn submodules(&self) -> Result<Vec<(String, String)>, String> {
+        self.inner.submodules()
+    }
+
+    fn remote_url(&self) -> Result<String, String> {
+        self.var(Member::GitRemote)
+            .map_or_else(|| self.inner.remote_url(), Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_names() {
+        let name = |key: &str| entry_name(Member::CustomKv, key);
+        assert_eq!(name("deploy_env"), "CUSTOM_KV_DEPLOY_ENV");
+        assert_eq!(name("a-b"), "CUSTOM_KV_Ax2DB");
+        assert_eq!(name("Key"), "CUSTOM_KV_x4BEY");
+        assert_eq!(
+            entry_name(Member::CrateEntries, "my-crate:é"),
+            "CRATE_ENTRIES_MYx2DCRATEx3AxC3xA9"
+        );
+
+        // Keys which only differ in punctuation or case get different names
+        let keys = ["a-b", "a_b", "a.b", "A_B", "ab", "", "_", "x", "x2D", "é"];
+        for key in keys {
+            assert_eq!(
+                parse_entry_name(&name(key)),
+                Some((Member::CustomKv, key.to_string()))
+            );
+            let others = keys.iter().filter(|&&other| name(other) == name(key));
+            assert_eq!(others.count(), 1, "{}", key);
+        }
+    }
+
+    #[test]
+    fn invalid_entry_names() {
+        for name in [
+            "GIT_SHA_X",
+            "CUSTOM_KV",
+            "CUSTOM_KVX",
+            "CUSTOM_KV_a",
+            "CUSTOM_KV_x2",
+            "CUSTOM_KV_x2d",
+            "CUSTOM_KV_x+F",
+            "CUSTOM_KV_A-B",
+            "CUSTOM_KV_xFF",
+        ] {
+            assert_eq!(parse_entry_name(name), None, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Pluggable git backends, see `LinkSection::with_git_provider()`.
+//!
+//! By default, git data comes from the `git` command line tool ([`GitCli`]). With the
+//! `git2` or `gix` features, it can come from libgit2 ([`Git2Provider`]) or gitoxide
+//! ([`GixProvider`]) instead, which don't need `git` to be installed on the build machine.
+//! [`HgProvider`] and [`JjProvider`] collect the same members from Mercurial and Jujutsu.
+//! Other version control setups can implement [`GitProvider`] themselves.
+//!
+//! [`Git2Provider`]: crate::Git2Provider
+//! [`GixProvider`]: crate::GixProvider
+//! [`HgProvider`]: crate::HgProvider
+//! [`JjProvider`]: crate::JjProvider
+
+use std::cmp::Ordering;
+use std::process::Command;
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::fake_git;
+
+/// A source of git data for the version section.
+///
+/// Each method describes the commit at `HEAD` of the repository containing the current
+/// directory, and returns `Err` with a message if the data is not available. The
+/// message is reported as a warning, or as an error with `fail_on_error()`.
+///
+/// Only the methods for the members that are enabled are called.
+pub trait GitProvider {
+    /// Gets the full SHA of `HEAD`, like `git rev-parse HEAD`.
+    fn sha(&self) -> Result<String, String>;
+
+    /// Describes `HEAD` by the closest annotated tag, like `git describe --always --dirty`.
+    fn describe(&self) -> Result<String, String>;
+
+    /// Describes `HEAD` like `describe()`, with the options of
+    /// `LinkSection::with_git_describe_opts()`.
+    ///
+    /// The default implementation calls `describe()` if the options are the defaults, and
+    /// reports that they are not supported otherwise.
+    fn describe_with(&self, opts: &DescribeOpts) -> Result<String, String> {
+        if *opts == DescribeOpts::default() {
+            self.describe()
+        } else {
+            Err("this git provider does not support describe options".to_string())
+        }
+    }
+
+    /// Gets the short name of the current branch, or `HEAD` if it is detached,
+    /// like `git rev-parse --abbrev-ref HEAD`.
+    fn branch(&self) -> Result<String, String>;
+
+    /// Gets the author date of `HEAD`, like `git log -1 --format=%aI`.
+    fn commit_timestamp(&self) -> Result<DateTime<FixedOffset>, String>;
+
+    /// Gets the subject of the commit message of `HEAD`, like `git log -1 --format=%s`.
+    fn commit_msg(&self) -> Result<String, String>;
+
+    /// Returns true if tracked files have uncommitted changes, like
+    /// `git status --porcelain --untracked-files=no` having output.
+    fn is_dirty(&self) -> Result<bool, String>;
+
+    /// Gets the tags pointing at `HEAD`, highest version first, like
+    /// `git tag --points-at HEAD --sort=-v:refname`.
+    ///
+    /// The default implementation reports that tags are not supported.
+    fn tags(&self) -> Result<Vec<String>, String> {
+        Err("this git provider does not support tags".to_string())
+    }
+
+    /// Gets the name and email of the author of `HEAD`, like `git log -1 --format=%an%n%ae`.
+    ///
+    /// The default implementation reports that identities are not supported.
+    fn commit_author(&self) -> Result<(String, String), String> {
+        Err("this git provider does not support commit authors".to_string())
+    }
+
+    /// Gets the name and email of the committer of `HEAD`, like `git log -1 --format=%cn%n%ce`.
+    ///
+    /// The default implementation reports that identities are not supported.
+    fn committer(&self) -> Result<(String, String), String> {
+        Err("this git provider does not support committers".to_string())
+    }
+
+    /// Gets the local and then remote branches pointing at `HEAD`, without the remote
+    /// name, like `git for-each-ref --points-at HEAD refs/heads refs/remotes`.
+    ///
+    /// The default implementation reports that this is not supported.
+    fn branches_at_head(&self) -> Result<Vec<String>, String> {
+        Err("this git provider does not support finding branches at HEAD".to_string())
+    }
+
+    /// Gets the closest branch which contains `HEAD`, without the remote name, like
+    /// `git name-rev HEAD`.
+    ///
+    /// The default implementation reports that this is not supported.
+    fn nearest_branch(&self) -> Result<String, String> {
+        Err("this git provider does not support finding the nearest branch".to_string())
+    }
+
+    /// Gets the path and checked out SHA of each submodule, also nested ones, like
+    /// `git submodule status --recursive`. A submodule which is not checked out has the
+    /// SHA recorded in the superproject.
+    ///
+    /// The default implementation reports that submodules are not supported.
+    fn submodules(&self) -> Result<Vec<(String, String)>, String> {
+        Err("this git provider does not support submodules".to_string())
+    }
+
+    /// Gets the committer date of `HEAD`, like `git log -1 --format=%cI`.
+    ///
+    /// The default implementation reports that committer dates are not supported.
+    fn committer_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        Err("this git provider does not support committer dates".to_string())
+    }
+
+    /// Gets the URL of the `origin` remote, like `git remote get-url origin`. Credentials
+    /// in it are stripped afterwards.
+    ///
+    /// The default implementation reports that remotes are not supported.
+    fn remote_url(&self) -> Result<String, String> {
+        Err("this git provider does not support remotes".to_string())
+    }
+}
+
+/// Options for the git describe member, like those of `git describe`.
+///
+/// See `LinkSection::with_git_describe_opts()`. The defaults give
+/// `git describe --always --dirty`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DescribeOpts {
+    /// Also use lightweight tags, like `--tags`.
+    pub tags: bool,
+    /// Only use tags matching a glob, e.g. `v*`, like `--match`.
+    pub match_pattern: Option<String>,
+    /// The number of hex digits of the abbreviated SHA, like `--abbrev`.
+    pub abbrev: Option<u32>,
+    /// The suffix appended if the working tree is dirty, like `--dirty=SUFFIX`. The
+    /// default is `-dirty`.
+    pub dirty_suffix: Option<String>,
+}
+
+/// Which date of `HEAD` the git commit timestamp and date members hold.
+///
+/// See `LinkSection::with_git_timestamp_source()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GitTimestampSource {
+    /// The author date, like `git log -1 --format=%aI`. It is kept when a commit is
+    /// rebased or cherry-picked.
+    #[default]
+    Author,
+    /// The committer date, like `git log -1 --format=%cI`. It is updated when a commit is
+    /// rebased, amended, or cherry-picked.
+    Committer,
+}
+
+/// Gets git data by running the `git` command line tool. This is the default.
+///
+/// If `VER_SHIM_FAKE_GIT` is set, commands are answered from canned responses instead.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GitCli;
+
+impl GitCli {
+    /// Runs a git command and returns stdout as a trimmed string.
+    pub(crate) fn run(&self, args: &[&str]) -> Result<String, String> {
+        if let Some(response) = fake_git::response(args) {
+            return response.map_err(|e| {
+                format!(
+                    "'git {}' failed ({} is set): {}",
+                    args.join(" "),
+                    fake_git::FAKE_GIT_VAR,
+                    e
+                )
+            });
+        }
+        run_command("git", args, &[])
+    }
+
+    /// Gets the name and email of the author (`'a'`) or committer (`'c'`).
+    fn identity(&self, who: char) -> Result<(String, String), String> {
+        let output = self.run(&["log", "-1", &format!("--format=%{who}n%n%{who}e")])?;
+        let (name, email) = output.split_once('\n').unwrap_or((&output, ""));
+        Ok((name.to_string(), email.to_string()))
+    }
+
+    /// Gets the author (`'a'`) or committer (`'c'`) date.
+    fn timestamp(&self, who: char) -> Result<DateTime<FixedOffset>, String> {
+        let timestamp = self.run(&["log", "-1", &format!("--format=%{who}I")])?;
+        DateTime::parse_from_rfc3339(&timestamp)
+            .map_err(|e| format!("failed to parse git timestamp '{}': {}", timestamp, e))
+    }
+}
+
+impl GitProvider for GitCli {
+    fn sha(&self) -> Result<String, String> {
+        self.run(&["rev-parse", "HEAD"])
+    }
+
+    fn describe(&self) -> Result<String, String> {
+        self.describe_with(&DescribeOpts::default())
+    }
+
+    fn describe_with(&self, opts: &DescribeOpts) -> Result<String, String> {
+        let mut args = vec!["describe".to_string(), "--always".to_string()];
+        if opts.tags {
+            args.push("--tags".to_string());
+        }
+        if let Some(ref pattern) = opts.match_pattern {
+            args.push(format!("--match={}", pattern));
+        }
+        if let Some(abbrev) = opts.abbrev {
+            args.push(format!("--abbrev={}", abbrev));
+        }
+        args.push(match opts.dirty_suffix {
+            Some(ref suffix) => format!("--dirty={}", suffix),
+            None => "--dirty".to_string(),
+        });
+        self.run(&args.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    fn branch(&self) -> Result<String, String> {
+        self.run(&["rev-parse", "--abbrev-ref", "HEAD"])
+    }
+
+    fn commit_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.timestamp('a')
+    }
+
+    fn commit_msg(&self) -> Result<String, String> {
+        self.run(&["log", "-1", "--format=%s"])
+    }
+
+    fn is_dirty(&self) -> Result<bool, String> {
+        self.run(&["status", "--porcelain", "--untracked-files=no"])
+            .map(|status| !status.is_empty())
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        // Unlike `git describe --exact-match`, this succeeds with empty output if HEAD
+        // is not tagged, so that a missing tag is not reported as a git failure.
+        let output = self.run(&["tag", "--points-at", "HEAD", "--sort=-v:refname"])?;
+        Ok(output.lines().map(str::to_string).collect())
+    }
+
+    fn commit_author(&self) -> Result<(String, String), String> {
+        self.identity('a')
+    }
+
+    fn committer(&self) -> Result<(String, String), String> {
+        self.identity('c')
+    }
+
+    fn committer_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.timestamp('c')
+    }
+
+    fn branches_at_head(&self) -> Result<Vec<String>, String> {
+        let output = self.run(&[
+            "for-each-ref",
+            "--points-at",
+            "HEAD",
+            "--format=%(refname)",
+            "refs/heads",
+            "refs/remotes",
+        ])?;
+        // for-each-ref sorts by refname, so local branches come first
+        Ok(output
+            .lines()
+            .filter_map(|refname| match refname.strip_prefix("refs/heads/") {
+                Some(branch) => Some(branch),
+                None => strip_remote(refname.strip_prefix("refs/remotes/")?),
+            })
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn nearest_branch(&self) -> Result<String, String> {
+        let name = self.run(&[
+            "name-rev",
+            "--name-only",
+            "--no-undefined",
+            "--refs=refs/heads/*",
+            "--refs=refs/remotes/*",
+            "HEAD",
+        ])?;
+        // The name is e.g. `main~2` or `remotes/origin/main^2~1`
+        let name = name.split(['~', '^']).next().unwrap_or_default();
+        match name.strip_prefix("remotes/") {
+            Some(remote_branch) => strip_remote(remote_branch),
+            None => Some(name),
+        }
+        .map(str::to_string)
+        .ok_or_else(|| format!("no branch contains HEAD, only {}", name))
+    }
+
+    fn submodules(&self) -> Result<Vec<(String, String)>, String> {
+        let output = self.run(&["submodule", "status", "--recursive"])?;
+        output
+            .lines()
+            .map(|line| {
+                // e.g. `+0123abcd... vendor/proto (v1.2.0-3-g0123abc)`, where the first
+                // character is the state of the submodule (trimmed on the first line)
+                let (sha, path) = line
+                    .trim_start_matches([' ', '+', '-', 'U'])
+                    .split_once(' ')
+                    .ok_or_else(|| format!("unexpected 'git submodule status' output: {}", line))?;
+                // The describe output of the submodule follows in parentheses, if known
+                let path = path
+                    .strip_suffix(')')
+                    .and_then(|rest| rest.rsplit_once(" ("))
+                    .map_or(path, |(path, _)| path);
+                Ok((path.to_string(), sha.to_string()))
+            })
+            .collect()
+    }
+
+    fn remote_url(&self) -> Result<String, String> {
+        self.run(&["remote", "get-url", "origin"])
+    }
+}
+
+/// Strips the remote name from `origin/main`. Returns `None` for `origin/HEAD`, which
+/// is not a branch.
+fn strip_remote(remote_branch: &str) -> Option<&str> {
+    let (_, branch) = remote_branch.split_once('/')?;
+    (branch != "HEAD").then_some(branch)
+}
+
+/// Runs a version control command and returns stdout as a trimmed string.
+pub(crate) fn run_command(
+    program: &str,
+    args: &[&str],
+    envs: &[(&str, &str)],
+) -> Result<String, String> {
+    let cmd = format!("{} {}", program, args.join(" "));
+    let output = Command::new(program)
+        .args(args)
+        .envs(envs.iter().copied())
+        .output()
+        .map_err(|e| format!("failed to execute '{}': {}", cmd, e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "'{}' failed with status {}: {}",
+            cmd,
+            output.status,
+            stderr.trim()
+        ));
+    }
+
+    String::from_utf8(output.stdout)
+        .map(|s| s.trim().to_string())
+        .map_err(|_| format!("'{}' output is not valid UTF-8", cmd))
+}
+
+/// Sorts tag names highest version first, like `git tag --sort=-v:refname`.
+pub(crate) fn sort_tags(tags: &mut [String]) {
+    tags.sort_by(|a, b| version_cmp(b, a));
+}
+
+/// Compares two names, treating runs of digits as numbers, so that `v1.10` > `v1.9`.
+fn version_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    while let (Some(ca), Some(cb)) = (a.chars().next(), b.chars().next()) {
+        let ordering = if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let (na, ra) = split_digits(a);
+            let (nb, rb) = split_digits(b);
+            (a, b) = (ra, rb);
+            let (na, nb) = (na.trim_start_matches('0'), nb.trim_start_matches('0'));
+            na.len().cmp(&nb.len()).then_with(|| na.cmp(nb))
+        } else {
+            (a, b) = (&a[ca.len_utf8()..], &b[cb.len_utf8()..]);
+            ca.cmp(&cb)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Splits a string into its leading digits and the rest.
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
@@ -0,0 +1,182 @@
+//! Git data from gitoxide, with the `gix` feature.
+
+use chrono::{DateTime, FixedOffset, TimeZone};
+use gix::commit::describe::SelectRef;
+
+use crate::git_provider::{DescribeOpts, GitProvider, sort_tags};
+
+/// Gets git data using gitoxide, a pure Rust implementation of git.
+///
+/// The repository is discovered from the current directory, like the `git` command line
+/// tool does. Unlike [`GitCli`](crate::GitCli), this ignores `VER_SHIM_FAKE_GIT`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GixProvider;
+
+impl GixProvider {
+    /// Opens the repository containing the current directory.
+    fn repo(&self) -> Result<gix::Repository, String> {
+        gix::discover(".").map_err(|e| format!("failed to open git repository: {}", e))
+    }
+
+    /// Calls `f` with the commit at `HEAD`.
+    fn with_head<T>(&self, f: impl FnOnce(&gix::Commit) -> Result<T, String>) -> Result<T, String> {
+        let repo = self.repo()?;
+        let commit = repo
+            .head_commit()
+            .map_err(|e| format!("failed to find HEAD commit: {}", e))?;
+        f(&commit)
+    }
+}
+
+impl GitProvider for GixProvider {
+    fn sha(&self) -> Result<String, String> {
+        self.with_head(|commit| Ok(commit.id.to_string()))
+    }
+
+    fn describe(&self) -> Result<String, String> {
+        self.describe_with(&DescribeOpts::default())
+    }
+
+    fn describe_with(&self, opts: &DescribeOpts) -> Result<String, String> {
+        if opts.match_pattern.is_some() {
+            return Err("gitoxide does not support describe match patterns".to_string());
+        }
+        self.with_head(|commit| {
+            let resolution = commit
+                .describe()
+                .names(if opts.tags {
+                    SelectRef::AllTags
+                } else {
+                    SelectRef::AnnotatedTags
+                })
+                .id_as_fallback(true)
+                .try_resolve()
+                .map_err(|e| format!("failed to describe HEAD: {}", e))?
+                .ok_or_else(|| "failed to describe HEAD".to_string())?;
+            let mut format = resolution
+                .format_with_dirty_suffix(String::new())
+                .map_err(|e| format!("failed to describe HEAD: {}", e))?;
+            if let Some(abbrev) = opts.abbrev {
+                format.hex_len = abbrev as usize;
+            }
+            // gitoxide puts a `-` before the suffix, so append it here instead
+            let dirty = format.dirty_suffix.take().is_some();
+            let mut describe = format.to_string();
+            if dirty {
+                describe.push_str(opts.dirty_suffix.as_deref().unwrap_or("-dirty"));
+            }
+            Ok(describe)
+        })
+    }
+
+    fn branch(&self) -> Result<String, String> {
+        let repo = self.repo()?;
+        let head = repo
+            .head_name()
+            .map_err(|e| format!("failed to find HEAD: {}", e))?;
+        Ok(match head {
+            Some(name) => name.shorten().to_string(),
+            None => "HEAD".to_string(),
+        })
+    }
+
+    fn commit_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.with_head(|commit| {
+            to_datetime(
+                commit
+                    .author()
+                    .map_err(|e| format!("failed to decode HEAD commit: {}", e))?,
+            )
+        })
+    }
+
+    fn commit_msg(&self) -> Result<String, String> {
+        self.with_head(|commit| {
+            let message = commit
+                .message()
+                .map_err(|e| format!("failed to decode HEAD commit: {}", e))?;
+            Ok(message.summary().to_string())
+        })
+    }
+
+    fn is_dirty(&self) -> Result<bool, String> {
+        self.repo()?
+            .is_dirty()
+            .map_err(|e| format!("failed to get git status: {}", e))
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        let repo = self.repo()?;
+        let head = repo
+            .head_id()
+            .map_err(|e| format!("failed to find HEAD commit: {}", e))?;
+        let references = repo
+            .references()
+            .map_err(|e| format!("failed to list git tags: {}", e))?;
+        let mut tags = Vec::new();
+        for reference in references
+            .tags()
+            .map_err(|e| format!("failed to list git tags: {}", e))?
+            .flatten()
+        {
+            let mut reference = reference;
+            if reference
+                .peel_to_commit()
+                .is_ok_and(|commit| commit.id == head)
+            {
+                tags.push(reference.name().shorten().to_string());
+            }
+        }
+        sort_tags(&mut tags);
+        Ok(tags)
+    }
+
+    fn commit_author(&self) -> Result<(String, String), String> {
+        self.with_head(|commit| {
+            let author = commit
+                .author()
+                .map_err(|e| format!("failed to decode HEAD commit: {}", e))?;
+            Ok((author.name.to_string(), author.email.to_string()))
+        })
+    }
+
+    fn committer(&self) -> Result<(String, String), String> {
+        self.with_head(|commit| {
+            let committer = commit
+                .committer()
+                .map_err(|e| format!("failed to decode HEAD commit: {}", e))?;
+            Ok((committer.name.to_string(), committer.email.to_string()))
+        })
+    }
+
+    fn committer_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.with_head(|commit| {
+            to_datetime(
+                commit
+                    .committer()
+                    .map_err(|e| format!("failed to decode HEAD commit: {}", e))?,
+            )
+        })
+    }
+
+    fn remote_url(&self) -> Result<String, String> {
+        let repo = self.repo()?;
+        let remote = repo
+            .find_remote("origin")
+            .map_err(|e| format!("failed to find the origin remote: {}", e))?;
+        remote
+            .url(gix::remote::Direction::Fetch)
+            .map(|url| url.to_bstring().to_string())
+            .ok_or_else(|| "the origin remote has no URL".to_string())
+    }
+}
+
+/// Converts the time of a signature to a chrono DateTime in its original time zone.
+fn to_datetime(signature: gix::actor::SignatureRef<'_>) -> Result<DateTime<FixedOffset>, String> {
+    let time = signature
+        .time()
+        .map_err(|e| format!("failed to parse git timestamp: {}", e))?;
+    FixedOffset::east_opt(time.offset)
+        .and_then(|offset| offset.timestamp_opt(time.seconds, 0).single())
+        .ok_or_else(|| format!("invalid git timestamp {}", time.seconds))
+}
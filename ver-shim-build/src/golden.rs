@@ -0,0 +1,102 @@
+//! Golden-file snapshots of section data, to catch format or configuration drift in CI.
+//!
+//! The snapshot is a text rendering of the section: the decoded members, followed by
+//! a hex dump of the buffer up to its last non-zero byte. Text keeps diffs of the
+//! checked-in file reviewable.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::SectionData;
+use crate::cargo_helpers::cargo_rerun_if;
+use crate::diagnostics::Diagnostics;
+use crate::error::VerShimError;
+use crate::output;
+
+/// The environment variable which switches to regenerating golden files.
+pub const UPDATE_VAR: &str = "VER_SHIM_UPDATE_GOLDEN";
+
+/// Bytes per line of the hex dump.
+const HEX_DUMP_WIDTH: usize = 16;
+
+/// Renders section bytes as a snapshot.
+pub fn render(buffer: &[u8]) -> Result<String, VerShimError> {
+    let data = SectionData::parse(buffer).map_err(|e| {
+        VerShimError::InvalidBinary(format!("failed to decode section data: {}", e))
+    })?;
+
+    let mut out = String::new();
+    writeln!(out, "# ver-shim section snapshot").unwrap();
+    writeln!(out, "buffer_size: {}", buffer.len()).unwrap();
+    writeln!(out, "format_version: {}", data.format_version()).unwrap();
+    writeln!(out, "num_members: {}", data.num_members()).unwrap();
+    for (member, value) in data.iter() {
+        writeln!(out, "{:?}: {:?}", member, value).unwrap();
+    }
+
+    let used = buffer
+        .iter()
+        .rposition(|&b| b != 0)
+        .map_or(0, |pos| pos + 1);
+    writeln!(out, "bytes:").unwrap();
+    for (line, chunk) in buffer[..used].chunks(HEX_DUMP_WIDTH).enumerate() {
+        write!(out, "{:04x}:", line * HEX_DUMP_WIDTH).unwrap();
+        for byte in chunk {
+            write!(out, " {:02x}", byte).unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+    Ok(out)
+}
+
+/// Compares a snapshot against the golden file at `path`, or rewrites the golden file
+/// if `VER_SHIM_UPDATE_GOLDEN` is set.
+pub fn check(
+    path: &Path,
+    snapshot: &str,
+    atomic: bool,
+    diag: &Diagnostics,
+) -> Result<(), VerShimError> {
+    cargo_rerun_if(&format!("env-changed={}", UPDATE_VAR));
+    cargo_rerun_if(&format!("changed={}", path.display()));
+
+    if std::env::var_os(UPDATE_VAR).is_some() {
+        output::write(path, snapshot, atomic)
+            .map_err(|e| VerShimError::io(format!("failed to write {}", path.display()), e))?;
+        diag.info(format!("updated golden file {}", path.display()));
+        return Ok(());
+    }
+
+    let golden = fs::read_to_string(path).map_err(|e| {
+        VerShimError::io(
+            format!(
+                "failed to read golden file {} (set {}=1 to create it)",
+                path.display(),
+                UPDATE_VAR
+            ),
+            e,
+        )
+    })?;
+    if golden == snapshot {
+        diag.info(format!("section matches golden file {}", path.display()));
+        return Ok(());
+    }
+
+    let mismatch = golden
+        .lines()
+        .zip(snapshot.lines())
+        .position(|(a, b)| a != b)
+        .unwrap_or_else(|| golden.lines().count().min(snapshot.lines().count()));
+    Err(VerShimError::Check(format!(
+        "section does not match golden file {} (first difference at line {}):\n\
+         expected: {}\n\
+         actual:   {}\n\
+         Set {}=1 to regenerate it if the change is intended.",
+        path.display(),
+        mismatch + 1,
+        golden.lines().nth(mismatch).unwrap_or("<end of file>"),
+        snapshot.lines().nth(mismatch).unwrap_or("<end of file>"),
+        UPDATE_VAR
+    )))
+}
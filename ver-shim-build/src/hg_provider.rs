@@ -0,0 +1,101 @@
+//! Version data from Mercurial, using the `hg` command line tool.
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::git_provider::{GitProvider, run_command, sort_tags};
+
+/// Gets version data from a Mercurial repository by running `hg`.
+///
+/// The members keep their git names: the SHA is the full changeset hash of the working
+/// directory parent, and the branch is the active bookmark, or the named branch if no
+/// bookmark is active. `describe` is built from the latest tag like `git describe`, e.g.
+/// `v1.2.0-3-m0123456789ab`. Mercurial has no separate committer, so that is not supported.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HgProvider;
+
+impl HgProvider {
+    /// Runs an hg command with `HGPLAIN` set, so that user configuration doesn't change
+    /// the output.
+    fn run(&self, args: &[&str]) -> Result<String, String> {
+        run_command("hg", args, &[("HGPLAIN", "1")])
+    }
+
+    /// Expands a template for the working directory parent, like `hg log -r . -T ...`.
+    fn log(&self, template: &str) -> Result<String, String> {
+        self.run(&["log", "-r", ".", "-T", template])
+    }
+}
+
+impl GitProvider for HgProvider {
+    fn sha(&self) -> Result<String, String> {
+        self.log("{node}")
+    }
+
+    fn describe(&self) -> Result<String, String> {
+        let output = self.log("{latesttag}\\n{latesttagdistance}\\n{short(node)}")?;
+        let mut lines = output.lines();
+        let (Some(tag), Some(distance), Some(node)) = (lines.next(), lines.next(), lines.next())
+        else {
+            return Err(format!("unexpected 'hg log' output: {}", output));
+        };
+        let mut describe = match (tag, distance) {
+            // No tag in the history, fall back to the hash like `git describe --always`
+            ("null", _) => node.to_string(),
+            (tag, "0") => tag.to_string(),
+            (tag, distance) => format!("{}-{}-m{}", tag, distance, node),
+        };
+        if self.is_dirty()? {
+            describe.push_str("-dirty");
+        }
+        Ok(describe)
+    }
+
+    fn branch(&self) -> Result<String, String> {
+        self.log("{ifeq(activebookmark, '', branch, activebookmark)}")
+    }
+
+    fn commit_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        let timestamp = self.log("{date|rfc3339date}")?;
+        DateTime::parse_from_rfc3339(&timestamp)
+            .map_err(|e| format!("failed to parse hg timestamp '{}': {}", timestamp, e))
+    }
+
+    fn commit_msg(&self) -> Result<String, String> {
+        self.log("{desc|firstline}")
+    }
+
+    fn is_dirty(&self) -> Result<bool, String> {
+        self.run(&["status", "--modified", "--added", "--removed", "--deleted"])
+            .map(|status| !status.is_empty())
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        let output = self.log("{join(tags, '\\n')}")?;
+        let mut tags: Vec<String> = output
+            .lines()
+            .filter(|tag| *tag != "tip")
+            .map(str::to_string)
+            .collect();
+        sort_tags(&mut tags);
+        Ok(tags)
+    }
+
+    fn commit_author(&self) -> Result<(String, String), String> {
+        let output = self.log("{person(author)}\\n{email(author)}")?;
+        let (name, email) = output.split_once('\n').unwrap_or((&output, ""));
+        Ok((name.to_string(), email.to_string()))
+    }
+
+    fn committer(&self) -> Result<(String, String), String> {
+        Err("Mercurial does not record a committer, only an author".to_string())
+    }
+
+    fn committer_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        Err("Mercurial does not record a committer date, only a commit date".to_string())
+    }
+
+    fn remote_url(&self) -> Result<String, String> {
+        // The `default` path is Mercurial's counterpart of `origin`
+        self.run(&["paths", "default"])
+    }
+}
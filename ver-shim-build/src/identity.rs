@@ -0,0 +1,50 @@
+//! Git author and committer identities, with optional email privacy.
+
+use sha2::{Digest, Sha256};
+
+/// Number of hex digits of the salted hash to keep.
+const HASH_HEX_LEN: usize = 16;
+
+/// How the email address of the author or committer is embedded.
+///
+/// See `LinkSection::with_email_privacy()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum EmailPrivacy {
+    /// Embed the full email address, e.g. `Jane Doe <jane.doe@example.com>`.
+    #[default]
+    Full,
+    /// Embed only the part of the email before the `@`, e.g. `Jane Doe <jane.doe>`.
+    Username,
+    /// Embed a salted hash of the email instead, e.g. `Jane Doe <sha256:3f2a9c01b7d4e865>`.
+    ///
+    /// The hash is the first 16 hex digits of SHA-256 over the salt followed by the
+    /// lowercased email. With the salt, builds can still be correlated to people
+    /// internally, but the email can't be recovered from a shipped binary by guessing.
+    SaltedHash(String),
+}
+
+impl EmailPrivacy {
+    /// Applies the privacy setting to an email address.
+    fn apply(&self, email: &str) -> String {
+        match self {
+            Self::Full => email.to_string(),
+            Self::Username => email.split('@').next().unwrap_or_default().to_string(),
+            Self::SaltedHash(salt) => {
+                let mut hasher = Sha256::new();
+                hasher.update(salt.as_bytes());
+                hasher.update(email.to_lowercase().as_bytes());
+                let hex: String = hasher
+                    .finalize()
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                format!("sha256:{}", &hex[..HASH_HEX_LEN])
+            }
+        }
+    }
+}
+
+/// Formats an identity as `Name <email>`, applying the email privacy setting.
+pub fn format(name: &str, email: &str, privacy: &EmailPrivacy) -> String {
+    format!("{} <{}>", name, privacy.apply(email))
+}
@@ -0,0 +1,294 @@
+//! Patching Intel HEX images.
+//!
+//! MCU firmware is often distributed as an Intel HEX file rather than an ELF. Given the
+//! load address of the version section, we overwrite the bytes of the data records which
+//! cover it, and recompute the checksums of those records. All other records, including
+//! their formatting, are passed through unchanged.
+//!
+//! See: https://en.wikipedia.org/wiki/Intel_HEX
+
+/// Data record.
+const DATA: u8 = 0x00;
+/// Extended segment address record: base address = value * 16.
+const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+/// Extended linear address record: base address = value << 16.
+const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+
+/// A single parsed record.
+struct Record {
+    offset: u16,
+    kind: u8,
+    data: Vec<u8>,
+}
+
+impl Record {
+    /// Parses a record line (without the line ending), validating its checksum.
+    fn parse(line: &str) -> Result<Self, String> {
+        let hex = line
+            .strip_prefix(':')
+            .ok_or_else(|| "record does not start with ':'".to_string())?;
+        let bytes = decode_hex(hex)?;
+        if bytes.len() < 5 || bytes.len() != 5 + bytes[0] as usize {
+            return Err("record length does not match its byte count".to_string());
+        }
+        let sum = bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        if sum != 0 {
+            return Err("record checksum mismatch".to_string());
+        }
+        Ok(Self {
+            offset: u16::from_be_bytes([bytes[1], bytes[2]]),
+            kind: bytes[3],
+            data: bytes[4..bytes.len() - 1].to_vec(),
+        })
+    }
+
+    /// Returns the value of an address record's data field.
+    fn address_value(&self) -> Result<u32, String> {
+        match self.data[..] {
+            [hi, lo] => Ok(u16::from_be_bytes([hi, lo]) as u32),
+            _ => Err("address record must have 2 data bytes".to_string()),
+        }
+    }
+
+    /// Encodes the record as a line (without the line ending), with a fresh checksum.
+    fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(5 + self.data.len());
+        bytes.push(self.data.len() as u8);
+        bytes.extend_from_slice(&self.offset.to_be_bytes());
+        bytes.push(self.kind);
+        bytes.extend_from_slice(&self.data);
+        let sum = bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        bytes.push(sum.wrapping_neg());
+
+        let mut line = String::with_capacity(1 + bytes.len() * 2);
+        line.push(':');
+        for b in bytes {
+            line.push_str(&format!("{:02X}", b));
+        }
+        line
+    }
+}
+
+/// Decodes the hex digits of a record, shared with the S-record parser in `srec`.
+pub(crate) fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err("record is not an even number of hex digits".to_string());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| format!("invalid hex digits '{}'", &hex[i..i + 2]))
+        })
+        .collect()
+}
+
+/// Overwrites `bytes` at `address` in the Intel HEX image `text`, returning the new image.
+///
+/// Returns `Err` if the image is malformed, or if its data records don't cover the
+/// whole address range (e.g. the address is wrong, or the section was discarded).
+pub fn patch(text: &str, address: u32, bytes: &[u8]) -> Result<String, String> {
+    let target_start = address as u64;
+    let target_end = target_start + bytes.len() as u64;
+
+    let mut out = String::with_capacity(text.len());
+    let mut base: u32 = 0;
+    let mut covered = 0usize;
+
+    for (idx, line) in text.split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\r', '\n']);
+        if content.is_empty() {
+            out.push_str(line);
+            continue;
+        }
+        let mut record = Record::parse(content).map_err(|e| format!("line {}: {}", idx + 1, e))?;
+
+        match record.kind {
+            DATA => {
+                let start = base as u64 + record.offset as u64;
+                let end = start + record.data.len() as u64;
+                let lo = start.max(target_start);
+                let hi = end.min(target_end);
+                if lo < hi {
+                    for addr in lo..hi {
+                        record.data[(addr - start) as usize] =
+                            bytes[(addr - target_start) as usize];
+                    }
+                    covered += (hi - lo) as usize;
+                    out.push_str(&record.encode());
+                    out.push_str(&line[content.len()..]);
+                    continue;
+                }
+            }
+            EXTENDED_SEGMENT_ADDRESS => {
+                base = record
+                    .address_value()
+                    .map_err(|e| format!("line {}: {}", idx + 1, e))?
+                    * 16;
+            }
+            EXTENDED_LINEAR_ADDRESS => {
+                base = record
+                    .address_value()
+                    .map_err(|e| format!("line {}: {}", idx + 1, e))?
+                    << 16;
+            }
+            _ => {}
+        }
+        out.push_str(line);
+    }
+
+    if covered < bytes.len() {
+        return Err(format!(
+            "data records cover only {} of the {} bytes at {:#x}, is the load address correct?",
+            covered,
+            bytes.len(),
+            address
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn record(offset: u16, kind: u8, data: &[u8]) -> String {
+        Record {
+            offset,
+            kind,
+            data: data.to_vec(),
+        }
+        .encode()
+    }
+
+    const EOF: &str = ":00000001FF";
+
+    /// Reads the bytes of an image by address, checking every record's checksum.
+    fn read_image(text: &str) -> BTreeMap<u32, u8> {
+        let mut memory = BTreeMap::new();
+        let mut base = 0;
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            let record = Record::parse(line).unwrap();
+            match record.kind {
+                DATA => {
+                    for (i, b) in record.data.iter().enumerate() {
+                        memory.insert(base + record.offset as u32 + i as u32, *b);
+                    }
+                }
+                EXTENDED_SEGMENT_ADDRESS => base = record.address_value().unwrap() * 16,
+                EXTENDED_LINEAR_ADDRESS => base = record.address_value().unwrap() << 16,
+                _ => {}
+            }
+        }
+        memory
+    }
+
+    #[test]
+    fn extended_linear_address() {
+        let text = [
+            record(0, EXTENDED_LINEAR_ADDRESS, &[0x08, 0x00]),
+            record(0x0100, DATA, &[0xff; 16]),
+            EOF.to_string(),
+        ]
+        .join("\n");
+        let patched = patch(&text, 0x0800_0104, b"ver").unwrap();
+
+        let memory = read_image(&patched);
+        assert_eq!(memory[&0x0800_0103], 0xff);
+        assert_eq!(memory[&0x0800_0104], b'v');
+        assert_eq!(memory[&0x0800_0106], b'r');
+        assert_eq!(memory[&0x0800_0107], 0xff);
+    }
+
+    #[test]
+    fn extended_segment_address() {
+        let text = [
+            record(0, EXTENDED_SEGMENT_ADDRESS, &[0x10, 0x00]),
+            record(0x0020, DATA, &[0xff; 16]),
+            EOF.to_string(),
+        ]
+        .join("\n");
+        // The base address is 0x1000 * 16
+        let patched = patch(&text, 0x1_0020, b"ver").unwrap();
+
+        let memory = read_image(&patched);
+        assert_eq!(memory[&0x1_0020], b'v');
+        assert_eq!(memory[&0x1_0022], b'r');
+        assert_eq!(memory[&0x1_0023], 0xff);
+    }
+
+    #[test]
+    fn section_spanning_records() {
+        let text = [
+            record(0x0000, DATA, &[0xff; 16]),
+            record(0x0010, DATA, &[0xff; 16]),
+            record(0x0020, DATA, &[0xff; 16]),
+            EOF.to_string(),
+        ]
+        .join("\n");
+        let bytes: Vec<u8> = (0..24).collect();
+        let patched = patch(&text, 0x0c, &bytes).unwrap();
+
+        let memory = read_image(&patched);
+        for (i, b) in bytes.iter().enumerate() {
+            assert_eq!(memory[&(0x0c + i as u32)], *b);
+        }
+        assert_eq!(memory[&0x0b], 0xff);
+        assert_eq!(memory[&0x24], 0xff);
+    }
+
+    #[test]
+    fn partial_coverage() {
+        let text = [record(0x0000, DATA, &[0xff; 16]), EOF.to_string()].join("\n");
+        let err = patch(&text, 0x0c, &[0; 8]).unwrap_err();
+        assert!(err.contains("cover only 4 of the 8 bytes"), "{}", err);
+
+        let err = patch(&text, 0x100, &[0; 8]).unwrap_err();
+        assert!(err.contains("cover only 0 of the 8 bytes"), "{}", err);
+    }
+
+    #[test]
+    fn checksums_are_recomputed() {
+        let untouched = record(0x0010, DATA, &[0xff; 16]);
+        let text = [
+            record(0x0000, DATA, &[0xff; 16]),
+            untouched.clone(),
+            EOF.to_string(),
+        ]
+        .join("\n");
+        let patched = patch(&text, 0x04, b"ver").unwrap();
+
+        let lines: Vec<&str> = patched.lines().collect();
+        let mut data = [0xff; 16];
+        data[4..7].copy_from_slice(b"ver");
+        assert_eq!(lines[0], record(0x0000, DATA, &data));
+        assert_eq!(Record::parse(lines[0]).unwrap().data, data);
+        assert_eq!(lines[1], untouched);
+        assert_eq!(lines[2], EOF);
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let text = ":10000000FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF01\n";
+        let err = patch(text, 0, b"ver").unwrap_err();
+        assert_eq!(err, "line 1: record checksum mismatch");
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let text = format!(
+            "{}\r\n{}\r\n\r\n{}\r\n",
+            record(0x0000, DATA, &[0xff; 16]),
+            record(0x0010, DATA, &[0xff; 16]),
+            EOF
+        );
+        let patched = patch(&text, 0x0e, b"ver").unwrap();
+
+        assert_eq!(patched.matches("\r\n").count(), 4);
+        assert_eq!(patched.matches('\n').count(), 4);
+        let memory = read_image(&patched);
+        assert_eq!(memory[&0x0e], b'v');
+        assert_eq!(memory[&0x10], b'r');
+    }
+}
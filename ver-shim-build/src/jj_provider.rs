@@ -0,0 +1,123 @@
+//! Version data from Jujutsu, using the `jj` command line tool.
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::git_provider::{GitProvider, run_command, sort_tags};
+
+/// The revision that is built: the parent of the working-copy commit, like `HEAD` in a
+/// colocated git repository. Changes in the working-copy commit count as uncommitted.
+const REVISION: &str = "@-";
+
+/// Gets version data from a Jujutsu repository by running `jj`.
+///
+/// The members keep their git names and describe the parent of the working-copy commit
+/// (`@-`), which is what `git` sees as `HEAD` in a colocated repository. The work tree is
+/// dirty if the working-copy commit (`@`) is not empty. The branch is the first local
+/// bookmark on `@-`, or `HEAD` if there is none, and `describe` is a tag on `@-` if there
+/// is one, or else the short commit id.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JjProvider;
+
+impl JjProvider {
+    /// Expands a template for one revision, like `jj log -r <revision> -T ...`.
+    fn log(&self, revision: &str, template: &str) -> Result<String, String> {
+        run_command(
+            "jj",
+            &[
+                "log",
+                "--no-graph",
+                "--color=never",
+                "--limit=1",
+                "-r",
+                revision,
+                "-T",
+                template,
+            ],
+            &[],
+        )
+    }
+
+    /// Gets the name and email of the author or committer signature.
+    fn identity(&self, signature: &str) -> Result<(String, String), String> {
+        let output = self.log(
+            REVISION,
+            &format!("{0}.name() ++ \"\\n\" ++ {0}.email()", signature),
+        )?;
+        let (name, email) = output.split_once('\n').unwrap_or((&output, ""));
+        Ok((name.to_string(), email.to_string()))
+    }
+
+    /// Gets the timestamp of the `author` or `committer` signature.
+    fn timestamp(&self, signature: &str) -> Result<DateTime<FixedOffset>, String> {
+        let timestamp = self.log(
+            REVISION,
+            &format!("{}.timestamp().format(\"%Y-%m-%dT%H:%M:%S%:z\")", signature),
+        )?;
+        DateTime::parse_from_rfc3339(&timestamp)
+            .map_err(|e| format!("failed to parse jj timestamp '{}': {}", timestamp, e))
+    }
+}
+
+impl GitProvider for JjProvider {
+    fn sha(&self) -> Result<String, String> {
+        self.log(REVISION, "commit_id")
+    }
+
+    fn describe(&self) -> Result<String, String> {
+        let mut describe = match self.tags()?.into_iter().next() {
+            Some(tag) => tag,
+            None => self.log(REVISION, "commit_id.short()")?,
+        };
+        if self.is_dirty()? {
+            describe.push_str("-dirty");
+        }
+        Ok(describe)
+    }
+
+    fn branch(&self) -> Result<String, String> {
+        let output = self.log(REVISION, "local_bookmarks.map(|b| b.name()).join(\"\\n\")")?;
+        Ok(output.lines().next().unwrap_or("HEAD").to_string())
+    }
+
+    fn commit_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.timestamp("author")
+    }
+
+    fn commit_msg(&self) -> Result<String, String> {
+        self.log(REVISION, "description.first_line()")
+    }
+
+    fn is_dirty(&self) -> Result<bool, String> {
+        self.log("@", "if(empty, \"clean\", \"dirty\")")
+            .map(|state| state == "dirty")
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        let output = self.log(REVISION, "tags.map(|t| t.name()).join(\"\\n\")")?;
+        let mut tags: Vec<String> = output.lines().map(str::to_string).collect();
+        sort_tags(&mut tags);
+        Ok(tags)
+    }
+
+    fn commit_author(&self) -> Result<(String, String), String> {
+        self.identity("author")
+    }
+
+    fn committer(&self) -> Result<(String, String), String> {
+        self.identity("committer")
+    }
+
+    fn committer_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.timestamp("committer")
+    }
+
+    fn remote_url(&self) -> Result<String, String> {
+        // Lines of `<name> <url>`
+        let output = run_command("jj", &["git", "remote", "list"], &[])?;
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix("origin "))
+            .map(str::to_string)
+            .ok_or_else(|| "no origin remote".to_string())
+    }
+}
@@ -0,0 +1,43 @@
+//! Key-value encoding used by members which hold a map.
+//!
+//! This matches the decoding in the `ver_shim` runtime crate: a sequence of
+//! NUL-terminated strings, alternating between keys and values. Keys are sorted
+//! and unique, which keeps the encoding deterministic.
+
+use std::collections::BTreeMap;
+
+use crate::diagnostics::Diagnostics;
+
+/// Encodes a map as member data.
+pub fn encode(map: &BTreeMap<String, String>) -> String {
+    let mut out = String::new();
+    for (key, value) in map {
+        out.push_str(key);
+        out.push('\0');
+        out.push_str(value);
+        out.push('\0');
+    }
+    out
+}
+
+/// Decodes member data into a map.
+///
+/// Trailing data that doesn't form a complete pair is ignored.
+pub fn decode(data: &str) -> BTreeMap<String, String> {
+    let mut map = BTreeMap::new();
+    let mut rest = data;
+    while let Some((key, after_key)) = rest.split_once('\0')
+        && let Some((value, after_value)) = after_key.split_once('\0')
+    {
+        map.insert(key.to_string(), value.to_string());
+        rest = after_value;
+    }
+    map
+}
+
+/// Reports an error if the string contains a NUL byte, which is used as the separator.
+pub fn check_no_nul(diag: &Diagnostics, what: &str, s: &str) {
+    if s.contains('\0') {
+        diag.error(format!("{} must not contain NUL bytes: {:?}", what, s));
+    }
+}
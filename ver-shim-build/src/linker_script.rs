@@ -0,0 +1,77 @@
+//! Linker script fragments which place the version section, for embedded targets.
+//!
+//! The fragment is passed to the linker in addition to its default script (or e.g. the
+//! `link.x` of `cortex-m-rt`), and is inserted after `.rodata` with `INSERT AFTER`, so it
+//! only works with linkers which understand GNU linker scripts: GNU ld and lld.
+
+use std::fmt::Write;
+
+use ver_shim::SECTION_NAME;
+
+use crate::cargo_helpers;
+use crate::diagnostics::Diagnostics;
+use crate::error::VerShimError;
+use crate::output;
+
+/// Where the linker places the version section.
+#[derive(Clone, Debug)]
+pub(crate) enum Placement {
+    /// At a fixed address.
+    Address(u64),
+    /// In a memory region declared in the `MEMORY` command, e.g. in `memory.x`.
+    Region(String),
+}
+
+/// Writes a linker script fragment to `OUT_DIR` which places the section, and passes it to
+/// the linker of the binaries of the crate.
+///
+/// Does nothing except for a warning on Apple and Windows targets, which don't use GNU
+/// linker scripts.
+pub(crate) fn emit(
+    section_name: Option<&str>,
+    placement: &Placement,
+    diag: &Diagnostics,
+) -> Result<(), VerShimError> {
+    let vendor = std::env::var("CARGO_CFG_TARGET_VENDOR").unwrap_or_default();
+    let os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    if vendor == "apple" || os == "windows" {
+        diag.warning("section placement is only supported for ELF targets, ignoring it");
+        return Ok(());
+    }
+
+    let script = render(section_name.unwrap_or(SECTION_NAME), placement);
+    let path = cargo_helpers::out_dir()?.join("ver_shim_placement.x");
+    output::write(&path, script, true)
+        .map_err(|e| VerShimError::io(format!("failed to write {}", path.display()), e))?;
+    cargo_helpers::cargo_rustc_link_arg_bins(&format!("-T{}", path.display()));
+    diag.info(format!(
+        "wrote linker script fragment to {}",
+        path.display()
+    ));
+    Ok(())
+}
+
+/// Renders the linker script fragment.
+///
+/// `KEEP` stops `--gc-sections` from discarding the section, since no code refers to
+/// it directly. The section must not be `NOLOAD`, or it wouldn't be in the flashed image.
+fn render(section_name: &str, placement: &Placement) -> String {
+    let mut script = String::from("SECTIONS\n{\n");
+    match placement {
+        Placement::Address(address) => {
+            writeln!(script, "  {} {:#x} :", section_name, address).unwrap();
+            writeln!(script, "  {{\n    KEEP(*({}));\n  }}", section_name).unwrap();
+        }
+        Placement::Region(region) => {
+            writeln!(script, "  {} :", section_name).unwrap();
+            writeln!(
+                script,
+                "  {{\n    KEEP(*({}));\n  }} > {}",
+                section_name, region
+            )
+            .unwrap();
+        }
+    }
+    script.push_str("}\nINSERT AFTER .rodata;\n");
+    script
+}
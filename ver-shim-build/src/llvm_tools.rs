@@ -1,94 +1,46 @@
 //! LLVM tools wrapper for section manipulation.
+//!
+//! Only `llvm-objcopy` is run, to rewrite sections. Sections and notes are read with the
+//! `object` crate instead, see `native`. If the `llvm-tools` component isn't installed
+//! (e.g. with a distro or Nix Rust), GNU `objcopy` from `PATH` is used instead, which
+//! takes the same arguments for everything we do.
 
+use std::env;
 use std::env::consts::EXE_SUFFIX;
-use std::io::{self, Write};
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Command, Output, Stdio};
 
+use crate::cargo_helpers::cargo_rerun_if;
+use crate::error::{SuspectedCause, ToolFailure, VerShimError};
 use crate::rustc;
 
-/// Wrapper for LLVM tools (llvm-readobj, llvm-objcopy).
+/// Wrapper for LLVM tools (llvm-objcopy).
 ///
-/// This provides access to LLVM tools from the Rust toolchain for reading
-/// and modifying ELF sections in binaries.
+/// This provides access to LLVM tools from the Rust toolchain for modifying
+/// ELF sections in binaries.
 pub struct LlvmTools {
-    bin_dir: PathBuf,
+    objcopy: PathBuf,
 }
 
 impl LlvmTools {
-    /// Creates a new `LlvmTools` instance by locating the LLVM tools directory.
-    pub fn new() -> Result<Self, String> {
-        let bin_dir = rustc::llvm_tools_bin_dir()?;
-        Ok(Self { bin_dir })
-    }
-
-    /// Gets the size of a section in a binary.
+    /// Creates a new `LlvmTools` instance by locating the tools.
     ///
-    /// Returns `Ok(Some(size))` if the section exists, `Ok(None)` if it doesn't,
-    /// or `Err` if there was an error executing llvm-readobj or parsing the output.
-    pub fn get_section_size(
-        &self,
-        bin: impl AsRef<Path>,
-        section_name: &str,
-    ) -> io::Result<Option<usize>> {
-        let bin = bin.as_ref();
-        let readobj_path = self.bin_dir.join(format!("llvm-readobj{}", EXE_SUFFIX));
-
-        let output = Command::new(&readobj_path)
-            .arg("--sections")
-            .arg(bin)
-            .output()?;
-
-        if !output.status.success() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("llvm-readobj failed with status {}", output.status),
-            ));
-        }
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-
-        // Parse llvm-readobj --sections output to find our section
-        // Format is like:
-        //   Section {
-        //     Index: 16
-        //     Name: .ver_shim_data (472)
-        //     Type: SHT_PROGBITS (0x1)
-        //     ...
-        //     Size: 512
-        //     ...
-        //   }
-        let mut in_target_section = false;
-        for line in stdout.lines() {
-            let trimmed = line.trim();
-
-            // Check if we're entering our target section
-            // Format: "Name: .ver_shim_data (472)"
-            if let Some(name_part) = trimmed.strip_prefix("Name:") {
-                // Remove parenthesized suffix and trim: ".ver_shim_data (472)" -> ".ver_shim_data"
-                let name = match name_part.find('(') {
-                    Some(idx) => name_part[..idx].trim(),
-                    None => name_part.trim(),
-                };
-                in_target_section = name == section_name;
-                continue;
-            }
-
-            // If we're in the target section, look for the Size line
-            if in_target_section
-                && let Some(size_str) = trimmed.strip_prefix("Size:")
-            {
-                let size = size_str.trim().parse::<usize>().map_err(|e| {
-                    io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("failed to parse section size '{}': {}", size_str.trim(), e),
-                    )
-                })?;
-                return Ok(Some(size));
-            }
-        }
-
-        Ok(None)
+    /// `objcopy` is the path set with `UpdateSectionCommand::with_objcopy_path()`, if any.
+    /// Otherwise it is taken from the `OBJCOPY` environment variable (see `tool_from_env()`),
+    /// or else from the LLVM tools directory of the Rust toolchain, or else GNU `objcopy`
+    /// from `PATH`.
+    pub fn new(objcopy: Option<&Path>) -> Result<Self, String> {
+        let objcopy = objcopy
+            .map(Path::to_path_buf)
+            .or_else(|| tool_from_env("OBJCOPY"));
+        let objcopy = match objcopy {
+            Some(objcopy) => objcopy,
+            None => bundled("llvm-objcopy").or_else(|e| find_in_path("objcopy").ok_or(e))?,
+        };
+        Ok(Self { objcopy })
     }
 
     /// Updates a section in a binary using llvm-objcopy.
@@ -101,28 +53,20 @@ impl LlvmTools {
         output: impl AsRef<Path>,
         section_name: &str,
         section_file: impl AsRef<Path>,
-    ) -> io::Result<()> {
-        let input = input.as_ref();
+    ) -> Result<(), VerShimError> {
         let output = output.as_ref();
-        let section_file = section_file.as_ref();
-
-        let objcopy_path = self.bin_dir.join(format!("llvm-objcopy{}", EXE_SUFFIX));
-        let update_arg = format!("{}={}", section_name, section_file.display());
-
-        let status = Command::new(&objcopy_path)
-            .arg("--update-section")
-            .arg(&update_arg)
-            .arg(input)
-            .arg(output)
-            .status()?;
-
-        if !status.success() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("llvm-objcopy failed with status {}", status),
-            ));
-        }
-
+        let update_arg = format!("{}={}", section_name, section_file.as_ref().display());
+        self.run(
+            &self.objcopy,
+            &[
+                "--update-section".as_ref(),
+                update_arg.as_ref(),
+                input.as_ref().as_os_str(),
+                output.as_os_str(),
+            ],
+            None,
+            Some(output),
+        )?;
         Ok(())
     }
 
@@ -139,38 +83,230 @@ impl LlvmTools {
         output: impl AsRef<Path>,
         section_name: &str,
         bytes: &[u8],
-    ) -> io::Result<()> {
-        let input = input.as_ref();
+    ) -> Result<(), VerShimError> {
         let output = output.as_ref();
+        let update_arg = format!("{}=/dev/stdin", section_name);
+        self.run(
+            &self.objcopy,
+            &[
+                "--update-section".as_ref(),
+                update_arg.as_ref(),
+                input.as_ref().as_os_str(),
+                output.as_os_str(),
+            ],
+            Some(bytes),
+            Some(output),
+        )?;
+        Ok(())
+    }
 
-        let objcopy_path = self.bin_dir.join(format!("llvm-objcopy{}", EXE_SUFFIX));
+    /// Gets the command line which `update_section_with_bytes()` runs, for a dry run.
+    pub fn update_section_command_line(
+        &self,
+        input: &Path,
+        output: &Path,
+        section_name: &str,
+    ) -> String {
         let update_arg = format!("{}=/dev/stdin", section_name);
+        command_line(
+            &self.objcopy,
+            &[
+                "--update-section".as_ref(),
+                update_arg.as_ref(),
+                input.as_os_str(),
+                output.as_os_str(),
+            ],
+        )
+    }
 
-        let mut child = Command::new(&objcopy_path)
-            .arg("--update-section")
-            .arg(&update_arg)
-            .arg(input)
-            .arg(output)
-            .stdin(Stdio::piped())
-            .spawn()?;
-
-        // Write bytes to stdin and close the pipe
-        let mut stdin = child
-            .stdin
-            .take()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "failed to open stdin"))?;
-        stdin.write_all(bytes)?;
-        drop(stdin); // Close the pipe
-
-        let status = child.wait()?;
-
-        if !status.success() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("llvm-objcopy failed with status {}", status),
-            ));
-        }
+    /// Writes a copy of `input` containing only the debug sections to `debug_output`,
+    /// using `llvm-objcopy --only-keep-debug`.
+    pub fn only_keep_debug(
+        &self,
+        input: impl AsRef<Path>,
+        debug_output: impl AsRef<Path>,
+    ) -> Result<(), VerShimError> {
+        let debug_output = debug_output.as_ref();
+        self.run(
+            &self.objcopy,
+            &[
+                "--only-keep-debug".as_ref(),
+                input.as_ref().as_os_str(),
+                debug_output.as_os_str(),
+            ],
+            None,
+            Some(debug_output),
+        )?;
+        Ok(())
+    }
 
+    /// Strips the debug sections from `input`, writing the result to `output` with a
+    /// `.gnu_debuglink` pointing at `debug_file`, using
+    /// `llvm-objcopy --strip-debug --add-gnu-debuglink`.
+    ///
+    /// `input` and `output` may be the same path.
+    pub fn strip_debug(
+        &self,
+        input: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+        debug_file: impl AsRef<Path>,
+    ) -> Result<(), VerShimError> {
+        let output = output.as_ref();
+        let debuglink_arg = format!("--add-gnu-debuglink={}", debug_file.as_ref().display());
+        self.run(
+            &self.objcopy,
+            &[
+                "--strip-debug".as_ref(),
+                debuglink_arg.as_ref(),
+                input.as_ref().as_os_str(),
+                output.as_os_str(),
+            ],
+            None,
+            Some(output),
+        )?;
         Ok(())
     }
+
+    /// Runs an LLVM tool with the given arguments, and optionally bytes on stdin.
+    ///
+    /// `output` is the file the tool writes, if any, which is used to guess the cause
+    /// if it fails. Returns `ToolFailed` if the tool exits with a non-zero status.
+    fn run(
+        &self,
+        tool_path: &Path,
+        args: &[&OsStr],
+        stdin: Option<&[u8]>,
+        output: Option<&Path>,
+    ) -> Result<Output, VerShimError> {
+        let run_err = |e| VerShimError::io(format!("failed to run {}", tool_path.display()), e);
+
+        let mut child = Command::new(tool_path)
+            .args(args)
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(run_err)?;
+
+        if let Some(bytes) = stdin {
+            // Write bytes to stdin and close the pipe
+            let mut pipe = child.stdin.take().expect("stdin is piped");
+            pipe.write_all(bytes).map_err(run_err)?;
+        }
+
+        let result = child.wait_with_output().map_err(run_err)?;
+        if result.status.success() {
+            return Ok(result);
+        }
+
+        let stderr = String::from_utf8_lossy(&result.stderr).into_owned();
+        Err(VerShimError::ToolFailed(ToolFailure {
+            command: command_line(tool_path, args),
+            status: result.status.to_string(),
+            cause: suspect_cause(&stderr, output),
+            stderr,
+        }))
+    }
+}
+
+/// Renders a command line, quoting the arguments which need it, so it can be pasted
+/// into a shell.
+fn command_line(tool_path: &Path, args: &[&OsStr]) -> String {
+    std::iter::once(tool_path.as_os_str())
+        .chain(args.iter().copied())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("{:?}", arg)
+            } else {
+                arg.into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Gets the path of `tool` in the LLVM tools directory of the Rust toolchain.
+///
+/// Returns `Err` if the directory can't be determined, or the tool isn't there because
+/// the `llvm-tools` component isn't installed.
+fn bundled(tool: &str) -> Result<PathBuf, String> {
+    let path = rustc::llvm_tools_bin_dir()?.join(format!("{}{}", tool, EXE_SUFFIX));
+    if !path.is_file() {
+        return Err(format!("{} not found", path.display()));
+    }
+    Ok(path)
+}
+
+/// Finds `tool` in the directories of `PATH`.
+fn find_in_path(tool: &str) -> Option<PathBuf> {
+    let file_name = format!("{}{}", tool, EXE_SUFFIX);
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// Gets a tool override from the environment, e.g. for a vendor toolchain when
+/// cross-compiling.
+///
+/// Like the `cc` crate does for `CC`, this checks `{var}_{target}` (e.g.
+/// `OBJCOPY_thumbv7em-none-eabihf` or `OBJCOPY_thumbv7em_none_eabihf`) and
+/// `TARGET_{var}` when `TARGET` is set, as it is for build scripts, then `{var}`.
+/// The value may be a path, or the name of a program in `PATH`.
+fn tool_from_env(var: &str) -> Option<PathBuf> {
+    let mut names = Vec::new();
+    if let Ok(target) = env::var("TARGET") {
+        names.push(format!("{}_{}", var, target));
+        names.push(format!("{}_{}", var, target.replace('-', "_")));
+        names.push(format!("TARGET_{}", var));
+    }
+    names.push(var.to_string());
+    names.into_iter().find_map(|name| {
+        cargo_rerun_if(&format!("env-changed={}", name));
+        env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+/// Guesses why an LLVM tool failed, from its stderr and the file it was writing.
+fn suspect_cause(stderr: &str, output: Option<&Path>) -> Option<SuspectedCause> {
+    let stderr = stderr.to_ascii_lowercase();
+    if stderr.contains("section")
+        && (stderr.contains("not found") || stderr.contains("could not find"))
+    {
+        return Some(SuspectedCause::MissingSection);
+    }
+    if stderr.contains("permission denied")
+        || stderr.contains("read-only file system")
+        || output.is_some_and(is_read_only)
+    {
+        return Some(SuspectedCause::ReadOnlyDestination);
+    }
+    if stderr.contains("unsupported")
+        || stderr.contains("not recognized")
+        || stderr.contains("unknown architecture")
+        || stderr.contains("invalid object")
+    {
+        return Some(SuspectedCause::ArchMismatch);
+    }
+    None
+}
+
+/// Returns true if `path` is a read-only file, or doesn't exist and its directory is
+/// read-only.
+fn is_read_only(path: &Path) -> bool {
+    let existing = if path.exists() {
+        path
+    } else {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        }
+    };
+    fs::metadata(existing).is_ok_and(|metadata| metadata.permissions().readonly())
 }
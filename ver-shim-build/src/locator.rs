@@ -0,0 +1,55 @@
+//! Locating the version buffer by its magic prefix, for the `locator` feature of `ver-shim`.
+//!
+//! When `ver-shim` is built with the `locator` feature, the buffer is not placed in a
+//! named link section. Instead it is preceded by `LOCATOR_MAGIC` and the buffer size,
+//! and we find it by scanning the file contents for the magic bytes. This works for any
+//! file format which stores the initialized data verbatim.
+
+use ver_shim::{LOCATOR_MAGIC, LOCATOR_PREFIX_LEN};
+
+/// Location of the version buffer within a file.
+#[derive(Clone, Copy, Debug)]
+pub struct Location {
+    /// Offset of the buffer (just after the prefix) from the start of the file.
+    pub offset: usize,
+    /// Size of the buffer in bytes.
+    pub size: usize,
+}
+
+/// Finds the version buffer in the file contents by scanning for the magic prefix.
+///
+/// Returns `Ok(None)` if the magic is not found, or `Err` if it is found more than
+/// once or the buffer would extend past the end of the file.
+pub fn find(data: &[u8]) -> Result<Option<Location>, String> {
+    let mut found = None;
+    for (pos, window) in data.windows(LOCATOR_MAGIC.len()).enumerate() {
+        if window != LOCATOR_MAGIC {
+            continue;
+        }
+        if found.is_some() {
+            return Err(format!(
+                "found the ver-shim magic prefix more than once (at least at offsets {} and {}), \
+                 are there multiple copies of ver-shim in the binary?",
+                found
+                    .map(|l: Location| l.offset - LOCATOR_PREFIX_LEN)
+                    .unwrap_or(0),
+                pos
+            ));
+        }
+
+        let size_pos = pos + LOCATOR_MAGIC.len();
+        let Some(size_bytes) = data.get(size_pos..size_pos + 2) else {
+            return Err(format!("truncated ver-shim prefix at offset {}", pos));
+        };
+        let size = u16::from_le_bytes([size_bytes[0], size_bytes[1]]) as usize;
+        let offset = pos + LOCATOR_PREFIX_LEN;
+        if offset + size > data.len() {
+            return Err(format!(
+                "ver-shim buffer at offset {} (size {}) extends past the end of the file",
+                offset, size
+            ));
+        }
+        found = Some(Location { offset, size });
+    }
+    Ok(found)
+}
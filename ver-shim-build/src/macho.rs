@@ -0,0 +1,283 @@
+//! Patching Mach-O binaries (macOS, iOS), including universal binaries.
+//!
+//! On Apple targets, `ver-shim` places the buffer in the `__DATA,__ver_shim_data` section.
+//! Rather than relying on llvm-objcopy's Mach-O support, we overwrite the section contents
+//! in place, like for static library archives. Since the section doesn't change size,
+//! the load commands stay valid.
+//!
+//! Patching invalidates the code signature, which every binary on Apple silicon has
+//! (at least an ad-hoc one added by the linker), so the binary must be re-signed.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::Path;
+use std::process::Command;
+
+use object::macho::LC_CODE_SIGNATURE;
+use object::read::macho::{FatArch, MachHeader, MachOFatFile32, MachOFatFile64};
+use object::{Endianness, FileKind, ObjectSection, macho};
+
+use crate::native;
+
+/// Returns true if the file at `path` is a Mach-O binary or a universal binary.
+pub fn is_macho_file(path: &Path) -> io::Result<bool> {
+    // `FileKind::parse()` needs 16 bytes, e.g. to tell universal binaries from Java classes
+    let mut magic = [0u8; 16];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(matches!(
+            FileKind::parse(&magic[..]),
+            Ok(FileKind::MachO32 | FileKind::MachO64 | FileKind::MachOFat32 | FileKind::MachOFat64)
+        )),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns true if the data is a universal binary, with several architectures.
+pub fn is_universal(data: &[u8]) -> bool {
+    matches!(
+        FileKind::parse(data),
+        Ok(FileKind::MachOFat32 | FileKind::MachOFat64)
+    )
+}
+
+/// Gets the file range of each architecture in a Mach-O or universal binary.
+fn arches(data: &[u8]) -> Result<Vec<Range<usize>>, String> {
+    let kind = FileKind::parse(data).map_err(|e| format!("invalid Mach-O file: {}", e))?;
+    let ranges = match kind {
+        FileKind::MachO32 | FileKind::MachO64 => vec![(0, data.len() as u64)],
+        FileKind::MachOFat32 => MachOFatFile32::parse(data)
+            .map_err(|e| format!("invalid universal binary: {}", e))?
+            .arches()
+            .iter()
+            .map(|arch| arch.file_range())
+            .collect::<Vec<_>>(),
+        FileKind::MachOFat64 => MachOFatFile64::parse(data)
+            .map_err(|e| format!("invalid universal binary: {}", e))?
+            .arches()
+            .iter()
+            .map(|arch| arch.file_range())
+            .collect::<Vec<_>>(),
+        _ => return Err(format!("not a Mach-O file: {:?}", kind)),
+    };
+    Ok(ranges
+        .into_iter()
+        .map(|(offset, size)| offset as usize..(offset + size) as usize)
+        .collect())
+}
+
+/// Finds the file ranges of the version section in every architecture that has it.
+pub fn find_sections(data: &[u8], custom_name: Option<&str>) -> Result<Vec<Range<usize>>, String> {
+    let mut ranges = Vec::new();
+    for arch in arches(data)? {
+        let arch_data = data
+            .get(arch.clone())
+            .ok_or("universal binary architecture is out of bounds")?;
+        let file = object::File::parse(arch_data)
+            .map_err(|e| format!("invalid Mach-O architecture: {}", e))?;
+        let Some(section) = native::version_section(&file, custom_name) else {
+            continue;
+        };
+        let Some((offset, size)) = section.file_range() else {
+            continue;
+        };
+        let start = arch.start + offset as usize;
+        ranges.push(start..start + size as usize);
+    }
+    Ok(ranges)
+}
+
+/// Returns true if any architecture has a code signature.
+pub fn has_code_signature(data: &[u8]) -> Result<bool, String> {
+    for arch in arches(data)? {
+        let arch_data = &data[arch];
+        let signed = match FileKind::parse(arch_data) {
+            Ok(FileKind::MachO32) => {
+                load_commands_have_signature::<macho::MachHeader32<Endianness>>(arch_data)
+            }
+            Ok(FileKind::MachO64) => {
+                load_commands_have_signature::<macho::MachHeader64<Endianness>>(arch_data)
+            }
+            _ => Ok(false),
+        }
+        .map_err(|e| format!("invalid Mach-O load commands: {}", e))?;
+        if signed {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn load_commands_have_signature<Mach: MachHeader>(data: &[u8]) -> object::Result<bool> {
+    let header = Mach::parse(data, 0)?;
+    let endian = header.endian()?;
+    let mut commands = header.load_commands(endian, data, 0)?;
+    while let Some(command) = commands.next()? {
+        if command.cmd() == LC_CODE_SIGNATURE {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Re-signs a binary with an ad-hoc signature, using `codesign`.
+///
+/// The identifier of the existing signature is kept, rather than derived from the file
+/// name, which may be that of a temporary file.
+///
+/// Returns `Ok(false)` if `codesign` is not available, e.g. when cross-compiling.
+pub fn adhoc_sign(path: &Path) -> io::Result<bool> {
+    let output = match Command::new("codesign")
+        .args(["--force", "--sign", "-", "--preserve-metadata=identifier"])
+        .arg(path)
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "codesign failed with status {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, CPU_TYPE_ARM64, CPU_TYPE_X86_64, SECTION_SIZE};
+    use crate::{Level, LinkSection, Member, SectionData};
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+
+    fn universal(signed: bool) -> Vec<u8> {
+        let section = fixtures::unpatched_section();
+        fixtures::fat_binary(&[
+            (
+                CPU_TYPE_X86_64,
+                &fixtures::macho(CPU_TYPE_X86_64, &section, signed),
+            ),
+            (
+                CPU_TYPE_ARM64,
+                &fixtures::macho(CPU_TYPE_ARM64, &section, signed),
+            ),
+        ])
+    }
+
+    /// Patches `data` with a custom string, returning the output and the warnings.
+    fn patch(name: &str, data: &[u8]) -> (Result<Vec<u8>, crate::VerShimError>, Vec<String>) {
+        let dir = fixtures::temp_dir(name);
+        let (input, output) = (dir.join("app"), dir.join("app_patched"));
+        fs::write(&input, data).unwrap();
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let result = LinkSection::new()
+            .with_diagnostics({
+                let warnings = warnings.clone();
+                move |diag| {
+                    if diag.level == Level::Warning {
+                        warnings.lock().unwrap().push(diag.message.clone());
+                    }
+                }
+            })
+            .with_custom("universal")
+            .patch_into(&input)
+            .try_write_to(&output)
+            .map(|_| fs::read(&output).unwrap());
+        fs::remove_dir_all(dir).unwrap();
+        let warnings = warnings.lock().unwrap().clone();
+        (result, warnings)
+    }
+
+    #[test]
+    fn detect_macho_file() {
+        let dir = fixtures::temp_dir("macho_detect");
+        let path = dir.join("app");
+        let thin = fixtures::macho(CPU_TYPE_ARM64, &fixtures::unpatched_section(), false);
+        for (data, expected) in [
+            (thin, true),
+            (universal(false), true),
+            (fixtures::elf_object(&[]), false),
+            (b"\xfe\xed\xfa".to_vec(), false),
+        ] {
+            fs::write(&path, data).unwrap();
+            assert_eq!(is_macho_file(&path).unwrap(), expected);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn thin_binary() {
+        let data = fixtures::macho(CPU_TYPE_ARM64, &fixtures::unpatched_section(), false);
+        assert!(!is_universal(&data));
+        let ranges = find_sections(&data, None).unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(&data[ranges[0].clone()], &fixtures::unpatched_section()[..]);
+        assert_eq!(
+            find_sections(&data, Some("__DATA,__ver_shim_data")).unwrap(),
+            ranges
+        );
+        assert_eq!(
+            find_sections(&data, Some("__DATA,__other")).unwrap(),
+            vec![]
+        );
+        assert!(!has_code_signature(&data).unwrap());
+    }
+
+    #[test]
+    fn universal_binary() {
+        let data = universal(false);
+        assert!(is_universal(&data));
+        let ranges = find_sections(&data, None).unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert!(ranges.iter().all(|range| range.len() == SECTION_SIZE));
+        assert!(ranges[1].start > ranges[0].end);
+        assert!(!has_code_signature(&data).unwrap());
+        assert!(has_code_signature(&universal(true)).unwrap());
+
+        assert!(find_sections(b"\xca\xfe\xba\xbe\0\0\0\x01", None).is_err());
+    }
+
+    #[test]
+    fn patch_every_architecture() {
+        let data = universal(false);
+        let (patched, warnings) = patch("macho_universal", &data);
+        let patched = patched.unwrap();
+        assert_eq!(warnings, Vec::<String>::new());
+        assert_eq!(patched.len(), data.len());
+        for range in find_sections(&patched, None).unwrap() {
+            let section = SectionData::parse(&patched[range]).unwrap();
+            assert_eq!(section.get(Member::Custom), Some("universal"));
+        }
+    }
+
+    #[test]
+    fn sections_of_different_sizes() {
+        let data = fixtures::fat_binary(&[
+            (
+                CPU_TYPE_X86_64,
+                &fixtures::macho(CPU_TYPE_X86_64, &fixtures::unpatched_section(), false),
+            ),
+            (
+                CPU_TYPE_ARM64,
+                &fixtures::macho(CPU_TYPE_ARM64, &ver_shim::unpatched_buffer::<256>(), false),
+            ),
+        ]);
+        let err = patch("macho_sizes", &data).0.unwrap_err().to_string();
+        assert!(err.contains("different sizes"), "{}", err);
+    }
+
+    // codesign is not available to re-sign the patched binary
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn signature_is_invalidated() {
+        let (patched, warnings) = patch("macho_signed", &universal(true));
+        patched.unwrap();
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("invalidated the code signature"));
+    }
+}
@@ -0,0 +1,82 @@
+//! Patching binaries in place, without llvm-objcopy.
+//!
+//! The version section is found with the `object` crate, and its contents are
+//! overwritten in the file. Since the section doesn't change size, none of the headers
+//! need to be updated. This is how PE images are always patched, and how ELF binaries
+//! are patched when llvm-tools is not installed. The section table is read the same way
+//! when llvm-objcopy does the patching, rather than by running a tool and parsing its
+//! output.
+
+use std::ops::Range;
+
+use object::{BinaryFormat, Object, ObjectSection};
+use ver_shim::{COFF_SECTION_NAME, SECTION_NAME};
+
+use crate::error::VerShimError;
+
+/// Gets the name of the version section in an object file or image of the given format.
+///
+/// `custom_name` is the name set with `LinkSection::with_section_name()`, if any.
+/// For Mach-O it may be given as `segment,section`, and only the section part is used.
+pub fn section_name(format: BinaryFormat, custom_name: Option<&str>) -> &str {
+    match (format, custom_name) {
+        (BinaryFormat::MachO, Some(name)) => name.rsplit(',').next().unwrap_or(name),
+        (_, Some(name)) => name,
+        (BinaryFormat::Coff | BinaryFormat::Pe, None) => COFF_SECTION_NAME,
+        (_, None) => SECTION_NAME,
+    }
+}
+
+/// Gets the version section of an object file or image, whatever its format.
+pub fn version_section<'data, 'file>(
+    file: &'file object::File<'data>,
+    custom_name: Option<&str>,
+) -> Option<object::Section<'data, 'file>> {
+    file.section_by_name(section_name(file.format(), custom_name))
+}
+
+/// Finds the file range of the version section of an executable or shared library.
+///
+/// Returns `Ok(None)` if there is no version section. For PE images, the range covers
+/// only the initialized data of the section, not the padding up to the file alignment.
+///
+/// Returns `MissingSection` if the section has no data in the file (e.g. if the linker
+/// made it NOBITS), since it can't be patched in place then.
+pub fn find_section(
+    data: &[u8],
+    custom_name: Option<&str>,
+) -> Result<Option<Range<usize>>, VerShimError> {
+    let file = object::File::parse(data)
+        .map_err(|e| VerShimError::InvalidBinary(format!("invalid binary: {}", e)))?;
+    let Some(section) = version_section(&file, custom_name) else {
+        return Ok(None);
+    };
+    let (offset, size) = section.file_range().ok_or_else(|| {
+        VerShimError::MissingSection(format!(
+            "section '{}' has no data in the file, so it can't be patched in place",
+            section_name(file.format(), custom_name)
+        ))
+    })?;
+    Ok(Some(offset as usize..(offset + size) as usize))
+}
+
+/// Gets the size of the version section of an object file or image.
+///
+/// Returns `Ok(None)` if there is no version section.
+pub fn section_size(data: &[u8], custom_name: Option<&str>) -> Result<Option<usize>, VerShimError> {
+    let file = object::File::parse(data)
+        .map_err(|e| VerShimError::InvalidBinary(format!("invalid binary: {}", e)))?;
+    Ok(version_section(&file, custom_name).map(|section| section.size() as usize))
+}
+
+/// Gets the GNU build-id of a binary, as a lowercase hex string.
+///
+/// Returns `Ok(None)` if the binary has no `.note.gnu.build-id` note.
+pub fn build_id(data: &[u8]) -> Result<Option<String>, VerShimError> {
+    let file = object::File::parse(data)
+        .map_err(|e| VerShimError::InvalidBinary(format!("invalid binary: {}", e)))?;
+    let build_id = file
+        .build_id()
+        .map_err(|e| VerShimError::InvalidBinary(format!("invalid build-id note: {}", e)))?;
+    Ok(build_id.map(|id| id.iter().map(|b| format!("{:02x}", b)).collect()))
+}
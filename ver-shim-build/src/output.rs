@@ -0,0 +1,60 @@
+//! Atomic writes of output files, so that a killed build doesn't leave a truncated file.
+//!
+//! Outputs are written to a temporary file next to the destination, then renamed over it.
+//! The temporary file is in the same directory, so the rename doesn't cross filesystems.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::VerShimError;
+
+/// Gets the temporary path that an output is written to before being renamed to `path`.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Writes `contents` to `path`, atomically unless `atomic` is false.
+pub(crate) fn write(path: &Path, contents: impl AsRef<[u8]>, atomic: bool) -> io::Result<()> {
+    if !atomic {
+        return fs::write(path, contents);
+    }
+    let temp = temp_path(path);
+    let result = fs::write(&temp, contents).and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Calls `f` with the path to write an output to, then moves that to `path`.
+///
+/// If `atomic` is true, `f` writes to a temporary file, which is renamed to `path` once
+/// `f` succeeds, and removed if it fails. Otherwise `f` writes to `path` directly.
+pub(crate) fn replace<T>(
+    path: &Path,
+    atomic: bool,
+    f: impl FnOnce(&Path) -> Result<T, VerShimError>,
+) -> Result<T, VerShimError> {
+    if !atomic {
+        return f(path);
+    }
+    let temp = temp_path(path);
+    let result = f(&temp).and_then(|value| {
+        fs::rename(&temp, path).map_err(|e| {
+            VerShimError::io(
+                format!("failed to rename {} to {}", temp.display(), path.display()),
+                e,
+            )
+        })?;
+        Ok(value)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
@@ -0,0 +1,135 @@
+//! Git data for the last commit touching a path, see `LinkSection::with_git_path_scope()`.
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::git_provider::{DescribeOpts, GitCli, GitProvider};
+
+/// Runs the `git` command line tool like [`GitCli`], but describes the last commit which
+/// touched `path` rather than `HEAD`.
+///
+/// The branch, the branches at `HEAD`, the submodules, and the remote are those of the
+/// repository, as with [`GitCli`].
+pub(crate) struct PathScopedGit {
+    /// A git pathspec, relative to the current directory.
+    pub(crate) path: String,
+}
+
+impl PathScopedGit {
+    /// Runs `git log -1` with a format for the last commit touching the path.
+    fn log(&self, format: &str) -> Result<String, String> {
+        let output = GitCli.run(&[
+            "log",
+            "-1",
+            &format!("--format={}", format),
+            "--",
+            &self.path,
+        ])?;
+        if output.is_empty() {
+            return Err(format!("no commit touches {}", self.path));
+        }
+        Ok(output)
+    }
+
+    /// Gets the name and email of the author (`'a'`) or committer (`'c'`).
+    fn identity(&self, who: char) -> Result<(String, String), String> {
+        let output = self.log(&format!("%{who}n%n%{who}e"))?;
+        let (name, email) = output.split_once('\n').unwrap_or((&output, ""));
+        Ok((name.to_string(), email.to_string()))
+    }
+
+    /// Gets the author (`'a'`) or committer (`'c'`) date.
+    fn timestamp(&self, who: char) -> Result<DateTime<FixedOffset>, String> {
+        let timestamp = self.log(&format!("%{who}I"))?;
+        DateTime::parse_from_rfc3339(&timestamp)
+            .map_err(|e| format!("failed to parse git timestamp '{}': {}", timestamp, e))
+    }
+}
+
+impl GitProvider for PathScopedGit {
+    fn sha(&self) -> Result<String, String> {
+        self.log("%H")
+    }
+
+    fn describe(&self) -> Result<String, String> {
+        self.describe_with(&DescribeOpts::default())
+    }
+
+    fn describe_with(&self, opts: &DescribeOpts) -> Result<String, String> {
+        let sha = self.sha()?;
+        let mut args = vec!["describe".to_string(), "--always".to_string()];
+        if opts.tags {
+            args.push("--tags".to_string());
+        }
+        if let Some(ref pattern) = opts.match_pattern {
+            args.push(format!("--match={}", pattern));
+        }
+        if let Some(abbrev) = opts.abbrev {
+            args.push(format!("--abbrev={}", abbrev));
+        }
+        args.push(sha);
+        let mut describe = GitCli.run(&args.iter().map(String::as_str).collect::<Vec<_>>())?;
+        // `--dirty` only works for HEAD, and would look at the whole work tree
+        if self.is_dirty()? {
+            describe.push_str(opts.dirty_suffix.as_deref().unwrap_or("-dirty"));
+        }
+        Ok(describe)
+    }
+
+    fn branch(&self) -> Result<String, String> {
+        GitCli.branch()
+    }
+
+    fn commit_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.timestamp('a')
+    }
+
+    fn commit_msg(&self) -> Result<String, String> {
+        self.log("%s")
+    }
+
+    fn is_dirty(&self) -> Result<bool, String> {
+        GitCli
+            .run(&[
+                "status",
+                "--porcelain",
+                "--untracked-files=no",
+                "--",
+                &self.path,
+            ])
+            .map(|status| !status.is_empty())
+    }
+
+    fn tags(&self) -> Result<Vec<String>, String> {
+        let sha = self.sha()?;
+        let output = GitCli.run(&["tag", "--points-at", &sha, "--sort=-v:refname"])?;
+        Ok(output.lines().map(str::to_string).collect())
+    }
+
+    fn commit_author(&self) -> Result<(String, String), String> {
+        self.identity('a')
+    }
+
+    fn committer(&self) -> Result<(String, String), String> {
+        self.identity('c')
+    }
+
+    fn committer_timestamp(&self) -> Result<DateTime<FixedOffset>, String> {
+        self.timestamp('c')
+    }
+
+    fn branches_at_head(&self) -> Result<Vec<String>, String> {
+        GitCli.branches_at_head()
+    }
+
+    fn nearest_branch(&self) -> Result<String, String> {
+        GitCli.nearest_branch()
+    }
+
+    fn submodules(&self) -> Result<Vec<(String, String)>, String> {
+        GitCli.submodules()
+    }
+
+    fn remote_url(&self) -> Result<String, String> {
+        GitCli.remote_url()
+    }
+}
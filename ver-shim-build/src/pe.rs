@@ -0,0 +1,135 @@
+//! PE/COFF helpers.
+//!
+//! PE images only have room for 8-byte section names, so on Windows targets `ver-shim`
+//! places the buffer in `COFF_SECTION_NAME` rather than `SECTION_NAME`. PE images are
+//! patched in place (see `native`) rather than relying on llvm-objcopy's COFF support,
+//! which would also lay out the image again.
+//!
+//! Modifying a PE image invalidates the `CheckSum` field of its optional header. The
+//! Windows loader only checks it for drivers and some system DLLs, but AV and signing
+//! tooling may flag a wrong checksum, so we recompute it after patching.
+//!
+//! See: https://learn.microsoft.com/en-us/windows/win32/debug/pe-format#optional-header-windows-specific-fields-image-only
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::diagnostics::Diagnostics;
+
+/// Offset of `e_lfanew` (the offset of the PE signature) in the DOS header.
+const E_LFANEW_OFFSET: usize = 0x3c;
+
+/// Size of the PE signature plus the COFF file header.
+const PE_HEADERS_SIZE: usize = 4 + 20;
+
+/// Offset of `CheckSum` within the optional header (same for PE32 and PE32+).
+const CHECKSUM_OFFSET: usize = 64;
+
+/// Index of the certificate table (Authenticode signature) in the data directories.
+const CERTIFICATE_TABLE_INDEX: usize = 4;
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Returns the offset of the optional header, if the data is a PE image.
+fn optional_header_offset(data: &[u8]) -> Option<usize> {
+    if !data.starts_with(b"MZ") {
+        return None;
+    }
+    let pe_offset = read_u32(data, E_LFANEW_OFFSET)? as usize;
+    if data.get(pe_offset..pe_offset + 4)? != b"PE\0\0" {
+        return None;
+    }
+    Some(pe_offset + PE_HEADERS_SIZE)
+}
+
+/// Returns true if the file at `path` is a PE image.
+pub fn is_pe_file(path: &Path) -> io::Result<bool> {
+    Ok(optional_header_offset(&fs::read(path)?).is_some())
+}
+
+/// Returns true if the PE image has an Authenticode signature (a non-empty certificate table).
+fn has_certificate_table(data: &[u8], optional_header: usize) -> bool {
+    // The data directories follow the Windows-specific fields, whose size depends on
+    // whether this is PE32 (magic 0x10b) or PE32+ (magic 0x20b).
+    let data_directories = match read_u16(data, optional_header) {
+        Some(0x10b) => optional_header + 96,
+        Some(0x20b) => optional_header + 112,
+        _ => return false,
+    };
+    let entry = data_directories + CERTIFICATE_TABLE_INDEX * 8;
+    read_u32(data, entry + 4).is_some_and(|size| size != 0)
+}
+
+/// Computes the PE checksum of the image, as done by `CheckSumMappedFile`.
+///
+/// This is the 16-bit one's complement sum of the file (with the checksum field itself
+/// treated as zero), plus the file length.
+pub fn compute_checksum(data: &[u8], checksum_offset: usize) -> u32 {
+    let mut sum: u64 = 0;
+    for (idx, chunk) in data.chunks(2).enumerate() {
+        let pos = idx * 2;
+        if pos >= checksum_offset && pos < checksum_offset + 4 {
+            continue;
+        }
+        let word = match chunk {
+            [lo, hi] => u16::from_le_bytes([*lo, *hi]),
+            [lo] => *lo as u16,
+            _ => unreachable!(),
+        };
+        sum += word as u64;
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum = (sum & 0xffff) + (sum >> 16);
+    (sum as u32).wrapping_add(data.len() as u32)
+}
+
+/// Recomputes and writes the checksum of a PE image in place.
+///
+/// Returns `Ok(false)` without touching the file if it is not a PE image.
+///
+/// This must be the last modification of the image before it is signed, since
+/// Authenticode signing covers the rest of the headers (and recomputes the checksum).
+/// If the image already had an Authenticode signature, patching invalidated it and a
+/// warning is emitted.
+pub fn update_checksum(path: &Path, diag: &Diagnostics) -> io::Result<bool> {
+    let mut data = fs::read(path)?;
+    let Some(optional_header) = optional_header_offset(&data) else {
+        return Ok(false);
+    };
+    let checksum_offset = optional_header + CHECKSUM_OFFSET;
+    if checksum_offset + 4 > data.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "PE optional header is truncated",
+        ));
+    }
+
+    if has_certificate_table(&data, optional_header) {
+        diag.warning(format!(
+            "{} has an Authenticode signature, which is invalidated by patching. \
+             Re-sign the binary after patching.",
+            path.display()
+        ));
+    }
+
+    let checksum = compute_checksum(&data, checksum_offset);
+    data[checksum_offset..checksum_offset + 4].copy_from_slice(&checksum.to_le_bytes());
+    fs::write(path, &data)?;
+    diag.info(format!(
+        "updated PE checksum of {} to {:#010x}",
+        path.display(),
+        checksum
+    ));
+    Ok(true)
+}
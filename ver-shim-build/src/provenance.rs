@@ -0,0 +1,135 @@
+//! Provenance statements for patched binaries, see
+//! `UpdateSectionCommand::with_provenance_file()`.
+//!
+//! The statement is an [in-toto Statement v1](https://github.com/in-toto/attestation/blob/main/spec/v1/statement.md)
+//! with a [SLSA Provenance v1](https://slsa.dev/spec/v1.0/provenance) predicate, written
+//! on a single line so that the file is also a valid in-toto bundle (`.intoto.jsonl`).
+
+use sha2::{Digest, Sha256};
+use ver_shim::Member;
+
+use crate::CollectedSection;
+use crate::ci;
+use crate::kv;
+use crate::section_data::json_string;
+
+/// The build type of the statement, which defines the meaning of its parameters.
+const BUILD_TYPE: &str = "https://github.com/cbeck88/ver-shim-rs/provenance/v1";
+
+/// Builds the provenance statement for a patched binary.
+///
+/// The subject is the binary, by name and SHA-256. The git commit (and the remote, if
+/// collected) and the lockfile hash are the resolved dependencies, and the cargo package
+/// data and build profile are the external parameters. The builder and the invocation
+/// come from the CI provider, detected when the statement is written.
+pub(crate) fn statement(name: &str, binary: &[u8], section: &CollectedSection) -> String {
+    let subject = object(&[
+        ("name", json_string(name)),
+        (
+            "digest",
+            object(&[("sha256", json_string(&sha256(binary)))]),
+        ),
+    ]);
+
+    let mut parameters = Vec::new();
+    for (key, member) in [
+        ("package", Member::PkgName),
+        ("version", Member::PkgVersion),
+        ("target", Member::TargetTriple),
+        ("branch", Member::GitBranch),
+    ] {
+        if let Some(value) = section.get(member) {
+            parameters.push((key, json_string(value)));
+        }
+    }
+    if let Some(profile) = section.get(Member::BuildProfile)
+        && let Some(profile) = kv::decode(profile).get("profile")
+    {
+        parameters.push(("profile", json_string(profile)));
+    }
+
+    let mut dependencies = Vec::new();
+    if let Some(sha) = section.get(Member::GitSha) {
+        let digest = ("digest", object(&[("gitCommit", json_string(sha))]));
+        dependencies.push(match section.get(Member::GitRemote) {
+            Some(remote) => object(&[("uri", json_string(&format!("git+{}", remote))), digest]),
+            None => object(&[("name", json_string("source")), digest]),
+        });
+    }
+    if let Some(hash) = section.get(Member::LockfileHash) {
+        dependencies.push(object(&[
+            ("name", json_string("Cargo.lock")),
+            ("digest", object(&[("sha256", json_string(hash))])),
+        ]));
+    }
+
+    let ci_info = ci::detect();
+    let provider = ci_info
+        .as_ref()
+        .and_then(|info| info.get("provider"))
+        .map_or("local", String::as_str);
+    let builder = object(&[
+        (
+            "id",
+            json_string(&format!("urn:ver-shim:builder:{}", provider)),
+        ),
+        (
+            "version",
+            object(&[("ver-shim-build", json_string(env!("CARGO_PKG_VERSION")))]),
+        ),
+    ]);
+    let invocation_id = ci_info
+        .as_ref()
+        .and_then(|info| info.get("run_url").or_else(|| info.get("pipeline_id")));
+    let metadata = match invocation_id {
+        Some(id) => object(&[("invocationId", json_string(id))]),
+        None => object(&[]),
+    };
+
+    let predicate = object(&[
+        (
+            "buildDefinition",
+            object(&[
+                ("buildType", json_string(BUILD_TYPE)),
+                ("externalParameters", object(&parameters)),
+                ("resolvedDependencies", array(&dependencies)),
+            ]),
+        ),
+        (
+            "runDetails",
+            object(&[("builder", builder), ("metadata", metadata)]),
+        ),
+    ]);
+    let mut statement = object(&[
+        ("_type", json_string("https://in-toto.io/Statement/v1")),
+        ("subject", array(&[subject])),
+        (
+            "predicateType",
+            json_string("https://slsa.dev/provenance/v1"),
+        ),
+        ("predicate", predicate),
+    ]);
+    statement.push('\n');
+    statement
+}
+
+/// Renders a JSON object from keys and rendered values.
+fn object(entries: &[(&str, String)]) -> String {
+    let entries: Vec<String> = entries
+        .iter()
+        .map(|(key, value)| format!("{}:{}", json_string(key), value))
+        .collect();
+    format!("{{{}}}", entries.join(","))
+}
+
+/// Renders a JSON array from rendered values.
+fn array(values: &[String]) -> String {
+    format!("[{}]", values.join(","))
+}
+
+fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,38 @@
+//! Patching raw binary images, e.g. the output of `objcopy -O binary`.
+//!
+//! A flat image has no headers and no section table, so the version section is found by
+//! its offset in the file: its load address minus the load address of the image.
+
+/// Overwrites the bytes at `offset` in a raw image.
+///
+/// If they extend past the end of the image, this is an error unless `fill` is set, in
+/// which case the image is first extended with that byte (e.g. `0xff`, for erased flash).
+pub fn patch(
+    image: &mut Vec<u8>,
+    offset: u64,
+    bytes: &[u8],
+    fill: Option<u8>,
+) -> Result<(), String> {
+    let end = offset + bytes.len() as u64;
+    if end > image.len() as u64 {
+        let Some(fill) = fill else {
+            return Err(format!(
+                "the section at {:#x}..{:#x} extends past the end of the image ({:#x} bytes)",
+                offset,
+                end,
+                image.len()
+            ));
+        };
+        let end = usize::try_from(end).map_err(|_| format!("offset {:#x} is too large", offset))?;
+        image.resize(end, fill);
+    }
+    let start = offset as usize;
+    image[start..start + bytes.len()].copy_from_slice(bytes);
+    Ok(())
+}
+
+/// Gets the existing bytes of the section, if they are within the image.
+pub fn existing(image: &[u8], offset: u64, len: usize) -> Option<&[u8]> {
+    let start = usize::try_from(offset).ok()?;
+    image.get(start..start.checked_add(len)?)
+}
@@ -0,0 +1,145 @@
+//! Pointing a binary at its SBOM, and embedding a compressed copy of it.
+//!
+//! The `SbomDigest` member is the format of the SBOM and its SHA-256, e.g.
+//! `cyclonedx-json sha256:3f2a...`, so that a binary can be matched with the SBOM which
+//! was generated for it. The `Sbom` member is the SBOM itself, compressed with raw
+//! DEFLATE and encoded as base64, since member data must be UTF-8. It is dropped if it
+//! doesn't fit in the section. Embedding it needs the `embed-sbom` feature.
+
+use std::fs;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::cargo_helpers::cargo_rerun_if;
+use crate::error::VerShimError;
+
+/// The compression level, from 0 to 10. The SBOM is compressed once per build, so use the
+/// best compression.
+#[cfg(feature = "embed-sbom")]
+const COMPRESSION_LEVEL: u8 = 10;
+
+/// The largest decompressed SBOM that `decompress()` accepts.
+#[cfg(feature = "embed-sbom")]
+const MAX_DECOMPRESSED_LEN: usize = 64 * 1024 * 1024;
+
+/// The standard base64 alphabet.
+#[cfg(feature = "embed-sbom")]
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// An SBOM file read for embedding.
+pub struct Sbom {
+    /// The data of the `SbomDigest` member.
+    pub digest: String,
+    /// The contents of the file.
+    #[cfg(feature = "embed-sbom")]
+    pub data: Vec<u8>,
+}
+
+/// Reads an SBOM file and computes its digest.
+///
+/// In a build script, this also asks cargo to rerun it when the file changes. Returns
+/// a `Config` error if the file is neither CycloneDX nor SPDX.
+pub fn read(path: &Path) -> Result<Sbom, VerShimError> {
+    cargo_rerun_if(&format!("changed={}", path.display()));
+    let data = fs::read(path)
+        .map_err(|e| VerShimError::io(format!("failed to read {}", path.display()), e))?;
+    let format = format(&data).ok_or_else(|| {
+        VerShimError::Config(format!(
+            "{} is not a CycloneDX or SPDX SBOM (JSON, XML, or tag-value)",
+            path.display()
+        ))
+    })?;
+    let hash: String = Sha256::digest(&data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok(Sbom {
+        digest: format!("{} sha256:{}", format, hash),
+        #[cfg(feature = "embed-sbom")]
+        data,
+    })
+}
+
+/// Tells the format of an SBOM from its contents, e.g. `cyclonedx-json`.
+fn format(data: &[u8]) -> Option<&'static str> {
+    let text = std::str::from_utf8(data).ok()?;
+    let trimmed = text.trim_start_matches('\u{feff}').trim_start();
+    if trimmed.starts_with('{') {
+        if text.contains("\"bomFormat\"") && text.contains("CycloneDX") {
+            return Some("cyclonedx-json");
+        }
+        if text.contains("\"spdxVersion\"") {
+            return Some("spdx-json");
+        }
+    } else if trimmed.starts_with('<') {
+        if text.contains("cyclonedx.org/schema/bom") {
+            return Some("cyclonedx-xml");
+        }
+    } else if text.lines().any(|line| line.starts_with("SPDXVersion:")) {
+        return Some("spdx-tag-value");
+    }
+    None
+}
+
+/// Compresses an SBOM into the data of the `Sbom` member.
+#[cfg(feature = "embed-sbom")]
+pub fn compress(data: &[u8]) -> String {
+    base64_encode(&miniz_oxide::deflate::compress_to_vec(
+        data,
+        COMPRESSION_LEVEL,
+    ))
+}
+
+/// Decompresses the data of the `Sbom` member, the inverse of `compress()`.
+#[cfg(feature = "embed-sbom")]
+pub fn decompress(member_data: &str) -> Result<Vec<u8>, String> {
+    let compressed = base64_decode(member_data)?;
+    miniz_oxide::inflate::decompress_to_vec_with_limit(&compressed, MAX_DECOMPRESSED_LEN)
+        .map_err(|e| format!("failed to decompress the embedded SBOM: {}", e))
+}
+
+/// Encodes bytes as base64, with padding.
+#[cfg(feature = "embed-sbom")]
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes base64, with or without padding.
+#[cfg(feature = "embed-sbom")]
+fn base64_decode(s: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(s.len() / 4 * 3);
+    let mut n: u32 = 0;
+    let mut bits = 0;
+    for c in s.trim_end_matches('=').bytes() {
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| format!("invalid base64 character {:?}", c as char))?;
+        // Only the bits which weren't output yet are kept
+        n = ((n << 6) | value as u32) & 0xffff;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
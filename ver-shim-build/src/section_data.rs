@@ -0,0 +1,835 @@
+//! Decoding section buffers, the inverse of `build_section_buffer()`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use object::{BinaryFormat, Object, ObjectSection};
+use ver_shim::{
+    Member, PREAMBLE_LEN, SECTION_NAME, SIZE_FOOTER_LEN, crc32, header_size, header_size_v1,
+};
+
+use crate::archive;
+use crate::error::VerShimError;
+use crate::format::SectionFormat;
+use crate::git_env;
+use crate::kv;
+use crate::locator;
+use crate::macho;
+use crate::native;
+#[cfg(feature = "embed-sbom")]
+use crate::sbom;
+use crate::wasm;
+
+/// Version data decoded from a `.ver_shim_data` section buffer.
+///
+/// This reads the same format as the `ver_shim` runtime crate, but from a byte slice,
+/// so that tools can inspect a section without linking the runtime crate's static.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SectionData {
+    /// Data for each member present in the header, indexed by member number.
+    ///
+    /// This may be longer than `Member::COUNT` if the section was written by a newer
+    /// version of `ver-shim-build`.
+    members: Vec<Option<String>>,
+    /// The buffer size recorded in the section, if any.
+    written_buffer_size: Option<usize>,
+    /// The format version of the section.
+    format_version: u8,
+}
+
+impl SectionData {
+    /// Decodes a section buffer.
+    ///
+    /// Both section format versions are supported. A buffer that was never patched
+    /// decodes to a `SectionData` with no members present. Returns an error if the header
+    /// is truncated, the format version is unknown, a member's range is invalid or out of
+    /// bounds, a member is not valid UTF-8, the section was written for a larger buffer, or
+    /// the checksum of a version 2 section doesn't match.
+    pub fn parse(buffer: &[u8]) -> Result<Self, String> {
+        if buffer.is_empty() {
+            return Err("section buffer is empty".to_string());
+        }
+        let format = SectionFormat::from_header(buffer)?;
+        let (num_members, offsets_pos) = match format {
+            Some(SectionFormat::V2) if buffer.len() < PREAMBLE_LEN => {
+                return Err(format!(
+                    "section buffer is {} bytes, too small for a header",
+                    buffer.len()
+                ));
+            }
+            Some(SectionFormat::V2) => (buffer[6] as usize, PREAMBLE_LEN),
+            _ => (buffer[0] as usize, 1),
+        };
+        let header_sz = match format {
+            Some(SectionFormat::V2) => header_size(num_members),
+            _ => header_size_v1(num_members),
+        };
+        if buffer.len() < header_sz {
+            return Err(format!(
+                "section buffer is {} bytes, too small for a header of {} members",
+                buffer.len(),
+                num_members
+            ));
+        }
+
+        let read_end = |idx: usize| {
+            let pos = offsets_pos + idx * 2;
+            header_sz + u16::from_le_bytes([buffer[pos], buffer[pos + 1]]) as usize
+        };
+
+        let mut members = Vec::with_capacity(num_members);
+        let mut start = header_sz;
+        for idx in 0..num_members {
+            let end = read_end(idx);
+            if end < start {
+                return Err(format!(
+                    "invalid range for member {}: start={}, end={}",
+                    idx, start, end
+                ));
+            }
+            if end > buffer.len() {
+                return Err(format!(
+                    "end offset {} exceeds buffer size {} for member {}",
+                    end,
+                    buffer.len(),
+                    idx
+                ));
+            }
+            let data = if start == end {
+                None
+            } else {
+                let s = std::str::from_utf8(&buffer[start..end])
+                    .map_err(|e| format!("invalid UTF-8 for member {}: {}", idx, e))?;
+                Some(s.to_string())
+            };
+            members.push(data);
+            start = end;
+        }
+
+        let written_buffer_size = match format {
+            Some(SectionFormat::V2) if num_members > 0 => {
+                Some(u16::from_le_bytes([buffer[7], buffer[8]]) as usize)
+            }
+            // In version 1 the size is in a footer after the data. An older ver-shim-build
+            // didn't write the footer, so it is zero, or missing if the data filled the buffer.
+            _ => match buffer.get(start..start + SIZE_FOOTER_LEN) {
+                Some(footer) if num_members > 0 => {
+                    match u16::from_le_bytes([footer[0], footer[1]]) as usize {
+                        0 => None,
+                        size => Some(size),
+                    }
+                }
+                _ => None,
+            },
+        };
+        if let Some(size) = written_buffer_size
+            && size > buffer.len()
+        {
+            return Err(format!(
+                "section was written for a {} byte buffer, but the buffer is {} bytes",
+                size,
+                buffer.len()
+            ));
+        }
+
+        if format.is_some() && num_members > 0 {
+            let expected = u32::from_le_bytes([buffer[9], buffer[10], buffer[11], buffer[12]]);
+            let actual = crc32(&buffer[offsets_pos..start]);
+            if actual != expected {
+                return Err(format!(
+                    "section checksum mismatch (expected {:08x}, got {:08x}), the section was \
+                     probably overwritten after it was patched",
+                    expected, actual
+                ));
+            }
+        }
+
+        Ok(Self {
+            members,
+            written_buffer_size,
+            format_version: if format.is_some() { 2 } else { 1 },
+        })
+    }
+
+    /// Reads and decodes the version section of a binary.
+    ///
+    /// This supports the same files as patching: executables and shared libraries (found
+    /// by section name, or by magic prefix for the `locator` feature of `ver-shim`), and
+    /// static library archives.
+    pub fn from_binary(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let data =
+            fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let buffer = find_buffer(&data, None)
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .ok_or_else(|| format!("section '{}' not found in {}", SECTION_NAME, path.display()))?;
+        Self::parse(&buffer).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Reads and decodes all version sections of a binary, with their names, in file order.
+    ///
+    /// This finds `.ver_shim_data` and the sections declared with
+    /// `ver_shim::version_section!`, by their header. The runtime writes the header before
+    /// the binary is patched, so unpatched sections are listed too, and are empty. Sections
+    /// of a `ver_shim` older than format version 2 have no header until patched, so of
+    /// those only `.ver_shim_data` is found. For Mach-O binaries, names have the form
+    /// `__DATA,__my_section`, as in the macro.
+    ///
+    /// Files which aren't executables or shared libraries, like static library archives,
+    /// only have their `.ver_shim_data` section read, see `from_binary()`.
+    pub fn all_from_binary(path: impl AsRef<Path>) -> Result<Vec<(String, Self)>, String> {
+        let path = path.as_ref();
+        let data =
+            fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let mut sections = Vec::new();
+        if let Ok(file) = object::File::parse(&*data) {
+            let default_name = native::section_name(file.format(), None);
+            for section in file.sections() {
+                let (Ok(name), Ok(buffer)) = (section.name(), section.data()) else {
+                    continue;
+                };
+                if name != default_name
+                    && !matches!(SectionFormat::from_header(buffer), Ok(Some(_)))
+                {
+                    continue;
+                }
+                let name = match section.segment_name() {
+                    Ok(Some(segment)) if file.format() == BinaryFormat::MachO => {
+                        format!("{},{}", segment, name)
+                    }
+                    _ => name.to_string(),
+                };
+                let section_data = Self::parse(buffer)
+                    .map_err(|e| format!("{}: section '{}': {}", path.display(), name, e))?;
+                sections.push((name, section_data));
+            }
+        }
+        // Not an object file, or the buffer is found by the locator prefix
+        if sections.is_empty() {
+            sections.push((SECTION_NAME.to_string(), Self::from_binary(path)?));
+        }
+        Ok(sections)
+    }
+
+    /// Returns the members whose data differs from `other`, in header order.
+    ///
+    /// An empty result means both sections hold the same version data.
+    pub fn diff(&self, other: &SectionData) -> Vec<MemberDiff> {
+        Member::ALL
+            .into_iter()
+            .filter(|&member| self.get(member) != other.get(member))
+            .map(|member| MemberDiff {
+                member,
+                old: self.get(member).map(str::to_string),
+                new: other.get(member).map(str::to_string),
+            })
+            .collect()
+    }
+
+    /// Gets the data of a member, if present.
+    pub fn get(&self, member: Member) -> Option<&str> {
+        self.members.get(member as usize)?.as_deref()
+    }
+
+    /// Iterates over the members that are present, in header order.
+    ///
+    /// Members unknown to this version of `ver-shim-build` are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = (Member, &str)> {
+        Member::ALL
+            .into_iter()
+            .filter_map(|member| Some((member, self.get(member)?)))
+    }
+
+    /// Gets the key-value pairs of a member which holds a map (see `Member::is_map()`).
+    ///
+    /// This is empty if the member is not present.
+    pub fn get_map(&self, member: Member) -> BTreeMap<String, String> {
+        kv::decode(self.get(member).unwrap_or(""))
+    }
+
+    /// Gets the entries registered by library crates, keyed by `crate_name:key`.
+    ///
+    /// This is empty if the `CrateEntries` member is not present.
+    pub fn crate_entries(&self) -> BTreeMap<String, String> {
+        self.get_map(Member::CrateEntries)
+    }
+
+    /// Gets the SBOM embedded with `LinkSection::embed_sbom()`, decompressed.
+    ///
+    /// Returns `Ok(None)` if no SBOM is embedded, and an error if it is corrupt. Needs the
+    /// `embed-sbom` feature.
+    #[cfg(feature = "embed-sbom")]
+    pub fn sbom(&self) -> Result<Option<Vec<u8>>, String> {
+        self.get(Member::Sbom).map(sbom::decompress).transpose()
+    }
+
+    /// Renders the data as a JSON object, with one entry per member known to this
+    /// version of `ver-shim-build` (`null` if absent), e.g. `{"git_sha": "...", ...}`.
+    ///
+    /// Members which hold a map, like crate entries (keyed by `crate_name:key`) and custom
+    /// key-value pairs, are rendered as nested objects.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{");
+        for (idx, member) in Member::ALL.into_iter().enumerate() {
+            if idx > 0 {
+                out.push(',');
+            }
+            write!(out, "\n  {}: ", json_string(member.name())).unwrap();
+            match self.get(member) {
+                None => out.push_str("null"),
+                Some(_) if member.is_map() => {
+                    out.push('{');
+                    for (idx, (key, value)) in self.get_map(member).iter().enumerate() {
+                        if idx > 0 {
+                            out.push(',');
+                        }
+                        write!(out, "\n    {}: {}", json_string(key), json_string(value)).unwrap();
+                    }
+                    out.push_str("\n  }");
+                }
+                Some(value) => out.push_str(&json_string(value)),
+            }
+        }
+        out.push_str("\n}\n");
+        out
+    }
+
+    /// Renders the present members as a TOML document, e.g. `git_sha = "..."`.
+    ///
+    /// Members which hold a map are rendered as tables, after the other members. TOML
+    /// has no null, so absent members are left out.
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        for (member, value) in self.iter() {
+            if !member.is_map() {
+                writeln!(out, "{} = {}", member.name(), json_string(value)).unwrap();
+            }
+        }
+        for (member, _) in self.iter() {
+            if member.is_map() {
+                write!(out, "\n[{}]\n", member.name()).unwrap();
+                for (key, value) in self.get_map(member) {
+                    writeln!(out, "{} = {}", json_string(&key), json_string(&value)).unwrap();
+                }
+            }
+        }
+        out
+    }
+
+    /// Renders the data as a YAML mapping, with the same entries as `to_json()`.
+    pub fn to_yaml(&self) -> String {
+        let mut out = String::new();
+        for member in Member::ALL {
+            write!(out, "{}:", member.name()).unwrap();
+            match self.get(member) {
+                None => out.push_str(" null\n"),
+                Some(_) if member.is_map() => {
+                    let map = self.get_map(member);
+                    if map.is_empty() {
+                        out.push_str(" {}");
+                    }
+                    out.push('\n');
+                    for (key, value) in map {
+                        writeln!(out, "  {}: {}", json_string(&key), json_string(&value)).unwrap();
+                    }
+                }
+                Some(value) => writeln!(out, " {}", json_string(value)).unwrap(),
+            }
+        }
+        out
+    }
+
+    /// Renders the present members as `KEY='value'` lines, for `eval` in a shell.
+    ///
+    /// The variables are named like those of `LinkSection::also_emit_rustc_env()`, e.g.
+    /// `VER_SHIM_GIT_SHA`. Each entry of a member which holds a map gets a variable of its
+    /// own, with the key appended in upper case, e.g. `VER_SHIM_CUSTOM_KV_DEPLOY_ENV` for
+    /// the custom key `deploy_env`. Other characters of the key are escaped, e.g.
+    /// `VER_SHIM_CUSTOM_KV_DEPLOYx2DENV` for `deploy-env`, so that every key gets a
+    /// variable of its own.
+    pub fn to_env(&self) -> String {
+        let mut out = String::new();
+        for (member, value) in self.iter() {
+            if member.is_map() {
+                for (key, value) in self.get_map(member) {
+                    let name = git_env::entry_name(member, &key);
+                    writeln!(out, "VER_SHIM_{}={}", name, shell_quote(&value)).unwrap();
+                }
+            } else {
+                let name = git_env::var_name(member);
+                writeln!(out, "{}={}", name, shell_quote(value)).unwrap();
+            }
+        }
+        out
+    }
+
+    /// Returns the number of members in the section header.
+    ///
+    /// This is zero for a section that was never patched.
+    pub fn num_members(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns the format version of the section, 1 or 2.
+    ///
+    /// A binary built with an older `ver-shim` has a version 1 section, which
+    /// `LinkSection::patch_into()` keeps when patching it.
+    pub fn format_version(&self) -> u8 {
+        self.format_version
+    }
+
+    /// Returns the size of the buffer that the section was written for, if known.
+    ///
+    /// This is `None` if the section was never patched, or was written by an older
+    /// `ver-shim-build`. See `ver_shim::written_buffer_size()`.
+    pub fn written_buffer_size(&self) -> Option<usize> {
+        self.written_buffer_size
+    }
+
+    /// Returns true if no members are present.
+    pub fn is_empty(&self) -> bool {
+        self.members.iter().all(Option::is_none)
+    }
+}
+
+/// Quotes a string for a POSIX shell.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Quotes and escapes a string for JSON.
+///
+/// The result is also a valid TOML and YAML string: all control characters are
+/// escaped, as well as the characters which YAML reads as line breaks or doesn't allow.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control()
+                || matches!(c, '\u{2028}' | '\u{2029}' | '\u{fffe}' | '\u{ffff}') =>
+            {
+                write!(out, "\\u{:04x}", c as u32).unwrap()
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A member whose data differs between two sections.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemberDiff {
+    /// The member which differs.
+    pub member: Member,
+    /// The data in the first section, if present.
+    pub old: Option<String>,
+    /// The data in the second section, if present.
+    pub new: Option<String>,
+}
+
+impl MemberDiff {
+    /// Renders the difference as a JSON object, with the member named as in
+    /// `SectionData::to_json()`, e.g. `{"member": "git_sha", "old": "...", "new": null}`.
+    pub fn to_json(&self) -> String {
+        let value = |data: &Option<String>| data.as_deref().map_or("null".into(), json_string);
+        format!(
+            "{{\"member\": {}, \"old\": {}, \"new\": {}}}",
+            json_string(self.member.name()),
+            value(&self.old),
+            value(&self.new)
+        )
+    }
+}
+
+/// Compares the version sections of two binaries, returning the members that differ.
+///
+/// An empty result means both binaries carry the same version data, e.g. so that a
+/// deploy tool can tell that an upgrade is a no-op. See `SectionData::from_binary()`
+/// for the supported files.
+///
+/// Panics if either binary can't be read or has no version section.
+pub fn compare_binaries(a: impl AsRef<Path>, b: impl AsRef<Path>) -> Vec<MemberDiff> {
+    let read = |path: &Path| {
+        SectionData::from_binary(path).unwrap_or_else(|e| panic!("ver-shim-build: {}", e))
+    };
+    read(a.as_ref()).diff(&read(b.as_ref()))
+}
+
+/// Reads the version data of a binary, without running it.
+///
+/// This is the library API behind `ver-shim read`, for deployment tooling which inspects
+/// artifacts: ELF, PE, and Mach-O executables and shared libraries (including universal
+/// binaries, of which the first architecture is read), WebAssembly modules, and static
+/// library archives. Read members with `SectionData::get()` or `SectionData::iter()`.
+///
+/// Like `SectionData::from_binary()`, but returns a `VerShimError`: `VerShimError::Io`
+/// if the file can't be read, and `VerShimError::InvalidBinary` if it has no version
+/// section, or the section can't be decoded.
+pub fn read_from_file(path: impl AsRef<Path>) -> Result<SectionData, VerShimError> {
+    let path = path.as_ref();
+    let data = fs::read(path)
+        .map_err(|e| VerShimError::io(format!("failed to read {}", path.display()), e))?;
+    let buffer = find_buffer(&data, None)
+        .map_err(|e| VerShimError::InvalidBinary(format!("{}: {}", path.display(), e)))?
+        .ok_or_else(|| {
+            VerShimError::InvalidBinary(format!(
+                "section '{}' not found in {}",
+                SECTION_NAME,
+                path.display()
+            ))
+        })?;
+    SectionData::parse(&buffer)
+        .map_err(|e| VerShimError::InvalidBinary(format!("{}: {}", path.display(), e)))
+}
+
+/// Finds the version buffer in the contents of a binary or archive.
+///
+/// `custom_name` is the name set with `LinkSection::with_section_name()`, if any. The
+/// wasm custom section and the locator prefix only mark the default buffer, so they are
+/// not used for a custom name.
+///
+/// Returns `Ok(None)` if there is neither a version section nor a locator magic prefix.
+pub(crate) fn find_buffer(
+    data: &[u8],
+    custom_name: Option<&str>,
+) -> Result<Option<Vec<u8>>, String> {
+    if archive::is_archive(data) {
+        let ranges = archive::find_sections(data, custom_name)?;
+        return Ok(ranges.first().map(|range| data[range.clone()].to_vec()));
+    }
+
+    // Every architecture of a universal binary is patched with the same data
+    if macho::is_universal(data) {
+        let ranges = macho::find_sections(data, custom_name)?;
+        return Ok(ranges.first().map(|range| data[range.clone()].to_vec()));
+    }
+
+    // The custom section holds a copy of the patched buffer, otherwise fall back to the
+    // locator prefix below
+    if custom_name.is_none()
+        && wasm::is_wasm(data)
+        && let Some(buffer) = wasm::custom_section(data, wasm::WASM_SECTION_NAME)?
+    {
+        return Ok(Some(buffer.to_vec()));
+    }
+
+    if let Ok(file) = object::File::parse(data)
+        && let Some(section) = native::version_section(&file, custom_name)
+    {
+        let buffer = section.data().map_err(|e| {
+            format!(
+                "failed to read section '{}': {}",
+                section.name().unwrap_or(SECTION_NAME),
+                e
+            )
+        })?;
+        // A section with no file data (e.g. if the linker made it NOBITS) is all zeros
+        if buffer.is_empty() {
+            return Ok(Some(vec![0u8; section.size() as usize]));
+        }
+        return Ok(Some(buffer.to_vec()));
+    }
+
+    if custom_name.is_some() {
+        return Ok(None);
+    }
+    Ok(locator::find(data)?
+        .map(|location| data[location.offset..location.offset + location.size].to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_section_buffer;
+    use crate::fixtures::{self, SECTION_SIZE};
+
+    fn member_data(members: &[(Member, &str)]) -> [Option<String>; Member::COUNT] {
+        let mut data: [Option<String>; Member::COUNT] = Default::default();
+        for (member, value) in members {
+            data[*member as usize] = Some(value.to_string());
+        }
+        data
+    }
+
+    fn buffer(members: &[(Member, &str)], format: SectionFormat) -> Vec<u8> {
+        build_section_buffer(&member_data(members), SECTION_SIZE, format).unwrap()
+    }
+
+    const MEMBERS: &[(Member, &str)] = &[
+        (Member::GitSha, "0123456789abcdef0123456789abcdef01234567"),
+        (Member::GitBranch, "main"),
+        (Member::Custom, "héllo"),
+    ];
+
+    #[test]
+    fn round_trip() {
+        for format in [SectionFormat::V1, SectionFormat::V2] {
+            let section = SectionData::parse(&buffer(MEMBERS, format)).unwrap();
+            assert_eq!(section.iter().collect::<Vec<_>>(), MEMBERS.to_vec());
+            assert_eq!(section.get(Member::GitTag), None);
+            assert_eq!(section.num_members(), Member::COUNT);
+            assert_eq!(section.written_buffer_size(), Some(SECTION_SIZE));
+        }
+        assert_eq!(
+            SectionData::parse(&buffer(MEMBERS, SectionFormat::V1))
+                .unwrap()
+                .format_version(),
+            1
+        );
+        assert_eq!(
+            SectionData::parse(&buffer(MEMBERS, SectionFormat::V2))
+                .unwrap()
+                .format_version(),
+            2
+        );
+    }
+
+    #[test]
+    fn member_names() {
+        use heck::ToSnakeCase;
+
+        for member in Member::ALL {
+            // The names were derived from the variant names before they were spelled out
+            assert_eq!(member.name(), format!("{:?}", member).to_snake_case());
+            assert_eq!(Member::from_name(member.name()), Some(member));
+        }
+        assert_eq!(Member::from_name("GIT_SHA"), None);
+    }
+
+    #[test]
+    fn unpatched_buffer_is_empty() {
+        let section = SectionData::parse(&fixtures::unpatched_section()).unwrap();
+        assert!(section.is_empty());
+        assert_eq!(section.format_version(), 2);
+        assert_eq!(section.written_buffer_size(), None);
+        // A version 1 runtime leaves the buffer zeroed
+        assert!(SectionData::parse(&[0; SECTION_SIZE]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn newer_members_are_kept() {
+        // A section written by a newer ver-shim-build, with one more member
+        let mut data = buffer(MEMBERS, SectionFormat::V2);
+        let offsets_end = header_size(Member::COUNT);
+        let last_end = [data[offsets_end - 2], data[offsets_end - 1]];
+        data.insert(offsets_end, last_end[1]);
+        data.insert(offsets_end, last_end[0]);
+        data.truncate(SECTION_SIZE);
+        data[6] += 1;
+        let data_end = header_size(Member::COUNT + 1) + u16::from_le_bytes(last_end) as usize;
+        let crc = crc32(&data[PREAMBLE_LEN..data_end]);
+        data[9..13].copy_from_slice(&crc.to_le_bytes());
+
+        let section = SectionData::parse(&data).unwrap();
+        assert_eq!(section.num_members(), Member::COUNT + 1);
+        assert_eq!(section.iter().collect::<Vec<_>>(), MEMBERS.to_vec());
+    }
+
+    #[test]
+    fn invalid_buffers() {
+        let err = |data: &[u8]| SectionData::parse(data).unwrap_err();
+        assert!(err(&[]).contains("empty"));
+
+        let data = buffer(MEMBERS, SectionFormat::V2);
+        assert!(err(&data[..PREAMBLE_LEN - 1]).contains("too small for a header"));
+        assert!(err(&data[..header_size(Member::COUNT) - 1]).contains("too small for a header"));
+        // Truncated after the data, so the buffer size in the header is too large
+        assert!(err(&data[..SECTION_SIZE - 1]).contains("written for a 512 byte buffer"));
+
+        let mut clobbered = data.clone();
+        clobbered[header_size(Member::COUNT)] ^= 1;
+        assert!(err(&clobbered).contains("checksum mismatch"));
+
+        let mut unknown_version = data.clone();
+        unknown_version[5] = 0xff;
+        SectionData::parse(&unknown_version).unwrap_err();
+
+        let mut not_utf8 = buffer(&[(Member::Custom, "a")], SectionFormat::V1);
+        not_utf8[header_size_v1(Member::COUNT)] = 0xff;
+        assert!(err(&not_utf8).contains("invalid UTF-8"));
+
+        let mut out_of_bounds = buffer(&[], SectionFormat::V1);
+        let last = 1 + (Member::COUNT - 1) * 2;
+        out_of_bounds[last..last + 2].copy_from_slice(&(SECTION_SIZE as u16).to_le_bytes());
+        assert!(err(&out_of_bounds).contains("exceeds buffer size"));
+
+        let mut backwards = buffer(&[(Member::Custom, "abc")], SectionFormat::V1);
+        let last = 1 + (Member::COUNT - 1) * 2;
+        backwards[last..last + 2].copy_from_slice(&0u16.to_le_bytes());
+        assert!(err(&backwards).contains("invalid range"));
+    }
+
+    #[test]
+    fn clobbered_section_reads_as_empty_at_runtime() {
+        let data = buffer(MEMBERS, SectionFormat::V2);
+        let section = ver_shim::VersionSection::new(Vec::leak(data.clone()));
+        assert_eq!(section.get(Member::Custom), Some("héllo"));
+        assert_eq!(section.written_buffer_size(), Some(SECTION_SIZE));
+
+        // e.g. another post-processing tool wrote to the section after it was patched
+        let mut clobbered = data;
+        clobbered[header_size(Member::COUNT) + 3] = b'X';
+        let section = ver_shim::VersionSection::new(Vec::leak(clobbered));
+        assert_eq!(section.iter().count(), 0);
+        assert_eq!(section.get(Member::GitSha), None);
+        assert_eq!(section.written_buffer_size(), None);
+    }
+
+    /// A section with values which need escaping in the output formats.
+    fn section_to_render() -> SectionData {
+        let custom_kv = kv::encode(&BTreeMap::from([
+            ("a-b".to_string(), "it's \"quoted\"".to_string()),
+            ("a_b".to_string(), "back\\slash # not a comment".to_string()),
+            ("Key: {}".to_string(), String::new()),
+        ]));
+        let members = [
+            (Member::GitSha, "0123456789abcdef0123456789abcdef01234567"),
+            (Member::GitBranch, " padded "),
+            (
+                Member::GitCommitMsg,
+                "line 1\nline 2\r\n\ttab \u{1} \u{7f} \u{85} \u{2028} \u{feff}",
+            ),
+            (Member::Custom, "- [not, a, list] & *alias !tag | > % @ `"),
+            (Member::CustomKv, &custom_kv),
+            // Not a valid map, so it reads as an empty one
+            (Member::CrateEntries, "junk"),
+        ];
+        let section = SectionData::parse(&buffer(&members, SectionFormat::V2)).unwrap();
+        assert!(section.get_map(Member::CrateEntries).is_empty());
+        section
+    }
+
+    #[test]
+    fn toml_output() {
+        let section = section_to_render();
+        let table: toml::Table = section.to_toml().parse().unwrap();
+        assert_eq!(table.len(), section.iter().count());
+        for (member, value) in section.iter() {
+            let parsed = &table[member.name()];
+            if member.is_map() {
+                let map: BTreeMap<String, String> = parsed
+                    .as_table()
+                    .unwrap()
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.as_str().unwrap().to_string()))
+                    .collect();
+                assert_eq!(map, section.get_map(member));
+            } else {
+                assert_eq!(parsed.as_str(), Some(value));
+            }
+        }
+    }
+
+    #[test]
+    fn yaml_output() {
+        let section = section_to_render();
+        let docs = yaml_rust2::YamlLoader::load_from_str(&section.to_yaml()).unwrap();
+        assert_eq!(docs[0].as_hash().unwrap().len(), Member::COUNT);
+        for member in Member::ALL {
+            let parsed = &docs[0][member.name()];
+            match section.get(member) {
+                None => assert!(parsed.is_null(), "{}", member.name()),
+                Some(_) if member.is_map() => {
+                    let map: BTreeMap<String, String> = parsed
+                        .as_hash()
+                        .unwrap()
+                        .iter()
+                        .map(|(key, value)| {
+                            let key = key.as_str().unwrap().to_string();
+                            (key, value.as_str().unwrap().to_string())
+                        })
+                        .collect();
+                    assert_eq!(map, section.get_map(member));
+                }
+                Some(value) => assert_eq!(parsed.as_str(), Some(value)),
+            }
+        }
+    }
+
+    #[test]
+    fn diff() {
+        let old = SectionData::parse(&buffer(MEMBERS, SectionFormat::V2)).unwrap();
+        let new = SectionData::parse(&buffer(
+            &[
+                (Member::GitSha, "0123456789abcdef0123456789abcdef01234567"),
+                (Member::GitBranch, "release"),
+                (Member::GitTag, "v1.0.0"),
+            ],
+            SectionFormat::V1,
+        ))
+        .unwrap();
+        assert_eq!(old.diff(&old), vec![]);
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                MemberDiff {
+                    member: Member::GitBranch,
+                    old: Some("main".into()),
+                    new: Some("release".into()),
+                },
+                MemberDiff {
+                    member: Member::Custom,
+                    old: Some("héllo".into()),
+                    new: None,
+                },
+                MemberDiff {
+                    member: Member::GitTag,
+                    old: None,
+                    new: Some("v1.0.0".into()),
+                },
+            ]
+        );
+        assert_eq!(
+            old.diff(&new)[2].to_json(),
+            r#"{"member": "git_tag", "old": null, "new": "v1.0.0"}"#
+        );
+    }
+
+    #[test]
+    fn compare() {
+        let dir = fixtures::temp_dir("compare_binaries");
+        let write = |name: &str, members: &[(Member, &str)]| {
+            let section = buffer(members, SectionFormat::V2);
+            let path = dir.join(name);
+            fs::write(&path, fixtures::elf_object(&[(SECTION_NAME, &section)])).unwrap();
+            path
+        };
+        let a = write("a", MEMBERS);
+        let b = write("b", MEMBERS);
+        let c = write("c", &MEMBERS[..2]);
+
+        assert_eq!(compare_binaries(&a, &b), vec![]);
+        assert_eq!(
+            compare_binaries(&a, &c),
+            vec![MemberDiff {
+                member: Member::Custom,
+                old: Some("héllo".into()),
+                new: None,
+            }]
+        );
+        assert_eq!(
+            read_from_file(&a).unwrap(),
+            SectionData::from_binary(&b).unwrap()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "not found")]
+    fn compare_without_section() {
+        let dir = fixtures::temp_dir("compare_without_section");
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        fs::write(&a, fixtures::elf_object(&[])).unwrap();
+        fs::write(&b, fixtures::elf_object(&[])).unwrap();
+        compare_binaries(&a, &b);
+    }
+}
@@ -0,0 +1,70 @@
+//! Detection of shallow git clones, in which `git describe` can't see the tags.
+
+use std::path::Path;
+
+use crate::diagnostics::Diagnostics;
+use crate::error::VerShimError;
+use crate::git_provider::run_command;
+
+/// How to fix a shallow clone, for the warning or error.
+const REMEDY: &str = "fetch the full history, e.g. with `fetch-depth: 0` for \
+                      actions/checkout, `GIT_DEPTH: 0` in GitLab CI, or `git fetch \
+                      --unshallow --tags`, or let ver-shim fetch it with \
+                      on_shallow_clone(OnShallow::Fetch)";
+
+/// What to do if the git repository is a shallow clone.
+///
+/// CI systems often clone only the last commit, without tags. `git describe` then gives
+/// a bare SHA, and the git tag member is missing. See `LinkSection::on_shallow_clone()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnShallow {
+    /// Report a warning, and collect what the shallow clone has.
+    #[default]
+    Warn,
+    /// Run `git fetch --unshallow --tags` before collecting. This needs network access
+    /// and the credentials of the clone.
+    Fetch,
+    /// Fail the build.
+    Fail,
+}
+
+/// Checks whether the repository of `git_dir` is a shallow clone, and handles it as
+/// `on_shallow` says.
+///
+/// A shallow clone has a `shallow` file in the git directory, listing the commits whose
+/// parents are missing.
+pub(crate) fn check(
+    git_dir: &Path,
+    on_shallow: OnShallow,
+    diag: &Diagnostics,
+    fail_on_error: bool,
+) -> Result<(), VerShimError> {
+    if !git_dir.join("shallow").exists() {
+        return Ok(());
+    }
+    match on_shallow {
+        OnShallow::Warn => {
+            diag.warning(format!(
+                "the git repository is a shallow clone, so git describe may give only a SHA: {}",
+                REMEDY
+            ));
+            Ok(())
+        }
+        OnShallow::Fail => Err(VerShimError::Git(format!(
+            "the git repository is a shallow clone: {}",
+            REMEDY
+        ))),
+        OnShallow::Fetch => {
+            diag.info(
+                "the git repository is a shallow clone, running git fetch --unshallow --tags",
+            );
+            match run_command("git", &["fetch", "--unshallow", "--tags"], &[]) {
+                Ok(_) => Ok(()),
+                Err(e) => diag.error_or_warning(
+                    fail_on_error,
+                    VerShimError::Git(format!("failed to unshallow the git repository: {}", e)),
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Signing the version data with an ed25519 key, see `LinkSection::with_signing_key()`.
+//!
+//! The signed message is described in `ver_shim::verify_signature()`, which checks it.
+
+use std::fs;
+use std::path::PathBuf;
+
+use ed25519_dalek::{Signer, SigningKey};
+use sha2::{Digest, Sha256};
+use ver_shim::{Member, SIGNATURE_CONTEXT};
+
+use crate::cargo_helpers::cargo_rerun_if;
+use crate::diagnostics::Diagnostics;
+use crate::error::VerShimError;
+
+/// Where the signing key comes from.
+pub(crate) enum KeySource {
+    /// A file with the key.
+    Path(PathBuf),
+    /// An environment variable with the key in hex, e.g. a CI secret.
+    Env(String),
+}
+
+/// Loads the signing key.
+///
+/// A key file holds the 32 byte ed25519 secret key, either as is or in hex. Returns `None`
+/// if the environment variable is not set, with a warning, or an error with
+/// `fail_on_error`, so that e.g. pull requests from forks, which don't get the secret,
+/// still build.
+pub(crate) fn load_key(
+    source: &KeySource,
+    diag: &Diagnostics,
+    fail_on_error: bool,
+) -> Result<Option<SigningKey>, VerShimError> {
+    let (bytes, origin) = match source {
+        KeySource::Path(path) => {
+            cargo_rerun_if(&format!("changed={}", path.display()));
+            let bytes = fs::read(path).map_err(|e| {
+                VerShimError::io(format!("failed to read signing key {}", path.display()), e)
+            })?;
+            (bytes, path.display().to_string())
+        }
+        KeySource::Env(var) => {
+            cargo_rerun_if(&format!("env-changed={}", var));
+            let Ok(value) = std::env::var(var) else {
+                diag.error_or_warning(
+                    fail_on_error,
+                    VerShimError::Env(format!("{} is not set, not signing the version data", var)),
+                )?;
+                return Ok(None);
+            };
+            (value.into_bytes(), var.clone())
+        }
+    };
+    let secret = match <[u8; 32]>::try_from(bytes.as_slice()) {
+        Ok(secret) => Some(secret),
+        Err(_) => std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|hex| decode_hex(hex.trim())),
+    };
+    let secret = secret.ok_or_else(|| {
+        VerShimError::Config(format!(
+            "the signing key in {} is not a 32 byte ed25519 key, as is or in hex",
+            origin
+        ))
+    })?;
+    let key = SigningKey::from_bytes(&secret);
+    diag.info(format!(
+        "signing the version data, public key = {}",
+        encode_hex(key.verifying_key().as_bytes())
+    ));
+    Ok(Some(key))
+}
+
+/// Signs the member data, replacing the signature member.
+pub(crate) fn sign(key: &SigningKey, member_data: &mut [Option<String>]) {
+    let mut hasher = Sha256::new();
+    hasher.update(SIGNATURE_CONTEXT);
+    for (idx, data) in member_data.iter().enumerate() {
+        if idx == Member::Signature as usize {
+            continue;
+        }
+        // An empty member is written as absent
+        if let Some(data) = data.as_deref().filter(|data| !data.is_empty()) {
+            hasher.update([idx as u8]);
+            hasher.update((data.len() as u16).to_le_bytes());
+            hasher.update(data);
+        }
+    }
+    let signature = key.sign(&hasher.finalize());
+    member_data[Member::Signature as usize] = Some(encode_hex(&signature.to_bytes()));
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_section_buffer;
+    use crate::fixtures::{self, SECTION_SIZE};
+    use crate::format::SectionFormat;
+    use crate::{LinkSection, Member};
+    use ver_shim::{SignatureError, VersionSection};
+
+    const SECRET: [u8; 32] = [7; 32];
+
+    fn public_key() -> [u8; 32] {
+        SigningKey::from_bytes(&SECRET).verifying_key().to_bytes()
+    }
+
+    fn member_data() -> [Option<String>; Member::COUNT] {
+        let mut data: [Option<String>; Member::COUNT] = Default::default();
+        data[Member::GitSha as usize] = Some("0123456789abcdef0123456789abcdef01234567".into());
+        data[Member::Custom as usize] = Some("signed".into());
+        data
+    }
+
+    fn verify(
+        data: &[Option<String>; Member::COUNT],
+        key: &[u8; 32],
+    ) -> Result<(), SignatureError> {
+        let buffer = build_section_buffer(data, SECTION_SIZE, SectionFormat::V2).unwrap();
+        VersionSection::new(Vec::leak(buffer)).verify_signature(key)
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let mut data = member_data();
+        sign(&SigningKey::from_bytes(&SECRET), &mut data);
+        assert_eq!(
+            data[Member::Signature as usize].as_ref().unwrap().len(),
+            128
+        );
+        assert_eq!(verify(&data, &public_key()), Ok(()));
+
+        let other_key = SigningKey::from_bytes(&[8; 32]).verifying_key().to_bytes();
+        assert_eq!(verify(&data, &other_key), Err(SignatureError::Invalid));
+
+        // Changing, adding, or removing a member after signing invalidates the signature
+        let mut changed = data.clone();
+        changed[Member::Custom as usize] = Some("changed".into());
+        assert_eq!(
+            verify(&changed, &public_key()),
+            Err(SignatureError::Invalid)
+        );
+        let mut added = data.clone();
+        added[Member::GitBranch as usize] = Some("main".into());
+        assert_eq!(verify(&added, &public_key()), Err(SignatureError::Invalid));
+        let mut removed = data.clone();
+        removed[Member::GitSha as usize] = None;
+        assert_eq!(
+            verify(&removed, &public_key()),
+            Err(SignatureError::Invalid)
+        );
+
+        // An empty member is written as absent, so it is signed as absent too
+        let mut empty = member_data();
+        empty[Member::GitTag as usize] = Some(String::new());
+        sign(&SigningKey::from_bytes(&SECRET), &mut empty);
+        assert_eq!(verify(&empty, &public_key()), Ok(()));
+    }
+
+    #[test]
+    fn missing_or_malformed() {
+        assert_eq!(
+            verify(&member_data(), &public_key()),
+            Err(SignatureError::Missing)
+        );
+        let mut data = member_data();
+        data[Member::Signature as usize] = Some("zz".repeat(64));
+        assert_eq!(verify(&data, &public_key()), Err(SignatureError::Malformed));
+        data[Member::Signature as usize] = Some("00".repeat(63));
+        assert_eq!(verify(&data, &public_key()), Err(SignatureError::Malformed));
+    }
+
+    #[test]
+    fn key_files() {
+        let dir = fixtures::temp_dir("signing_keys");
+        let diag = Diagnostics::default();
+        let load = |contents: &[u8]| {
+            let path = dir.join("key");
+            fs::write(&path, contents).unwrap();
+            load_key(&KeySource::Path(path), &diag, false).map(|key| key.map(|key| key.to_bytes()))
+        };
+        assert_eq!(load(&SECRET).unwrap(), Some(SECRET));
+        assert_eq!(
+            load(format!("{}\n", encode_hex(&SECRET)).as_bytes()).unwrap(),
+            Some(SECRET)
+        );
+        assert!(matches!(load(b"not a key"), Err(VerShimError::Config(_))));
+        assert!(matches!(load(&[7; 31]), Err(VerShimError::Config(_))));
+        assert!(load_key(&KeySource::Path(dir.join("missing")), &diag, false).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unset_key_variable() {
+        let source = KeySource::Env("VER_SHIM_TEST_UNSET_SIGNING_KEY".into());
+        let diag = Diagnostics::default();
+        assert!(load_key(&source, &diag, false).unwrap().is_none());
+        assert!(matches!(
+            load_key(&source, &diag, true),
+            Err(VerShimError::Env(_))
+        ));
+    }
+
+    #[test]
+    fn signed_section() {
+        let dir = fixtures::temp_dir("signed_section");
+        let key_path = dir.join("key");
+        fs::write(&key_path, encode_hex(&SECRET)).unwrap();
+        let buffer = LinkSection::new()
+            .with_custom("signed")
+            .with_signing_key(&key_path)
+            .build_section_bytes();
+        let section = VersionSection::new(Vec::leak(buffer));
+        assert_eq!(section.get(Member::Custom), Some("signed"));
+        assert_eq!(section.verify_signature(&public_key()), Ok(()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+//! Patching Motorola S-record images.
+//!
+//! This works like the Intel HEX patching in `ihex`: the bytes of the data records which
+//! cover the version section are overwritten, and the checksums of those records are
+//! recomputed. All other records are passed through unchanged. S-records carry the full
+//! address in each data record, so there are no address records to track.
+//!
+//! See: https://en.wikipedia.org/wiki/SREC_(file_format)
+
+use crate::ihex::decode_hex;
+
+/// A single parsed data record (`S1`, `S2`, or `S3`).
+struct Record {
+    kind: u8,
+    address: u32,
+    data: Vec<u8>,
+}
+
+/// Returns the number of address bytes of a data record type, or `None` for other types.
+fn address_len(kind: u8) -> Option<usize> {
+    match kind {
+        b'1' => Some(2),
+        b'2' => Some(3),
+        b'3' => Some(4),
+        _ => None,
+    }
+}
+
+impl Record {
+    /// Parses a data record line (without the line ending), validating its checksum.
+    ///
+    /// Returns `Ok(None)` for records other than data records.
+    fn parse(line: &str) -> Result<Option<Self>, String> {
+        let rest = line
+            .strip_prefix('S')
+            .ok_or_else(|| "record does not start with 'S'".to_string())?;
+        let kind = *rest
+            .as_bytes()
+            .first()
+            .ok_or_else(|| "record has no type".to_string())?;
+        let Some(address_len) = address_len(kind) else {
+            return Ok(None);
+        };
+        let bytes = decode_hex(&rest[1..])?;
+        if bytes.len() < 2 + address_len || bytes.len() != 1 + bytes[0] as usize {
+            return Err("record length does not match its byte count".to_string());
+        }
+        let sum = bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        if sum != 0xff {
+            return Err("record checksum mismatch".to_string());
+        }
+        let address = bytes[1..1 + address_len]
+            .iter()
+            .fold(0u32, |acc, b| (acc << 8) | *b as u32);
+        Ok(Some(Self {
+            kind,
+            address,
+            data: bytes[1 + address_len..bytes.len() - 1].to_vec(),
+        }))
+    }
+
+    /// Encodes the record as a line (without the line ending), with a fresh checksum.
+    fn encode(&self) -> String {
+        let address_len = address_len(self.kind).expect("data record");
+        let mut bytes = Vec::with_capacity(2 + address_len + self.data.len());
+        bytes.push((address_len + self.data.len() + 1) as u8);
+        bytes.extend_from_slice(&self.address.to_be_bytes()[4 - address_len..]);
+        bytes.extend_from_slice(&self.data);
+        let sum = bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        bytes.push(!sum);
+
+        let mut line = String::with_capacity(2 + bytes.len() * 2);
+        line.push('S');
+        line.push(self.kind as char);
+        for b in bytes {
+            line.push_str(&format!("{:02X}", b));
+        }
+        line
+    }
+}
+
+/// Overwrites `bytes` at `address` in the S-record image `text`, returning the new image.
+///
+/// Returns `Err` if the image is malformed, or if its data records don't cover the
+/// whole address range (e.g. the address is wrong, or the section was discarded).
+pub fn patch(text: &str, address: u32, bytes: &[u8]) -> Result<String, String> {
+    let target_start = address as u64;
+    let target_end = target_start + bytes.len() as u64;
+
+    let mut out = String::with_capacity(text.len());
+    let mut covered = 0usize;
+
+    for (idx, line) in text.split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\r', '\n']);
+        if content.is_empty() {
+            out.push_str(line);
+            continue;
+        }
+        let record = Record::parse(content).map_err(|e| format!("line {}: {}", idx + 1, e))?;
+
+        if let Some(mut record) = record {
+            let start = record.address as u64;
+            let end = start + record.data.len() as u64;
+            let lo = start.max(target_start);
+            let hi = end.min(target_end);
+            if lo < hi {
+                for addr in lo..hi {
+                    record.data[(addr - start) as usize] = bytes[(addr - target_start) as usize];
+                }
+                covered += (hi - lo) as usize;
+                out.push_str(&record.encode());
+                out.push_str(&line[content.len()..]);
+                continue;
+            }
+        }
+        out.push_str(line);
+    }
+
+    if covered < bytes.len() {
+        return Err(format!(
+            "data records cover only {} of the {} bytes at {:#x}, is the load address correct?",
+            covered,
+            bytes.len(),
+            address
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn record(kind: u8, address: u32, data: &[u8]) -> String {
+        Record {
+            kind,
+            address,
+            data: data.to_vec(),
+        }
+        .encode()
+    }
+
+    const HEADER: &str = "S00600004844521B";
+
+    /// Reads the bytes of an image by address, checking every record's checksum.
+    fn read_image(text: &str) -> BTreeMap<u32, u8> {
+        let mut memory = BTreeMap::new();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            if let Some(record) = Record::parse(line).unwrap() {
+                for (i, b) in record.data.iter().enumerate() {
+                    memory.insert(record.address + i as u32, *b);
+                }
+            }
+        }
+        memory
+    }
+
+    #[test]
+    fn data_record_types() {
+        for (kind, address, end) in [
+            (b'1', 0x1000, "S9031000EC"),
+            (b'2', 0x01_0000, "S804010000FA"),
+            (b'3', 0x0800_0000, "S70508000000F2"),
+        ] {
+            let text = [
+                HEADER.to_string(),
+                record(kind, address, &[0xff; 16]),
+                end.to_string(),
+            ]
+            .join("\n");
+            let patched = patch(&text, address + 4, b"ver").unwrap();
+
+            let lines: Vec<&str> = patched.lines().collect();
+            assert_eq!(lines[0], HEADER);
+            assert!(lines[1].starts_with(&format!("S{}", kind as char)));
+            assert_eq!(lines[2], end);
+            let memory = read_image(&patched);
+            assert_eq!(memory[&(address + 3)], 0xff);
+            assert_eq!(memory[&(address + 4)], b'v');
+            assert_eq!(memory[&(address + 6)], b'r');
+            assert_eq!(memory[&(address + 7)], 0xff);
+        }
+    }
+
+    #[test]
+    fn section_spanning_records() {
+        let text = [
+            record(b'3', 0x0800_0000, &[0xff; 16]),
+            record(b'3', 0x0800_0010, &[0xff; 16]),
+            record(b'3', 0x0800_0020, &[0xff; 16]),
+        ]
+        .join("\n");
+        let bytes: Vec<u8> = (0..24).collect();
+        let patched = patch(&text, 0x0800_000c, &bytes).unwrap();
+
+        let memory = read_image(&patched);
+        for (i, b) in bytes.iter().enumerate() {
+            assert_eq!(memory[&(0x0800_000c + i as u32)], *b);
+        }
+        assert_eq!(memory[&0x0800_000b], 0xff);
+        assert_eq!(memory[&0x0800_0024], 0xff);
+    }
+
+    #[test]
+    fn partial_coverage() {
+        let text = record(b'1', 0x0000, &[0xff; 16]);
+        let err = patch(&text, 0x0c, &[0; 8]).unwrap_err();
+        assert!(err.contains("cover only 4 of the 8 bytes"), "{}", err);
+
+        let err = patch(&text, 0x100, &[0; 8]).unwrap_err();
+        assert!(err.contains("cover only 0 of the 8 bytes"), "{}", err);
+    }
+
+    #[test]
+    fn checksums_are_recomputed() {
+        let untouched = record(b'1', 0x0010, &[0xff; 16]);
+        let text = [record(b'1', 0x0000, &[0xff; 16]), untouched.clone()].join("\n");
+        let patched = patch(&text, 0x04, b"ver").unwrap();
+
+        let lines: Vec<&str> = patched.lines().collect();
+        let mut data = [0xff; 16];
+        data[4..7].copy_from_slice(b"ver");
+        assert_eq!(lines[0], record(b'1', 0x0000, &data));
+        assert_eq!(Record::parse(lines[0]).unwrap().unwrap().data, data);
+        assert_eq!(lines[1], untouched);
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let text = "S1130000FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF00\n";
+        let err = patch(text, 0, b"ver").unwrap_err();
+        assert_eq!(err, "line 1: record checksum mismatch");
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let text = format!(
+            "{}\r\n{}\r\n\r\n{}\r\n",
+            HEADER,
+            record(b'1', 0x0000, &[0xff; 16]),
+            record(b'1', 0x0010, &[0xff; 16]),
+        );
+        let patched = patch(&text, 0x0e, b"ver").unwrap();
+
+        assert_eq!(patched.matches("\r\n").count(), 4);
+        assert_eq!(patched.matches('\n').count(), 4);
+        let memory = read_image(&patched);
+        assert_eq!(memory[&0x0e], b'v');
+        assert_eq!(memory[&0x10], b'r');
+    }
+}
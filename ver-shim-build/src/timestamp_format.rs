@@ -0,0 +1,41 @@
+//! Formats of the git commit timestamp and build timestamp members.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::error::VerShimError;
+
+/// How a timestamp member is formatted.
+///
+/// See `LinkSection::with_git_commit_timestamp_format()` and
+/// `LinkSection::with_build_timestamp_format()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// RFC 3339, e.g. `2024-01-15T10:30:00+00:00`.
+    #[default]
+    Rfc3339,
+    /// Unix time in seconds, e.g. `1705314600`.
+    Unix,
+    /// A `chrono` strftime format string, e.g. `%Y%m%d%H%M%S` for `20240115103000`.
+    Custom(String),
+}
+
+impl TimestampFormat {
+    /// Formats a timestamp.
+    ///
+    /// Returns a `Config` error if a custom format string is invalid.
+    pub(crate) fn apply(&self, time: &DateTime<FixedOffset>) -> Result<String, VerShimError> {
+        match self {
+            Self::Rfc3339 => Ok(time.to_rfc3339()),
+            Self::Unix => Ok(time.timestamp().to_string()),
+            Self::Custom(format) => {
+                let mut out = String::new();
+                write!(out, "{}", time.format(format)).map_err(|_| {
+                    VerShimError::Config(format!("invalid timestamp format {:?}", format))
+                })?;
+                Ok(out)
+            }
+        }
+    }
+}
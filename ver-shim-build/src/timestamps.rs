@@ -0,0 +1,80 @@
+//! Sanity checks for collected timestamps, see `LinkSection::check_timestamps()`.
+//!
+//! These catch timestamps which are obviously wrong, e.g. from a CI machine with a
+//! broken clock, before they are embedded in a shipped binary.
+
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+
+use crate::diagnostics::{Diagnostics, Level};
+use crate::error::VerShimError;
+
+/// How far in the future a commit timestamp may be, to allow for clock skew
+/// between the machine that made the commit and the build machine.
+const MAX_CLOCK_SKEW: TimeDelta = TimeDelta::minutes(5);
+
+/// Gets the earliest plausible timestamp: the first commit of git itself.
+///
+/// Anything earlier most likely comes from a zero or unset clock.
+fn earliest_plausible() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2005, 4, 7, 0, 0, 0).unwrap()
+}
+
+/// Checks the commit and build timestamps, and reports any problem at `level`.
+///
+/// Returns the first problem as an error if `level` is `Error`.
+pub fn check(
+    commit_time: Option<DateTime<Utc>>,
+    build_time: Option<DateTime<Utc>>,
+    level: Level,
+    diag: &Diagnostics,
+) -> Result<(), VerShimError> {
+    let earliest = earliest_plausible();
+
+    if let Some(commit_time) = commit_time {
+        if commit_time < earliest {
+            diag.report(
+                level,
+                VerShimError::Check(format!(
+                    "git commit timestamp {} is implausibly old, is the commit date wrong?",
+                    commit_time.to_rfc3339()
+                )),
+            )?;
+        }
+        let now = Utc::now();
+        if commit_time > now + MAX_CLOCK_SKEW {
+            diag.report(
+                level,
+                VerShimError::Check(format!(
+                    "git commit timestamp {} is in the future (now is {}), is the clock wrong?",
+                    commit_time.to_rfc3339(),
+                    now.to_rfc3339()
+                )),
+            )?;
+        }
+    }
+
+    if let Some(build_time) = build_time {
+        if build_time < earliest {
+            diag.report(
+                level,
+                VerShimError::Check(format!(
+                    "build timestamp {} is implausibly old, is the clock or VER_SHIM_BUILD_TIME wrong?",
+                    build_time.to_rfc3339()
+                )),
+            )?;
+        }
+        if let Some(commit_time) = commit_time
+            && build_time + MAX_CLOCK_SKEW < commit_time
+        {
+            diag.report(
+                level,
+                VerShimError::Check(format!(
+                    "build timestamp {} is earlier than git commit timestamp {}",
+                    build_time.to_rfc3339(),
+                    commit_time.to_rfc3339()
+                )),
+            )?;
+        }
+    }
+    Ok(())
+}
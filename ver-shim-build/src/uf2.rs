@@ -0,0 +1,209 @@
+//! Patching UF2 images, as used by the RP2040 and other USB mass storage bootloaders.
+//!
+//! A UF2 file is a sequence of 512-byte blocks, each carrying up to 476 bytes of payload
+//! for a target address. We overwrite the payload bytes of the blocks which cover the
+//! version section. Blocks have no checksum of their own, so nothing else changes.
+//!
+//! See: https://github.com/microsoft/uf2
+
+const BLOCK_SIZE: usize = 512;
+const MAGIC_START0: u32 = 0x0a32_4655;
+const MAGIC_START1: u32 = 0x9e5d_5157;
+const MAGIC_END: u32 = 0x0ab1_6f30;
+/// The payload is not for main flash, e.g. a comment.
+const FLAG_NOT_MAIN_FLASH: u32 = 0x0000_0001;
+/// The block carries an MD5 checksum of a flash region, which patching would invalidate.
+const FLAG_MD5_CHECKSUM: u32 = 0x0000_4000;
+/// Offset of the payload in a block, after the eight header words.
+const PAYLOAD_OFFSET: usize = 32;
+const MAX_PAYLOAD: usize = 476;
+
+fn word(block: &[u8], index: usize) -> u32 {
+    u32::from_le_bytes(block[index * 4..index * 4 + 4].try_into().unwrap())
+}
+
+/// Returns true if the data is a UF2 image, i.e. starts with a UF2 block.
+pub fn is_uf2(data: &[u8]) -> bool {
+    data.len() >= BLOCK_SIZE && word(data, 0) == MAGIC_START0 && word(data, 1) == MAGIC_START1
+}
+
+/// Overwrites `bytes` at `address` in the UF2 image `data`.
+///
+/// Returns `Err` if the image is malformed, if its blocks don't cover the whole address
+/// range (e.g. the address is wrong), or if a block to patch has an MD5 checksum.
+pub fn patch(data: &mut [u8], address: u32, bytes: &[u8]) -> Result<(), String> {
+    if !data.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(format!(
+            "file size {} is not a multiple of the {} byte block size",
+            data.len(),
+            BLOCK_SIZE
+        ));
+    }
+    let target_start = address as u64;
+    let target_end = target_start + bytes.len() as u64;
+    let mut covered = 0usize;
+
+    for (index, block) in data.chunks_exact_mut(BLOCK_SIZE).enumerate() {
+        if word(block, 0) != MAGIC_START0
+            || word(block, 1) != MAGIC_START1
+            || word(&block[BLOCK_SIZE - 4..], 0) != MAGIC_END
+        {
+            return Err(format!("block {}: bad magic", index));
+        }
+        let flags = word(block, 2);
+        if flags & FLAG_NOT_MAIN_FLASH != 0 {
+            continue;
+        }
+        let payload_size = word(block, 4) as usize;
+        if payload_size > MAX_PAYLOAD {
+            return Err(format!(
+                "block {}: payload size {} is too large",
+                index, payload_size
+            ));
+        }
+
+        let start = word(block, 3) as u64;
+        let end = start + payload_size as u64;
+        let lo = start.max(target_start);
+        let hi = end.min(target_end);
+        if lo >= hi {
+            continue;
+        }
+        if flags & FLAG_MD5_CHECKSUM != 0 {
+            return Err(format!(
+                "block {} has an MD5 checksum, which patching would invalidate",
+                index
+            ));
+        }
+        for addr in lo..hi {
+            block[PAYLOAD_OFFSET + (addr - start) as usize] = bytes[(addr - target_start) as usize];
+        }
+        covered += (hi - lo) as usize;
+    }
+
+    if covered < bytes.len() {
+        return Err(format!(
+            "blocks cover only {} of the {} bytes at {:#x}, is the load address correct?",
+            covered,
+            bytes.len(),
+            address
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a block with 256 bytes of `0xff` payload at `address`, as the RP2040 tools do.
+    fn block(address: u32, flags: u32, index: u32, count: u32) -> Vec<u8> {
+        let mut block = vec![0; BLOCK_SIZE];
+        let header = [
+            MAGIC_START0,
+            MAGIC_START1,
+            flags,
+            address,
+            256,
+            index,
+            count,
+            0,
+        ];
+        for (i, w) in header.iter().enumerate() {
+            block[i * 4..i * 4 + 4].copy_from_slice(&w.to_le_bytes());
+        }
+        block[PAYLOAD_OFFSET..PAYLOAD_OFFSET + 256].fill(0xff);
+        block[BLOCK_SIZE - 4..].copy_from_slice(&MAGIC_END.to_le_bytes());
+        block
+    }
+
+    fn image(addresses: &[u32]) -> Vec<u8> {
+        let count = addresses.len() as u32;
+        addresses
+            .iter()
+            .enumerate()
+            .flat_map(|(i, address)| block(*address, 0, i as u32, count))
+            .collect()
+    }
+
+    fn payload(data: &[u8], index: usize) -> &[u8] {
+        let block = &data[index * BLOCK_SIZE..(index + 1) * BLOCK_SIZE];
+        &block[PAYLOAD_OFFSET..PAYLOAD_OFFSET + 256]
+    }
+
+    #[test]
+    fn patch_block() {
+        let original = image(&[0x1000_0000, 0x1000_0100]);
+        let mut data = original.clone();
+        assert!(is_uf2(&data));
+        patch(&mut data, 0x1000_0110, b"ver").unwrap();
+
+        assert_eq!(payload(&data, 0), payload(&original, 0));
+        assert_eq!(&payload(&data, 1)[0x10..0x13], b"ver");
+        assert!(payload(&data, 1)[..0x10].iter().all(|&b| b == 0xff));
+        assert!(payload(&data, 1)[0x13..].iter().all(|&b| b == 0xff));
+        // Only the payload bytes change
+        let changed: Vec<usize> = (0..data.len())
+            .filter(|&i| data[i] != original[i])
+            .collect();
+        assert_eq!(
+            changed,
+            (BLOCK_SIZE + PAYLOAD_OFFSET + 0x10..BLOCK_SIZE + PAYLOAD_OFFSET + 0x13)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn section_spanning_blocks() {
+        let mut data = image(&[0x1000_0000, 0x1000_0100, 0x1000_0200]);
+        let bytes: Vec<u8> = (0..=255).cycle().take(300).collect();
+        patch(&mut data, 0x1000_00f0, &bytes).unwrap();
+
+        assert_eq!(&payload(&data, 0)[0xf0..], &bytes[..0x10]);
+        assert_eq!(payload(&data, 1), &bytes[0x10..0x110]);
+        assert_eq!(&payload(&data, 2)[..0x1c], &bytes[0x110..]);
+        assert!(payload(&data, 2)[0x1c..].iter().all(|&b| b == 0xff));
+    }
+
+    #[test]
+    fn blocks_out_of_order() {
+        let mut data = image(&[0x1000_0100, 0x1000_0000]);
+        patch(&mut data, 0x1000_00fe, b"ver").unwrap();
+
+        assert_eq!(&payload(&data, 1)[0xfe..], b"ve");
+        assert_eq!(&payload(&data, 0)[..1], b"r");
+    }
+
+    #[test]
+    fn partial_coverage() {
+        let mut data = image(&[0x1000_0000]);
+        let err = patch(&mut data, 0x1000_00fc, &[0; 8]).unwrap_err();
+        assert!(err.contains("cover only 4 of the 8 bytes"), "{}", err);
+    }
+
+    #[test]
+    fn not_main_flash_is_skipped() {
+        let mut data = block(0x1000_0000, FLAG_NOT_MAIN_FLASH, 0, 1);
+        let err = patch(&mut data, 0x1000_0000, b"ver").unwrap_err();
+        assert!(err.contains("cover only 0 of the 3 bytes"), "{}", err);
+    }
+
+    #[test]
+    fn md5_checksum() {
+        let mut data = block(0x1000_0000, FLAG_MD5_CHECKSUM, 0, 1);
+        let err = patch(&mut data, 0x1000_0000, b"ver").unwrap_err();
+        assert!(err.contains("MD5 checksum"), "{}", err);
+    }
+
+    #[test]
+    fn malformed() {
+        let mut data = image(&[0x1000_0000]);
+        data.push(0);
+        assert!(patch(&mut data, 0x1000_0000, b"ver").is_err());
+
+        let mut data = image(&[0x1000_0000]);
+        data[BLOCK_SIZE - 1] = 0;
+        let err = patch(&mut data, 0x1000_0000, b"ver").unwrap_err();
+        assert_eq!(err, "block 0: bad magic");
+    }
+}
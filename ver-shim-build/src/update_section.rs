@@ -1,22 +1,90 @@
 //! Update section command for patching artifact dependency binaries.
 
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 
-use ver_shim::SECTION_NAME;
+use sha2::{Digest, Sha256};
+use ver_shim::{Member, SECTION_NAME};
 
-use crate::LinkSection;
-use crate::cargo_helpers::{self, cargo_rerun_if, cargo_warning};
+use crate::CollectedSection;
+use crate::archive;
+use crate::cargo_helpers::{self, cargo_rerun_if};
+use crate::debuginfo;
+use crate::diagnostics::Diagnostics;
+use crate::elf_layout;
+use crate::error::VerShimError;
+use crate::format::SectionFormat;
+use crate::ihex;
 use crate::llvm_tools::LlvmTools;
+use crate::locator;
+use crate::macho;
+use crate::native;
+use crate::output;
+use crate::pe;
+use crate::provenance;
+use crate::raw;
+use crate::section_data;
+#[cfg(feature = "signing")]
+use crate::signing;
+use crate::srec;
+use crate::uf2;
+use crate::wasm;
 
 /// Builder for updating sections in a binary.
 ///
-/// Created by calling `LinkSection::patch_into()` or `LinkSection::patch_into_bin_dep()`.
+/// Created by calling `LinkSection::patch_into()` or `LinkSection::patch_into_bin_dep()`,
+/// or the same methods of `CollectedSection`.
 #[must_use]
 pub struct UpdateSectionCommand {
-    pub(crate) link_section: LinkSection,
+    pub(crate) section: CollectedSection,
     pub(crate) bin_path: PathBuf,
     pub(crate) new_name: Option<String>,
+    pub(crate) debug_path: Option<PathBuf>,
+    pub(crate) image: Image,
+    pub(crate) mode: Option<u32>,
+    pub(crate) atomic: bool,
+    pub(crate) resign_adhoc: bool,
+    pub(crate) post_process: Vec<PostProcess>,
+    pub(crate) fill: Option<u8>,
+    pub(crate) preserve_layout: bool,
+    pub(crate) page_size: Option<u64>,
+    pub(crate) objcopy_path: Option<PathBuf>,
+    pub(crate) verify: bool,
+    pub(crate) provenance: Option<PathBuf>,
+    pub(crate) self_hash: bool,
+}
+
+/// How an executable, shared library, or archive is patched.
+enum Method {
+    /// Overwriting the buffer in a WebAssembly module, and setting its custom section.
+    Wasm,
+    /// Overwriting the section in every member object of a static library archive.
+    Archive,
+    /// Overwriting the section of every architecture of a Mach-O binary, and re-signing it.
+    MachO,
+    /// Overwriting the section in the file: for PE images, for ELF binaries whose layout
+    /// must not change, and for ELF binaries if no objcopy is found.
+    InPlace,
+    /// Updating the section with objcopy.
+    Objcopy(LlvmTools),
+}
+
+/// A hook run on the patched binary, see `UpdateSectionCommand::with_post_process()`.
+pub(crate) type PostProcess = Box<dyn FnOnce(&Path) -> io::Result<()>>;
+
+/// The kind of file being patched.
+pub(crate) enum Image {
+    /// An executable, shared library, or static library archive. The section is found by name.
+    Binary,
+    /// An Intel HEX image. The section is at a fixed load address.
+    IntelHex { address: u32 },
+    /// A Motorola S-record image. The section is at a fixed load address.
+    Srec { address: u32 },
+    /// A UF2 image. The section is at a fixed load address.
+    Uf2 { address: u32 },
+    /// A raw binary image. The section is at a fixed offset in the file.
+    Raw { offset: u64 },
 }
 
 impl UpdateSectionCommand {
@@ -28,12 +96,184 @@ impl UpdateSectionCommand {
     ///
     /// If `write_to()` is called with a file path (not a directory), this will panic.
     ///
-    /// If not called, the default name is `{original_name}.bin`.
+    /// If not called, the default name is `{original_name}.bin`, or `{original_stem}-patched.hex`
+    /// for Intel HEX images.
     pub fn with_filename(mut self, name: &str) -> Self {
         self.new_name = Some(name.to_string());
         self
     }
 
+    /// Splits the debug info out of the patched binary into a separate file.
+    ///
+    /// After patching, the debug sections are copied to `debug_path` with
+    /// `llvm-objcopy --only-keep-debug`, and the patched binary is stripped with
+    /// `--strip-debug`, adding a `.gnu_debuglink` to the debug file.
+    ///
+    /// Patching happens before splitting, so the stripped binary and the debug file
+    /// carry the same GNU build-id and the same section contents, and symbol server
+    /// uploads (e.g. to debuginfod) still match. The build-ids are checked after
+    /// splitting. See also `build_id_debug_path()`.
+    ///
+    /// If `debug_path` is a directory, the debug file is written there as
+    /// `{output_name}.debug`.
+    pub fn with_split_debuginfo(mut self, debug_path: impl AsRef<Path>) -> Self {
+        self.debug_path = Some(debug_path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the permissions of the patched binary, e.g. `0o755`.
+    ///
+    /// By default the output gets the permissions of the input binary. On non-unix
+    /// platforms only the owner write bit is used, and a mode without it makes the output
+    /// read-only. Either way, on unix the extended attributes of the input binary are
+    /// copied to the output. ACLs are not copied.
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Sets whether the patched binary is written atomically.
+    ///
+    /// Defaults to the setting of `LinkSection::atomic()`, which is on unless disabled.
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    /// Extends a raw image with `fill` if the section extends past its end.
+    ///
+    /// `objcopy -O binary` leaves out trailing sections which have no data, so an image may
+    /// end before the version section, e.g. if it's placed at the end of flash. Without
+    /// this, that is an error. Use `0xff` for the contents of erased flash. Only applies to
+    /// `patch_into_raw()`.
+    pub fn with_padding(mut self, fill: u8) -> Self {
+        self.fill = Some(fill);
+        self
+    }
+
+    /// Patches ELF binaries in place, instead of with llvm-objcopy.
+    ///
+    /// llvm-objcopy writes the output file anew, which may move sections and change the
+    /// file offsets of segments. In place, only the bytes of the version section change,
+    /// so the layout is exactly that of the input. This is always done for Android
+    /// binaries (those with a `.note.android.ident` section), whose loader is strict about
+    /// segment alignment.
+    pub fn preserve_layout(mut self) -> Self {
+        self.preserve_layout = true;
+        self
+    }
+
+    /// Checks that the patched ELF binary can be loaded on devices with the given page
+    /// size, e.g. `16384` for Android devices with 16 KiB pages.
+    ///
+    /// Every `PT_LOAD` segment must be aligned to at least `page_size`, and its file
+    /// offset must be congruent to its address modulo its alignment. If not, the write
+    /// fails with `VerShimError::Check`, and the output is not created. `page_size` must be
+    /// a power of two.
+    pub fn check_page_alignment(mut self, page_size: u64) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Sets the `objcopy` used to update the section, instead of `llvm-objcopy` from the
+    /// Rust toolchain, e.g. `arm-none-eabi-objcopy` from a vendor toolchain.
+    ///
+    /// The path may also be the name of a program in `PATH`. Without this, the `OBJCOPY`
+    /// environment variable is used if set, or its target-specific variants
+    /// `OBJCOPY_{target}` and `TARGET_OBJCOPY`, like the `cc` crate does for `CC`.
+    pub fn with_objcopy_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.objcopy_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets whether the version section is read back from the patched binary and checked
+    /// against the generated data. On by default.
+    ///
+    /// A mismatch fails the write with `VerShimError::Check`, and the output is not
+    /// created. This catches e.g. an `objcopy` which silently wrote to another section.
+    /// Only applies to executables, shared libraries, static library archives, and
+    /// WebAssembly modules, not to the images of `patch_into_ihex()` and the like. Of a
+    /// universal Mach-O binary, the first architecture is checked.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Records the SHA-256 of the patched binary in the section, so that the binary can
+    /// check its own integrity with `ver_shim::verify_self_hash()`.
+    ///
+    /// The hash covers the patched file with the version buffer zeroed, and for PE images
+    /// with the header checksum zeroed, so that writing the hash into the section doesn't
+    /// change it. Anything which modifies the binary afterwards makes the check fail, so
+    /// post-processing hooks must not modify it, and signing (e.g. with Authenticode)
+    /// can't be combined with this. Only applies to ELF and PE binaries, since Mach-O
+    /// binaries are re-signed after patching, with a signature over the section.
+    ///
+    /// The hash takes 64 bytes of the section. If the binary has a signing key from
+    /// `LinkSection::with_signing_key()`, the signature covers the hash too.
+    pub fn with_self_hash(mut self) -> Self {
+        self.self_hash = true;
+        self
+    }
+
+    /// Writes a provenance statement for the patched binary to `path`, for supply chain
+    /// tooling that checks where a binary came from.
+    ///
+    /// The statement is an in-toto Statement with a SLSA Provenance v1 predicate. Its
+    /// subject is the patched binary with its SHA-256, taken after the post-processing
+    /// hooks. The collected git SHA (and the remote URL, if collected) and the lockfile
+    /// hash are listed as resolved dependencies, and the package name, version, target,
+    /// branch, and profile as external parameters, if collected. The builder is the CI
+    /// provider, e.g. `urn:ver-shim:builder:github-actions`, or
+    /// `urn:ver-shim:builder:local` outside of CI, and the URL of the CI run is the
+    /// invocation ID.
+    ///
+    /// If `path` is a directory, the statement is written there as
+    /// `{output_name}.intoto.jsonl`. The statement is not signed; sign it with e.g.
+    /// `cosign attest-blob` if a verifier needs that.
+    pub fn with_provenance_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.provenance = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Re-signs the patched binary ad-hoc with `codesign --force --sign -`.
+    ///
+    /// Mach-O binaries which were signed are always re-signed, since patching invalidates
+    /// the signature, and a warning is emitted if `codesign` is missing. With this, the
+    /// binary is signed even if it wasn't before, and a missing `codesign` is an error.
+    /// Other binaries are not affected, apart from a warning.
+    pub fn resign_adhoc(mut self) -> Self {
+        self.resign_adhoc = true;
+        self
+    }
+
+    /// Adds a hook which is run on the patched binary, e.g. to sign it.
+    ///
+    /// Hooks run in the order they were added, after the permissions are set. With atomic
+    /// writes (the default), the path passed to the hook is the temporary file, which is
+    /// renamed to the output afterwards. An error from a hook fails the write, and the
+    /// output is not created.
+    ///
+    /// ```ignore
+    /// section
+    ///     .patch_into_bin_dep("my-server", "my-server")
+    ///     .with_post_process(|path| {
+    ///         let status = std::process::Command::new("signtool").arg("sign").arg(path).status()?;
+    ///         if !status.success() {
+    ///             return Err(std::io::Error::other(format!("signtool failed ({status})")));
+    ///         }
+    ///         Ok(())
+    ///     })
+    ///     .write_to_target_profile_dir();
+    /// ```
+    pub fn with_post_process(
+        mut self,
+        hook: impl FnOnce(&Path) -> io::Result<()> + 'static,
+    ) -> Self {
+        self.post_process.push(Box::new(hook));
+        self
+    }
+
     /// Writes the patched binary to the specified path.
     ///
     /// If the path is a directory, the output filename will be determined by
@@ -44,8 +284,19 @@ impl UpdateSectionCommand {
     ///
     /// If the section doesn't exist in the input binary, a warning is logged and the
     /// binary is copied without modification.
+    ///
+    /// Panics on failure, see `try_write_to()`.
     pub fn write_to(self, path: impl AsRef<Path>) {
-        eprintln!("ver-shim-build: input binary = {}", self.bin_path.display());
+        let diag = self.section.diagnostics.clone();
+        self.try_write_to(path).unwrap_or_else(|e| diag.fail(e));
+    }
+
+    /// Like `write_to()`, but returns an error instead of panicking.
+    ///
+    /// Returns the path to the written binary.
+    pub fn try_write_to(mut self, path: impl AsRef<Path>) -> Result<PathBuf, VerShimError> {
+        let diag = self.section.diagnostics.clone();
+        diag.info(format!("input binary = {}", self.bin_path.display()));
 
         // Emit rerun-if-changed for the input binary
         // See: https://doc.rust-lang.org/cargo/reference/build-scripts.html#rerun-if-changed
@@ -55,89 +306,814 @@ impl UpdateSectionCommand {
         let path = path.as_ref();
         let output_path = if path.is_dir() {
             // Directory: use new_name if set, otherwise default to {original_name}.bin
-            let original_name = self
-                .bin_path
-                .file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("output");
-            let default_name = format!("{}.bin", original_name);
+            let default_name = self.default_output_name();
             let output_name = self.new_name.as_deref().unwrap_or(&default_name);
             path.join(output_name)
         } else {
-            // File path: write directly, but panic if with_filename was used
+            // File path: write directly, but fail if with_filename was used
             if self.new_name.is_some() {
-                panic!(
-                    "ver-shim-build: with_filename() cannot be used when write_to() \
+                return Err(VerShimError::Config(format!(
+                    "with_filename() cannot be used when write_to() \
                      is called with a file path (not a directory): {}",
                     path.display()
-                );
+                )));
             }
             path.to_path_buf()
         };
 
-        let llvm = LlvmTools::new().unwrap_or_else(|e| {
-            panic!(
-                "ver-shim-build: could not find LLVM tools directory: {}\n\
-                 Please install llvm-tools: rustup component add llvm-tools",
-                e
-            )
+        // The default debug file name is that of the output, not of a temporary file
+        let debug_path = self.debug_path.as_ref().map(|debug_path| {
+            if debug_path.is_dir() {
+                let output_name = output_path
+                    .file_name()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("output");
+                debug_path.join(format!("{}.debug", output_name))
+            } else {
+                debug_path.clone()
+            }
         });
 
-        // Get section size from the binary
-        let section_size = llvm
-            .get_section_size(&self.bin_path, SECTION_NAME)
-            .unwrap_or_else(|e| {
-                panic!(
-                    "ver-shim-build: failed to read section info from {}: {}",
-                    self.bin_path.display(),
+        let provenance_path = self.provenance.as_ref().map(|provenance_path| {
+            if provenance_path.is_dir() {
+                let output_name = output_path
+                    .file_name()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("output");
+                provenance_path.join(format!("{}.intoto.jsonl", output_name))
+            } else {
+                provenance_path.clone()
+            }
+        });
+
+        if self.section.dry_run {
+            self.report_plan(&output_path, debug_path.as_deref())?;
+            if let Some(provenance_path) = &provenance_path {
+                diag.info(format!(
+                    "dry run, would write provenance to {}",
+                    provenance_path.display()
+                ));
+            }
+            return Ok(output_path);
+        }
+
+        if let Some(page_size) = self.page_size
+            && !page_size.is_power_of_two()
+        {
+            return Err(VerShimError::Config(format!(
+                "check_page_alignment() needs a power of two, not {}",
+                page_size
+            )));
+        }
+
+        let post_process = std::mem::take(&mut self.post_process);
+        output::replace(&output_path, self.atomic, |write_path| {
+            self.patch_to(write_path, debug_path.as_deref())?;
+            if self.self_hash {
+                self.write_self_hash(write_path)?;
+            }
+            if self.verify && matches!(self.image, Image::Binary) {
+                self.verify_output(write_path)?;
+            }
+            if let Some(page_size) = self.page_size {
+                self.check_page_alignment_of(write_path, page_size)?;
+            }
+            set_permissions(&self.bin_path, write_path, self.mode)?;
+            copy_xattrs(&self.bin_path, write_path, &diag)?;
+            for hook in post_process {
+                hook(write_path).map_err(|e| {
+                    VerShimError::io(
+                        format!("post-processing of {} failed", output_path.display()),
+                        e,
+                    )
+                })?;
+            }
+            Ok(())
+        })?;
+
+        if let Some(provenance_path) = provenance_path {
+            self.write_provenance(&output_path, &provenance_path)?;
+        }
+        Ok(output_path)
+    }
+
+    /// Records the hash of the patched binary in its section, see `with_self_hash()`.
+    fn write_self_hash(&mut self, path: &Path) -> Result<(), VerShimError> {
+        let mut data = read(path)?;
+        let is_pe = pe::is_pe_file(path).unwrap_or(false);
+        if !matches!(self.image, Image::Binary) || !(elf_layout::is_elf(&data) || is_pe) {
+            return Err(VerShimError::Config(format!(
+                "with_self_hash() only applies to ELF and PE binaries, not {}",
+                self.bin_path.display()
+            )));
+        }
+        let buffer = section_data::find_buffer(&data, self.custom_section_name())
+            .map_err(|e| {
+                VerShimError::InvalidBinary(format!(
+                    "failed to read the version section of {}: {}",
+                    path.display(),
                     e
-                )
-            });
+                ))
+            })?
+            .ok_or_else(|| {
+                VerShimError::Check(format!(
+                    "{} has no version section to record the self hash in",
+                    self.bin_path.display()
+                ))
+            })?;
+        let range = ver_shim::find_self_hash_buffer(&data, &buffer).ok_or_else(|| {
+            VerShimError::Check(format!(
+                "the version section of {} is not stored in the file as is",
+                path.display()
+            ))
+        })?;
+
+        let mut masked = data.clone();
+        ver_shim::mask_self_hash_image(&mut masked, range.clone());
+        let hash: String = Sha256::digest(&masked)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let diag = self.section.diagnostics.clone();
+        diag.info(format!("self hash = {}", hash));
+
+        let section = &mut self.section;
+        section.member_data[Member::SelfHash as usize] = Some(hash);
+        #[cfg(feature = "signing")]
+        if let Some(key) = &section.signing_key {
+            signing::sign(key, &mut section.member_data);
+        }
+        let format = SectionFormat::detect(&buffer)?;
+        let section_bytes = section.build_section_bytes_for(buffer.len(), format)?;
+        data[range].copy_from_slice(&section_bytes);
+        write(path, &data)?;
+        if is_pe {
+            update_pe_checksum(path, &diag)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the provenance statement of the patched binary, see `with_provenance_file()`.
+    fn write_provenance(
+        &self,
+        output_path: &Path,
+        provenance_path: &Path,
+    ) -> Result<(), VerShimError> {
+        let binary = read(output_path)?;
+        let name = output_path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("output");
+        let statement = provenance::statement(name, &binary, &self.section);
+        output::write(provenance_path, statement, self.atomic).map_err(|e| {
+            VerShimError::io(format!("failed to write {}", provenance_path.display()), e)
+        })?;
+        self.section
+            .diagnostics
+            .info(format!("wrote provenance to {}", provenance_path.display()));
+        Ok(())
+    }
+
+    /// Writes the patched binary to `output_path`, splitting the debug info to
+    /// `debug_path` if set.
+    fn patch_to(&self, output_path: &Path, debug_path: Option<&Path>) -> Result<(), VerShimError> {
+        let diag = self.section.diagnostics.clone();
+
+        let is_macho = matches!(self.image, Image::Binary)
+            && macho::is_macho_file(&self.bin_path).unwrap_or(false);
+        if self.resign_adhoc && !is_macho {
+            diag.warning("resign_adhoc() only applies to Mach-O binaries");
+        }
+
+        match self.image {
+            Image::Binary => {}
+            Image::IntelHex { address } => {
+                return self.patch_text_image(address, output_path, "Intel HEX", ihex::patch);
+            }
+            Image::Srec { address } => {
+                return self.patch_text_image(address, output_path, "S-record", srec::patch);
+            }
+            Image::Uf2 { address } => return self.patch_uf2(address, output_path),
+            Image::Raw { offset } => return self.patch_raw(offset, output_path),
+        }
+
+        let llvm = match self.method(debug_path)? {
+            Method::Wasm => return self.patch_wasm(output_path),
+            Method::Archive => return self.patch_archive(output_path),
+            Method::MachO => return self.patch_macho(output_path),
+            Method::InPlace => {
+                self.patch_in_place(output_path)?;
+                // ELF binaries patched in place to keep their layout are split afterwards
+                if let Some(debug_path) = debug_path
+                    && !pe::is_pe_file(&self.bin_path).unwrap_or(false)
+                {
+                    let llvm =
+                        LlvmTools::new(self.objcopy_path.as_deref()).map_err(llvm_tools_missing)?;
+                    debuginfo::split(&llvm, output_path, debug_path, &diag)?;
+                }
+                return Ok(());
+            }
+            Method::Objcopy(llvm) => llvm,
+        };
+
+        // Get section size from the binary
+        let section_size =
+            native::section_size(&read(&self.bin_path)?, self.custom_section_name())?;
 
         match section_size {
             Some(size) => {
                 // Build section data with the correct buffer size from the binary
-                let section_bytes = self
-                    .link_section
-                    .with_buffer_size(size)
-                    .build_section_bytes();
+                let format = self.detect_format_in_file()?;
+                let section_bytes = self.section.build_section_bytes_for(size, format)?;
 
                 llvm.update_section_with_bytes(
                     &self.bin_path,
-                    &output_path,
-                    SECTION_NAME,
+                    output_path,
+                    self.section_name(),
                     &section_bytes,
-                )
-                .unwrap_or_else(|e| {
-                    panic!(
-                        "ver-shim-build: failed to update section in {}: {}",
-                        self.bin_path.display(),
-                        e
-                    )
-                });
-                eprintln!(
-                    "ver-shim-build: wrote patched binary to {}",
-                    output_path.display()
-                );
+                )?;
+                diag.info(format!("wrote patched binary to {}", output_path.display()));
+                update_pe_checksum(output_path, &diag)?;
+
+                if let Some(debug_path) = debug_path {
+                    debuginfo::split(&llvm, output_path, debug_path, &diag)?;
+                }
             }
             None => {
-                // Section doesn't exist, copy binary without modification
-                cargo_warning(&format!(
-                    "section '{}' not found in {}, copying without modification",
-                    SECTION_NAME,
-                    self.bin_path.display()
-                ));
-                fs::copy(&self.bin_path, &output_path).unwrap_or_else(|e| {
-                    panic!(
-                        "ver-shim-build: failed to copy {} to {}: {}",
-                        self.bin_path.display(),
-                        output_path.display(),
-                        e
+                // Section doesn't exist. If ver-shim was built with the `locator` feature,
+                // the buffer is found by its magic prefix instead.
+                let mut bin_bytes = read(&self.bin_path)?;
+                let location = self.locate_buffer(&bin_bytes)?;
+
+                match location {
+                    Some(location) => {
+                        let range = location.offset..location.offset + location.size;
+                        let format = SectionFormat::detect(&bin_bytes[range.clone()])?;
+                        let section_bytes = self
+                            .section
+                            .build_section_bytes_for(location.size, format)?;
+                        bin_bytes[range].copy_from_slice(&section_bytes);
+                        write(output_path, &bin_bytes)?;
+                        update_pe_checksum(output_path, &diag)?;
+                        diag.info(format!(
+                            "wrote patched binary to {} (located by magic prefix at offset {})",
+                            output_path.display(),
+                            location.offset
+                        ));
+                    }
+                    None => {
+                        // Neither the section nor the magic prefix exist, copy binary without modification
+                        diag.warning(format!(
+                            "section '{}' not found in {}, copying without modification",
+                            self.section_name(),
+                            self.bin_path.display()
+                        ));
+                        copy(&self.bin_path, output_path)?;
+                        diag.info(format!("copied to {}", output_path.display()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports how the binary would be patched, for `LinkSection::dry_run()`.
+    fn report_plan(
+        &self,
+        output_path: &Path,
+        debug_path: Option<&Path>,
+    ) -> Result<(), VerShimError> {
+        // Fail like patching would, if the input is missing
+        fs::metadata(&self.bin_path).map_err(|e| {
+            VerShimError::io(format!("failed to read {}", self.bin_path.display()), e)
+        })?;
+        let how = match self.image {
+            Image::IntelHex { address } => format!("patch the Intel HEX image at {:#x}", address),
+            Image::Srec { address } => format!("patch the S-record image at {:#x}", address),
+            Image::Uf2 { address } => format!("patch the UF2 image at {:#x}", address),
+            Image::Raw { offset } => format!("patch the raw image at offset {:#x}", offset),
+            Image::Binary => match self.method(debug_path)? {
+                Method::Wasm => "patch the WebAssembly module in place".to_string(),
+                Method::Archive => "patch the archive members in place".to_string(),
+                Method::MachO => "patch the Mach-O binary in place".to_string(),
+                Method::InPlace => "patch the binary in place".to_string(),
+                Method::Objcopy(llvm) => format!(
+                    "run `{}`",
+                    llvm.update_section_command_line(
+                        &self.bin_path,
+                        output_path,
+                        self.section_name()
                     )
-                });
-                eprintln!("ver-shim-build: copied to {}", output_path.display());
+                ),
+            },
+        };
+        let diag = &self.section.diagnostics;
+        diag.info(format!(
+            "dry run, would {} and write {}",
+            how,
+            output_path.display()
+        ));
+        if let Some(debug_path) = debug_path {
+            diag.info(format!(
+                "dry run, would split debug info to {}",
+                debug_path.display()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Decides how to patch an executable, shared library, or archive.
+    fn method(&self, debug_path: Option<&Path>) -> Result<Method, VerShimError> {
+        // WebAssembly modules are patched in place, by the locator prefix
+        if wasm::is_wasm_file(&self.bin_path).unwrap_or(false) {
+            return Ok(Method::Wasm);
+        }
+
+        // Static library archives are patched in place, without llvm-objcopy
+        if archive::is_archive_file(&self.bin_path).unwrap_or(false) {
+            return Ok(Method::Archive);
+        }
+
+        // PE images are patched in place too, rather than relying on llvm-objcopy
+        if pe::is_pe_file(&self.bin_path).unwrap_or(false) {
+            return Ok(Method::InPlace);
+        }
+
+        // Mach-O binaries are patched in place too, then re-signed
+        if macho::is_macho_file(&self.bin_path).unwrap_or(false) {
+            return Ok(Method::MachO);
+        }
+
+        // Android libraries are patched in place too, so their layout can't change
+        if self.preserve_layout || elf_layout::is_android_file(&self.bin_path).unwrap_or(false) {
+            return Ok(Method::InPlace);
+        }
+
+        match LlvmTools::new(self.objcopy_path.as_deref()) {
+            Ok(llvm) => Ok(Method::Objcopy(llvm)),
+            // Without llvm-tools or binutils, the section can still be patched in place,
+            // as long as it doesn't need to be split afterwards
+            Err(e) if debug_path.is_none() => {
+                self.section.diagnostics.info(format!(
+                    "could not find LLVM tools or GNU binutils ({}), patching in place",
+                    e
+                ));
+                Ok(Method::InPlace)
+            }
+            Err(e) => Err(llvm_tools_missing(e)),
+        }
+    }
+
+    /// Gets the custom section name set with `LinkSection::with_section_name()`, if any.
+    fn custom_section_name(&self) -> Option<&str> {
+        self.section.section_name.as_deref()
+    }
+
+    /// Gets the name of the section to patch.
+    fn section_name(&self) -> &str {
+        self.custom_section_name().unwrap_or(SECTION_NAME)
+    }
+
+    /// Finds the buffer by its magic prefix, for the `locator` feature of `ver-shim`.
+    ///
+    /// The locator only marks the default buffer, so this is skipped for a custom
+    /// section name, rather than patching the wrong buffer.
+    fn locate_buffer(&self, data: &[u8]) -> Result<Option<locator::Location>, VerShimError> {
+        if self.custom_section_name().is_some() {
+            return Ok(None);
+        }
+        locator::find(data).map_err(|e| {
+            VerShimError::InvalidBinary(format!(
+                "failed to locate version buffer in {}: {}",
+                self.bin_path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Detects the section format that the input binary expects, from the current
+    /// contents of its version section.
+    ///
+    /// A section with no data in the file is all zeros, so it is for an older `ver_shim`.
+    /// If the section can't be read, this falls back to the latest format.
+    fn detect_format_in_file(&self) -> Result<SectionFormat, VerShimError> {
+        let data = read(&self.bin_path)?;
+        match native::find_section(&data, self.custom_section_name()) {
+            Ok(Some(range)) => SectionFormat::detect(&data[range]),
+            Err(VerShimError::MissingSection(_)) => Ok(SectionFormat::V1),
+            Ok(None) | Err(_) => Ok(SectionFormat::LATEST),
+        }
+    }
+
+    /// Gets the default output filename, used when writing to a directory.
+    fn default_output_name(&self) -> String {
+        match self.image {
+            Image::Binary => {
+                let original_name = self
+                    .bin_path
+                    .file_name()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("output");
+                format!("{}.bin", original_name)
+            }
+            Image::IntelHex { .. } => {
+                let original_stem = self
+                    .bin_path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("output");
+                format!("{}-patched.hex", original_stem)
+            }
+            Image::Srec { .. } | Image::Uf2 { .. } | Image::Raw { .. } => {
+                let original_stem = self
+                    .bin_path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("output");
+                // S-record files come as .srec, .s19, .mot, etc., so keep the extension
+                let default_extension = match self.image {
+                    Image::Srec { .. } => "srec",
+                    Image::Uf2 { .. } => "uf2",
+                    _ => "bin",
+                };
+                let extension = self
+                    .bin_path
+                    .extension()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or(default_extension);
+                format!("{}-patched.{}", original_stem, extension)
+            }
+        }
+    }
+
+    /// Patches the version section into an Intel HEX or S-record image at the given load
+    /// address, with the `patch` function of the `ihex` or `srec` module.
+    ///
+    /// The section size can't be read from the image, so the buffer size of the
+    /// `LinkSection` is used (see `LinkSection::with_buffer_size()`). Likewise, the
+    /// latest section format is written.
+    fn patch_text_image(
+        &self,
+        address: u32,
+        output_path: &Path,
+        format_name: &str,
+        patch: fn(&str, u32, &[u8]) -> Result<String, String>,
+    ) -> Result<(), VerShimError> {
+        let diag = self.section.diagnostics.clone();
+        let text = fs::read_to_string(&self.bin_path).map_err(|e| {
+            VerShimError::io(format!("failed to read {}", self.bin_path.display()), e)
+        })?;
+        let section_bytes = self.section.try_build_section_bytes()?;
+        let patched = patch(&text, address, &section_bytes).map_err(|e| {
+            VerShimError::InvalidBinary(format!(
+                "failed to patch {} image {}: {}",
+                format_name,
+                self.bin_path.display(),
+                e
+            ))
+        })?;
+        write(output_path, patched)?;
+        diag.info(format!(
+            "wrote patched {} image to {} ({} bytes at {:#x})",
+            format_name,
+            output_path.display(),
+            section_bytes.len(),
+            address
+        ));
+        Ok(())
+    }
+
+    /// Patches the version section into a raw binary image at the given offset.
+    ///
+    /// As for Intel HEX images, the buffer size of the `LinkSection` is used. The format
+    /// is detected from the bytes at the offset, if they are in the image.
+    fn patch_raw(&self, offset: u64, output_path: &Path) -> Result<(), VerShimError> {
+        let diag = self.section.diagnostics.clone();
+        let mut data = read(&self.bin_path)?;
+        let size = self.section.buffer_size;
+        let format = match raw::existing(&data, offset, size) {
+            Some(existing) => {
+                if SectionFormat::from_header(existing)
+                    .ok()
+                    .flatten()
+                    .is_none()
+                    && existing.iter().any(|&b| b != 0)
+                {
+                    diag.warning(format!(
+                        "the bytes at offset {:#x} of {} don't start with a version section \
+                         header. Check the offset, unless the image was built with an older \
+                         ver-shim.",
+                        offset,
+                        self.bin_path.display()
+                    ));
+                }
+                SectionFormat::detect(existing)?
+            }
+            None => SectionFormat::LATEST,
+        };
+        let section_bytes = self.section.build_section_bytes_for(size, format)?;
+        raw::patch(&mut data, offset, &section_bytes, self.fill).map_err(|e| {
+            VerShimError::InvalidBinary(format!(
+                "failed to patch raw image {}: {}",
+                self.bin_path.display(),
+                e
+            ))
+        })?;
+        write(output_path, &data)?;
+        diag.info(format!(
+            "wrote patched raw image to {} ({} bytes at offset {:#x})",
+            output_path.display(),
+            section_bytes.len(),
+            offset
+        ));
+        Ok(())
+    }
+
+    /// Patches the version section into a UF2 image at the given load address.
+    ///
+    /// As for Intel HEX images, the buffer size of the `LinkSection` is used, and the
+    /// latest section format is written.
+    fn patch_uf2(&self, address: u32, output_path: &Path) -> Result<(), VerShimError> {
+        let diag = self.section.diagnostics.clone();
+        let mut data = read(&self.bin_path)?;
+        let invalid = |e: String| {
+            VerShimError::InvalidBinary(format!(
+                "failed to patch UF2 image {}: {}",
+                self.bin_path.display(),
+                e
+            ))
+        };
+        if !uf2::is_uf2(&data) {
+            return Err(invalid("not a UF2 image".to_string()));
+        }
+        let section_bytes = self.section.try_build_section_bytes()?;
+        uf2::patch(&mut data, address, &section_bytes).map_err(invalid)?;
+        write(output_path, &data)?;
+        diag.info(format!(
+            "wrote patched UF2 image to {} ({} bytes at {:#x})",
+            output_path.display(),
+            section_bytes.len(),
+            address
+        ));
+        Ok(())
+    }
+
+    /// Patches the version buffer of a WebAssembly module, and stores a copy of it in the
+    /// `ver_shim_data` custom section.
+    fn patch_wasm(&self, output_path: &Path) -> Result<(), VerShimError> {
+        let diag = self.section.diagnostics.clone();
+        let mut data = read(&self.bin_path)?;
+        let Some(location) = self.locate_buffer(&data)? else {
+            diag.warning(format!(
+                "version buffer not found in {}, copying without modification",
+                self.bin_path.display()
+            ));
+            return copy(&self.bin_path, output_path);
+        };
+
+        let range = location.offset..location.offset + location.size;
+        let format = SectionFormat::detect(&data[range.clone()])?;
+        let section_bytes = self
+            .section
+            .build_section_bytes_for(location.size, format)?;
+        data[range].copy_from_slice(&section_bytes);
+        let data = wasm::set_custom_section(&data, wasm::WASM_SECTION_NAME, &section_bytes)
+            .map_err(|e| {
+                VerShimError::InvalidBinary(format!(
+                    "failed to patch WebAssembly module {}: {}",
+                    self.bin_path.display(),
+                    e
+                ))
+            })?;
+        write(output_path, &data)?;
+        diag.info(format!(
+            "wrote patched WebAssembly module to {}",
+            output_path.display()
+        ));
+        Ok(())
+    }
+
+    /// Patches the version section of every member object of a static library archive.
+    fn patch_archive(&self, output_path: &Path) -> Result<(), VerShimError> {
+        let diag = self.section.diagnostics.clone();
+        let mut data = read(&self.bin_path)?;
+        let ranges = archive::find_sections(&data, self.custom_section_name()).map_err(|e| {
+            VerShimError::InvalidBinary(format!(
+                "failed to read archive {}: {}",
+                self.bin_path.display(),
+                e
+            ))
+        })?;
+
+        let Some(size) = ranges.first().map(|range| range.len()) else {
+            diag.warning(format!(
+                "section '{}' not found in any member of {}, copying without modification",
+                self.section_name(),
+                self.bin_path.display()
+            ));
+            return copy(&self.bin_path, output_path);
+        };
+        if ranges.iter().any(|range| range.len() != size) {
+            return Err(VerShimError::InvalidBinary(format!(
+                "members of {} have '{}' sections of different sizes, \
+                 are there multiple copies of ver-shim in the archive?",
+                self.bin_path.display(),
+                self.section_name()
+            )));
+        }
+
+        let format = SectionFormat::detect(&data[ranges[0].clone()])?;
+        let section_bytes = self.section.build_section_bytes_for(size, format)?;
+        for range in &ranges {
+            data[range.clone()].copy_from_slice(&section_bytes);
+        }
+
+        write(output_path, &data)?;
+        diag.info(format!(
+            "wrote patched archive to {} ({} member section(s) updated)",
+            output_path.display(),
+            ranges.len()
+        ));
+        Ok(())
+    }
+
+    /// Patches the version section of an executable or shared library in place,
+    /// without llvm-objcopy. This is used for PE images (Windows executables and DLLs),
+    /// and for ELF binaries when llvm-tools is not installed.
+    fn patch_in_place(&self, output_path: &Path) -> Result<(), VerShimError> {
+        let diag = self.section.diagnostics.clone();
+        let mut data = read(&self.bin_path)?;
+        let range = native::find_section(&data, self.custom_section_name()).map_err(|e| {
+            let msg = format!("failed to patch {}: {}", self.bin_path.display(), e);
+            match e {
+                VerShimError::MissingSection(_) => VerShimError::MissingSection(msg),
+                _ => VerShimError::InvalidBinary(msg),
+            }
+        })?;
+        // If ver-shim was built with the `locator` feature, the buffer is found by its
+        // magic prefix instead.
+        let range = match range {
+            Some(range) => Some(range),
+            None => self
+                .locate_buffer(&data)?
+                .map(|location| location.offset..location.offset + location.size),
+        };
+
+        let Some(range) = range else {
+            diag.warning(format!(
+                "version section not found in {}, copying without modification",
+                self.bin_path.display()
+            ));
+            return copy(&self.bin_path, output_path);
+        };
+
+        let format = SectionFormat::detect(&data[range.clone()])?;
+        let section_bytes = self.section.build_section_bytes_for(range.len(), format)?;
+        data[range].copy_from_slice(&section_bytes);
+        write(output_path, &data)?;
+        diag.info(format!("wrote patched binary to {}", output_path.display()));
+        update_pe_checksum(output_path, &diag)?;
+
+        // ELF binaries split their debuginfo after patching in place, see `patch_to()`
+        if self.debug_path.is_some() && !elf_layout::is_elf(&data) {
+            diag.warning(
+                "with_split_debuginfo() is not supported for PE images, debug info is in the .pdb file",
+            );
+        }
+        Ok(())
+    }
+
+    /// Reads the version section back from the patched binary, and checks that it holds
+    /// the generated data, see `verify()`.
+    fn verify_output(&self, path: &Path) -> Result<(), VerShimError> {
+        let data = read(path)?;
+        let buffer = section_data::find_buffer(&data, self.custom_section_name()).map_err(|e| {
+            VerShimError::InvalidBinary(format!(
+                "failed to read back the version section of {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        // Nothing was patched, since the section is missing (with a warning)
+        let Some(buffer) = buffer else {
+            return Ok(());
+        };
+
+        let format = SectionFormat::detect(&buffer)?;
+        let expected = self.section.expected_section_bytes(buffer.len(), format)?;
+        if let Some(offset) = buffer.iter().zip(&expected).position(|(a, b)| a != b) {
+            return Err(VerShimError::Check(format!(
+                "the version section of the patched {} doesn't hold the generated data \
+                 (first difference at byte {}), was another section written?",
+                self.bin_path.display(),
+                offset
+            )));
+        }
+        self.section.diagnostics.info(format!(
+            "verified the version section of {}",
+            path.display()
+        ));
+        Ok(())
+    }
+
+    /// Checks the segment alignment of the patched binary, see `check_page_alignment()`.
+    fn check_page_alignment_of(&self, path: &Path, page_size: u64) -> Result<(), VerShimError> {
+        let diag = &self.section.diagnostics;
+        let data = read(path)?;
+        if !elf_layout::is_elf(&data) {
+            diag.warning("check_page_alignment() only applies to ELF binaries");
+            return Ok(());
+        }
+        elf_layout::check_alignment(&data, page_size).map_err(|e| {
+            VerShimError::Check(format!(
+                "patched {} can't be loaded with {} byte pages: {}",
+                self.bin_path.display(),
+                page_size,
+                e
+            ))
+        })?;
+        diag.info(format!(
+            "segments of {} are aligned for {} byte pages",
+            path.display(),
+            page_size
+        ));
+        Ok(())
+    }
+
+    /// Patches the version section of a Mach-O binary, or of every architecture of a
+    /// universal binary, and re-signs it if it was signed.
+    fn patch_macho(&self, output_path: &Path) -> Result<(), VerShimError> {
+        let diag = self.section.diagnostics.clone();
+        let mut data = read(&self.bin_path)?;
+        let invalid = |e: String| {
+            VerShimError::InvalidBinary(format!(
+                "failed to read Mach-O binary {}: {}",
+                self.bin_path.display(),
+                e
+            ))
+        };
+        let ranges = macho::find_sections(&data, self.custom_section_name()).map_err(invalid)?;
+
+        let Some(size) = ranges.first().map(|range| range.len()) else {
+            diag.warning(format!(
+                "section '{}' not found in {}, copying without modification",
+                self.section_name(),
+                self.bin_path.display()
+            ));
+            return copy(&self.bin_path, output_path);
+        };
+        if ranges.iter().any(|range| range.len() != size) {
+            return Err(VerShimError::InvalidBinary(format!(
+                "architectures of {} have '{}' sections of different sizes, \
+                 were they built with different VER_SHIM_BUFFER_SIZE?",
+                self.bin_path.display(),
+                self.section_name()
+            )));
+        }
+
+        let format = SectionFormat::detect(&data[ranges[0].clone()])?;
+        let section_bytes = self.section.build_section_bytes_for(size, format)?;
+        for range in &ranges {
+            data[range.clone()].copy_from_slice(&section_bytes);
+        }
+
+        write(output_path, &data)?;
+        diag.info(format!(
+            "wrote patched Mach-O binary to {} ({} architecture(s) updated)",
+            output_path.display(),
+            ranges.len()
+        ));
+
+        let signed = macho::has_code_signature(&data).map_err(invalid)?;
+        if signed || self.resign_adhoc {
+            match macho::adhoc_sign(output_path) {
+                Ok(true) => diag.info(format!("re-signed {} (ad-hoc)", output_path.display())),
+                Ok(false) if self.resign_adhoc => {
+                    return Err(VerShimError::Config(
+                        "resign_adhoc() was used, but codesign is not available".into(),
+                    ));
+                }
+                Ok(false) => diag.warning(format!(
+                    "patching invalidated the code signature of {}, and codesign is not available. \
+                     Re-sign the patched binary (e.g. `codesign --force --sign -`) before running \
+                     it on macOS.",
+                    self.bin_path.display()
+                )),
+                Err(e) => {
+                    return Err(VerShimError::io(
+                        format!("failed to re-sign {}", output_path.display()),
+                        e,
+                    ));
+                }
             }
         }
+
+        if self.debug_path.is_some() {
+            diag.warning(
+                "with_split_debuginfo() is not supported for Mach-O binaries, use dsymutil instead",
+            );
+        }
+        Ok(())
     }
 
     /// Writes the patched binary to the target profile directory (e.g., `target/debug/`).
@@ -151,7 +1127,317 @@ impl UpdateSectionCommand {
     /// - <https://github.com/rust-lang/cargo/issues/9661#issuecomment-2159267601>
     /// - <https://github.com/rust-lang/cargo/issues/13663>
     pub fn write_to_target_profile_dir(self) {
-        let target_dir = cargo_helpers::target_profile_dir();
-        self.write_to(target_dir);
+        let diag = self.section.diagnostics.clone();
+        self.try_write_to_target_profile_dir()
+            .unwrap_or_else(|e| diag.fail(e));
+    }
+
+    /// Like `write_to_target_profile_dir()`, but returns an error instead of panicking.
+    ///
+    /// Returns the path to the written binary.
+    pub fn try_write_to_target_profile_dir(self) -> Result<PathBuf, VerShimError> {
+        let target_dir = cargo_helpers::target_profile_dir()?;
+        self.try_write_to(target_dir)
+    }
+}
+
+fn llvm_tools_missing(e: String) -> VerShimError {
+    VerShimError::Objcopy(format!(
+        "could not find LLVM tools or GNU binutils: {}\n\
+         Please install llvm-tools: rustup component add llvm-tools",
+        e
+    ))
+}
+
+/// Reads a file, with the path in the error.
+fn read(path: &Path) -> Result<Vec<u8>, VerShimError> {
+    fs::read(path).map_err(|e| VerShimError::io(format!("failed to read {}", path.display()), e))
+}
+
+/// Writes a file, with the path in the error.
+fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), VerShimError> {
+    fs::write(path, contents)
+        .map_err(|e| VerShimError::io(format!("failed to write {}", path.display()), e))
+}
+
+/// Copies the input binary to the output without modification.
+fn copy(from: &Path, to: &Path) -> Result<(), VerShimError> {
+    fs::copy(from, to).map_err(|e| {
+        VerShimError::io(
+            format!("failed to copy {} to {}", from.display(), to.display()),
+            e,
+        )
+    })?;
+    Ok(())
+}
+
+/// Sets the permissions of the output to `mode`, or copies them from the input.
+///
+/// `fs::copy` and llvm-objcopy already copy them, but `fs::write` creates a new file
+/// with default permissions, which would lose the executable bit.
+fn set_permissions(from: &Path, to: &Path, mode: Option<u32>) -> Result<(), VerShimError> {
+    let Some(mode) = mode else {
+        return copy_permissions(from, to);
+    };
+    #[cfg(unix)]
+    let permissions = {
+        use std::os::unix::fs::PermissionsExt;
+        fs::Permissions::from_mode(mode)
+    };
+    #[cfg(not(unix))]
+    let permissions = {
+        let mut permissions = fs::metadata(to)
+            .map_err(|e| VerShimError::io(format!("failed to stat {}", to.display()), e))?
+            .permissions();
+        permissions.set_readonly(mode & 0o200 == 0);
+        permissions
+    };
+    fs::set_permissions(to, permissions)
+        .map_err(|e| VerShimError::io(format!("failed to set permissions on {}", to.display()), e))
+}
+
+/// Copies the permissions of `from` to `to`.
+fn copy_permissions(from: &Path, to: &Path) -> Result<(), VerShimError> {
+    let permissions = fs::metadata(from)
+        .map_err(|e| VerShimError::io(format!("failed to stat {}", from.display()), e))?
+        .permissions();
+    fs::set_permissions(to, permissions)
+        .map_err(|e| VerShimError::io(format!("failed to set permissions on {}", to.display()), e))
+}
+
+/// Copies the extended attributes of `from` to `to`, e.g. an SELinux label or the macOS
+/// quarantine flag.
+///
+/// Attributes which can't be set, e.g. because the file system doesn't support them or
+/// setting them needs privileges, are skipped with a warning.
+#[cfg(unix)]
+fn copy_xattrs(from: &Path, to: &Path, diag: &Diagnostics) -> Result<(), VerShimError> {
+    let names = match xattr::list(from) {
+        Ok(names) => names,
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => return Ok(()),
+        Err(e) => {
+            return Err(VerShimError::io(
+                format!("failed to list extended attributes of {}", from.display()),
+                e,
+            ));
+        }
+    };
+    for name in names {
+        let value = xattr::get(from, &name).map_err(|e| {
+            VerShimError::io(
+                format!(
+                    "failed to read extended attribute {} of {}",
+                    name.to_string_lossy(),
+                    from.display()
+                ),
+                e,
+            )
+        })?;
+        let Some(value) = value else {
+            continue;
+        };
+        if let Err(e) = xattr::set(to, &name, &value) {
+            diag.warning(format!(
+                "failed to copy extended attribute {} to {}: {}",
+                name.to_string_lossy(),
+                to.display(),
+                e
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Extended attributes are only copied on unix.
+#[cfg(not(unix))]
+fn copy_xattrs(_from: &Path, _to: &Path, _diag: &Diagnostics) -> Result<(), VerShimError> {
+    Ok(())
+}
+
+/// Recomputes the checksum of the output binary, if it is a PE image.
+fn update_pe_checksum(path: &Path, diag: &Diagnostics) -> Result<(), VerShimError> {
+    pe::update_checksum(path, diag).map_err(|e| {
+        VerShimError::io(
+            format!("failed to update PE checksum of {}", path.display()),
+            e,
+        )
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LinkSection;
+    use crate::fixtures::{self, PE_CHECKSUM_OFFSET};
+    use crate::section_data::SectionData;
+
+    /// Creates an input and an output file in a fresh temporary directory.
+    fn files(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "ver_shim_permissions_{}_{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (from, to) = (dir.join("input"), dir.join("output"));
+        fs::write(&from, b"input").unwrap();
+        fs::write(&to, b"output").unwrap();
+        (from, to)
+    }
+
+    fn cleanup(from: &Path) {
+        let dir = from.parent().unwrap();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let mut permissions = fs::metadata(&path).unwrap().permissions();
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            fs::set_permissions(&path, permissions).unwrap();
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    fn mode(path: &Path) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).unwrap().permissions().mode() & 0o7777
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permissions_are_copied() {
+        use std::os::unix::fs::PermissionsExt;
+        let (from, to) = files("copied");
+        fs::set_permissions(&from, fs::Permissions::from_mode(0o751)).unwrap();
+        set_permissions(&from, &to, None).unwrap();
+        assert_eq!(mode(&to), 0o751);
+        cleanup(&from);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn with_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let (from, to) = files("with_mode");
+        fs::set_permissions(&from, fs::Permissions::from_mode(0o600)).unwrap();
+        set_permissions(&from, &to, Some(0o755)).unwrap();
+        assert_eq!(mode(&to), 0o755);
+        set_permissions(&from, &to, Some(0o500)).unwrap();
+        assert_eq!(mode(&to), 0o500);
+        cleanup(&from);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn xattrs_are_copied() {
+        let (from, to) = files("xattrs");
+        // Not every file system supports user attributes, e.g. tmpfs on older kernels
+        if xattr::set(&from, "user.ver_shim_test", b"value").is_err() {
+            cleanup(&from);
+            return;
+        }
+        copy_xattrs(&from, &to, &Diagnostics::default()).unwrap();
+        assert_eq!(
+            xattr::get(&to, "user.ver_shim_test").unwrap().as_deref(),
+            Some(&b"value"[..])
+        );
+        cleanup(&from);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn read_only_is_copied() {
+        let (from, to) = files("copied");
+        let mut permissions = fs::metadata(&from).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&from, permissions).unwrap();
+        set_permissions(&from, &to, None).unwrap();
+        assert!(fs::metadata(&to).unwrap().permissions().readonly());
+        cleanup(&from);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn with_mode() {
+        let (from, to) = files("with_mode");
+        set_permissions(&from, &to, Some(0o444)).unwrap();
+        assert!(fs::metadata(&to).unwrap().permissions().readonly());
+        set_permissions(&from, &to, Some(0o755)).unwrap();
+        assert!(!fs::metadata(&to).unwrap().permissions().readonly());
+        cleanup(&from);
+    }
+
+    /// Patches `binary` with a self hash, returning the output and the recorded hash.
+    fn patch_with_self_hash(name: &str, binary: &[u8]) -> (Vec<u8>, String) {
+        let dir = fixtures::temp_dir(name);
+        let (input, output) = (dir.join("app"), dir.join("app_patched"));
+        fs::write(&input, binary).unwrap();
+        LinkSection::new()
+            .with_custom("hashed")
+            .patch_into(&input)
+            .preserve_layout()
+            .with_self_hash()
+            .try_write_to(&output)
+            .unwrap();
+        let patched = fs::read(&output).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+
+        let buffer = section_data::find_buffer(&patched, None).unwrap().unwrap();
+        let section = SectionData::parse(&buffer).unwrap();
+        assert_eq!(section.get(Member::Custom), Some("hashed"));
+        let hash = section.get(Member::SelfHash).unwrap().to_string();
+        (patched, hash)
+    }
+
+    /// Hashes an image the way `ver_shim::verify_self_hash()` does.
+    fn self_hash(image: &[u8]) -> String {
+        let buffer = section_data::find_buffer(image, None).unwrap().unwrap();
+        let range = ver_shim::find_self_hash_buffer(image, &buffer).unwrap();
+        let mut masked = image.to_vec();
+        ver_shim::mask_self_hash_image(&mut masked, range);
+        Sha256::digest(&masked)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    #[test]
+    fn self_hash_of_elf() {
+        let binary = fixtures::elf_object(&[
+            (".text", &[0xc3; 16]),
+            (SECTION_NAME, &fixtures::unpatched_section()),
+        ]);
+        let (patched, hash) = patch_with_self_hash("self_hash_elf", &binary);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(self_hash(&patched), hash);
+
+        // Any change outside the version buffer is detected
+        let mut tampered = patched.clone();
+        tampered[0x40] ^= 1;
+        assert_ne!(self_hash(&tampered), hash);
+    }
+
+    #[test]
+    fn self_hash_of_pe() {
+        let binary = fixtures::pe_image(&fixtures::unpatched_section());
+        let (patched, hash) = patch_with_self_hash("self_hash_pe", &binary);
+        // The checksum was recomputed after the hash was recorded, so it must be masked
+        let checksum = &patched[PE_CHECKSUM_OFFSET..PE_CHECKSUM_OFFSET + 4];
+        assert_ne!(
+            checksum,
+            &binary[PE_CHECKSUM_OFFSET..PE_CHECKSUM_OFFSET + 4]
+        );
+        assert_eq!(self_hash(&patched), hash);
+
+        let mut unmasked = patched.clone();
+        let range = native::find_section(&patched, None).unwrap().unwrap();
+        unmasked[range].fill(0);
+        let unmasked: String = Sha256::digest(&unmasked)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        assert_ne!(unmasked, hash);
     }
 }
@@ -0,0 +1,78 @@
+//! Detecting the version control system in use, see `LinkSection::with_vcs_auto()`.
+
+use std::path::{Path, PathBuf};
+
+use crate::cargo_helpers::cargo_rerun_if;
+use crate::git_provider::{GitCli, GitProvider};
+use crate::hg_provider::HgProvider;
+use crate::jj_provider::JjProvider;
+
+/// A version control system which version data can be collected from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Vcs {
+    Git,
+    Jujutsu,
+    Mercurial,
+}
+
+impl Vcs {
+    /// The name of the version control system, for diagnostics.
+    pub fn name(self) -> &'static str {
+        match self {
+            Vcs::Git => "git",
+            Vcs::Jujutsu => "jj",
+            Vcs::Mercurial => "hg",
+        }
+    }
+
+    /// Creates the provider for this version control system, using its command line tool.
+    pub fn provider(self) -> Box<dyn GitProvider + Send + Sync> {
+        match self {
+            Vcs::Git => Box::new(GitCli),
+            Vcs::Jujutsu => Box::new(JjProvider),
+            Vcs::Mercurial => Box::new(HgProvider),
+        }
+    }
+}
+
+/// Finds the version control system of the current directory, and the root of the
+/// repository, by walking up to the closest `.git`, `.jj` or `.hg`.
+///
+/// `.git` wins if a directory has several, e.g. in a colocated jj repository, since
+/// `git` itself works there.
+pub fn detect() -> Option<(Vcs, PathBuf)> {
+    let mut dir = std::env::current_dir().ok()?;
+    loop {
+        for (marker, vcs) in [
+            (".git", Vcs::Git),
+            (".jj", Vcs::Jujutsu),
+            (".hg", Vcs::Mercurial),
+        ] {
+            if dir.join(marker).exists() {
+                return Some((vcs, dir));
+            }
+        }
+        if !dir.pop() {
+            return None;
+        }
+    }
+}
+
+/// Emits cargo rerun-if-changed directives for the state of a jj or hg repository.
+///
+/// For git, see `emit_git_rerun_if_changed()`.
+pub fn emit_rerun_if_changed(vcs: Vcs, root: &Path) {
+    let watched: &[&str] = match vcs {
+        Vcs::Git => return,
+        // Every jj command that changes the repository creates a new operation
+        Vcs::Jujutsu => &[".jj/repo/op_heads/heads"],
+        // The dirstate records the working directory parent, bookmarks the active bookmark
+        Vcs::Mercurial => &[".hg/dirstate", ".hg/bookmarks", ".hg/bookmarks.current"],
+    };
+    for path in watched {
+        let path = root.join(path);
+        if path.exists() {
+            cargo_rerun_if(&format!("changed={}", path.display()));
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Windows VERSIONINFO resources, shown as the file properties in Explorer.
+//!
+//! The resource is written as a compiled `.res` file, which the MSVC linker and lld-link
+//! accept directly, so no resource compiler is needed. See the `VS_VERSIONINFO` docs:
+//! <https://learn.microsoft.com/en-us/windows/win32/menurc/vs-versioninfo>
+
+use std::path::Path;
+
+use ver_shim::Member;
+
+use crate::cargo_helpers;
+use crate::diagnostics::Diagnostics;
+use crate::error::VerShimError;
+use crate::output;
+
+/// `RT_VERSION`, the resource type of a VERSIONINFO resource.
+const RT_VERSION: u16 = 16;
+/// `VS_VERSION_INFO`, the resource id of a VERSIONINFO resource.
+const VS_VERSION_INFO: u16 = 1;
+/// US English, with the Unicode code page.
+const LANGUAGE: u16 = 0x0409;
+const CODE_PAGE: u16 = 0x04b0;
+
+/// Writes a VERSIONINFO resource to `OUT_DIR` and links it into the binaries of the crate.
+///
+/// Only does anything when building for Windows with the MSVC linker, otherwise a
+/// warning is emitted.
+pub(crate) fn emit(
+    member_data: &[Option<String>; Member::COUNT],
+    diag: &Diagnostics,
+) -> Result<(), VerShimError> {
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows") {
+        return Ok(());
+    }
+    if std::env::var("CARGO_CFG_TARGET_ENV").as_deref() != Ok("msvc") {
+        diag.warning(
+            "with_windows_versioninfo() is only supported with the MSVC linker, \
+             not emitting a VERSIONINFO resource",
+        );
+        return Ok(());
+    }
+
+    let path = cargo_helpers::out_dir()?.join("ver_shim_versioninfo.res");
+    write_res(&path, &version_resource(member_data))?;
+    cargo_helpers::cargo_rustc_link_arg_bins(&path.display().to_string());
+    diag.info(format!("wrote VERSIONINFO resource to {}", path.display()));
+    Ok(())
+}
+
+/// Writes a `.res` file containing a single VERSIONINFO resource.
+fn write_res(path: &Path, resource: &[u8]) -> Result<(), VerShimError> {
+    // A .res file starts with an empty entry, which marks it as a 32-bit resource file
+    let mut res = resource_header(0, 0, 0, 0, 0);
+    res.extend(resource_header(
+        resource.len() as u32,
+        RT_VERSION,
+        VS_VERSION_INFO,
+        0x0030,
+        LANGUAGE,
+    ));
+    res.extend_from_slice(resource);
+    pad(&mut res);
+    output::write(path, res, true)
+        .map_err(|e| VerShimError::io(format!("failed to write {}", path.display()), e))
+}
+
+/// Builds the header of a resource entry in a `.res` file, with a numeric type and name.
+fn resource_header(data_size: u32, kind: u16, name: u16, flags: u16, language: u16) -> Vec<u8> {
+    let mut header = Vec::with_capacity(32);
+    header.extend(data_size.to_le_bytes());
+    header.extend(32u32.to_le_bytes());
+    for id in [kind, name] {
+        header.extend(0xffffu16.to_le_bytes());
+        header.extend(id.to_le_bytes());
+    }
+    header.extend(0u32.to_le_bytes()); // DataVersion
+    header.extend(flags.to_le_bytes());
+    header.extend(language.to_le_bytes());
+    header.extend(0u32.to_le_bytes()); // Version
+    header.extend(0u32.to_le_bytes()); // Characteristics
+    header
+}
+
+/// Builds the `VS_VERSIONINFO` structure from the collected data and the cargo package.
+fn version_resource(member_data: &[Option<String>; Member::COUNT]) -> Vec<u8> {
+    let get = |member: Member| member_data[member as usize].as_deref();
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+    let pkg_name = env("CARGO_PKG_NAME").unwrap_or_default();
+    let pkg_version = env("CARGO_PKG_VERSION").unwrap_or_default();
+    let file_version = get(Member::GitDescribe).map_or_else(|| pkg_version.clone(), String::from);
+
+    let mut comments = Vec::new();
+    if let Some(sha) = get(Member::GitSha) {
+        comments.push(format!("git {}", sha));
+    }
+    if let Some(branch) = get(Member::GitBranch) {
+        comments.push(format!("branch {}", branch));
+    }
+    if let Some(date) = get(Member::BuildDate) {
+        comments.push(format!("built {}", date));
+    }
+
+    let strings = [
+        (
+            "FileDescription",
+            env("CARGO_PKG_DESCRIPTION").unwrap_or_else(|| pkg_name.clone()),
+        ),
+        ("FileVersion", file_version),
+        ("InternalName", pkg_name.clone()),
+        ("ProductName", pkg_name),
+        ("ProductVersion", pkg_version),
+        ("Comments", comments.join(", ")),
+    ];
+    let strings: Vec<Vec<u8>> = strings
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| {
+            let text = utf16z(value);
+            block(key, &text, (text.len() / 2) as u16, true, &[])
+        })
+        .collect();
+    let table_key = format!("{:04X}{:04X}", LANGUAGE, CODE_PAGE);
+    let string_table = block(&table_key, &[], 0, true, &strings);
+    let string_file_info = block("StringFileInfo", &[], 0, true, &[string_table]);
+
+    let mut translation = LANGUAGE.to_le_bytes().to_vec();
+    translation.extend(CODE_PAGE.to_le_bytes());
+    let var = block("Translation", &translation, 4, false, &[]);
+    let var_file_info = block("VarFileInfo", &[], 0, true, &[var]);
+
+    let fixed = fixed_file_info(get(Member::GitDirty) == Some("true"));
+    block(
+        "VS_VERSION_INFO",
+        &fixed,
+        fixed.len() as u16,
+        false,
+        &[string_file_info, var_file_info],
+    )
+}
+
+/// Builds the `VS_FIXEDFILEINFO` structure, with the version numbers of the cargo package.
+fn fixed_file_info(dirty: bool) -> Vec<u8> {
+    let part = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .and_then(|value| value.parse::<u16>().ok())
+            .unwrap_or(0) as u32
+    };
+    let version_ms = (part("CARGO_PKG_VERSION_MAJOR") << 16) | part("CARGO_PKG_VERSION_MINOR");
+    let version_ls = part("CARGO_PKG_VERSION_PATCH") << 16;
+
+    let mut flags = 0u32;
+    if std::env::var("PROFILE").as_deref() == Ok("debug") {
+        flags |= 0x01; // VS_FF_DEBUG
+    }
+    if std::env::var("CARGO_PKG_VERSION_PRE").is_ok_and(|pre| !pre.is_empty()) {
+        flags |= 0x02; // VS_FF_PRERELEASE
+    }
+    if dirty {
+        flags |= 0x08; // VS_FF_PRIVATEBUILD
+    }
+
+    let fields = [
+        0xfeef_04bd, // dwSignature
+        0x0001_0000, // dwStrucVersion
+        version_ms,  // dwFileVersionMS
+        version_ls,  // dwFileVersionLS
+        version_ms,  // dwProductVersionMS
+        version_ls,  // dwProductVersionLS
+        0x3f,        // dwFileFlagsMask
+        flags,       // dwFileFlags
+        0x0004_0004, // dwFileOS: VOS_NT_WINDOWS32
+        0x1,         // dwFileType: VFT_APP
+        0,           // dwFileSubtype
+        0,           // dwFileDateMS
+        0,           // dwFileDateLS
+    ];
+    fields
+        .iter()
+        .flat_map(|field| field.to_le_bytes())
+        .collect()
+}
+
+/// Builds a block of the version resource: a header with the key, the value, and the
+/// child blocks, each aligned to 4 bytes.
+///
+/// `value_length` is in words for text values, and in bytes otherwise.
+fn block(key: &str, value: &[u8], value_length: u16, text: bool, children: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = vec![0; 4];
+    out.extend(u16::from(text).to_le_bytes());
+    out.extend(utf16z(key));
+    pad(&mut out);
+    out.extend_from_slice(value);
+    for child in children {
+        pad(&mut out);
+        out.extend_from_slice(child);
+    }
+    let length = out.len() as u16;
+    out[0..2].copy_from_slice(&length.to_le_bytes());
+    out[2..4].copy_from_slice(&value_length.to_le_bytes());
+    out
+}
+
+/// Encodes a string as null-terminated UTF-16LE.
+fn utf16z(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .chain([0])
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+/// Pads `out` with zeros to a multiple of 4 bytes.
+fn pad(out: &mut Vec<u8>) {
+    out.resize(out.len().next_multiple_of(4), 0);
+}
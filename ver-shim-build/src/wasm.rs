@@ -0,0 +1,258 @@
+//! Patching WebAssembly modules.
+//!
+//! On wasm targets, `ver-shim` always places the buffer behind the locator prefix in a
+//! data segment, since a custom section can't be read by the module itself. The bytes of
+//! active data segments are stored as is in the `.wasm` file, so the buffer is patched in
+//! place there. A copy of the patched buffer is also stored in a custom section named
+//! `WASM_SECTION_NAME`, which the host can read without instantiating the module, e.g.
+//! with `WebAssembly.Module.customSections()` in JavaScript.
+//!
+//! See: https://webassembly.github.io/spec/core/binary/modules.html
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::Path;
+
+/// Name of the custom section holding a copy of the version data.
+pub const WASM_SECTION_NAME: &str = "ver_shim_data";
+
+const MAGIC: &[u8; 4] = b"\0asm";
+const CUSTOM_SECTION: u8 = 0;
+
+/// Returns true if the data is a WebAssembly module.
+pub fn is_wasm(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Returns true if the file at `path` is a WebAssembly module.
+pub fn is_wasm_file(path: &Path) -> io::Result<bool> {
+    let mut magic = [0u8; MAGIC.len()];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(is_wasm(&magic)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// A section of a module.
+struct Section {
+    id: u8,
+    /// The whole section, including the id and size.
+    range: Range<usize>,
+    /// The contents of the section, after the size.
+    contents: Range<usize>,
+}
+
+/// Reads an unsigned LEB128 number at `*pos`, advancing it.
+fn read_leb128(data: &[u8], pos: &mut usize) -> Result<u32, String> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *data
+            .get(*pos)
+            .ok_or_else(|| "unexpected end of module".to_string())?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("invalid LEB128 number".to_string())
+}
+
+/// Appends `value` as an unsigned LEB128 number.
+fn write_leb128(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Lists the sections of a module.
+fn sections(data: &[u8]) -> Result<Vec<Section>, String> {
+    if !is_wasm(data) || data.len() < 8 {
+        return Err("not a WebAssembly module".to_string());
+    }
+    let mut sections = Vec::new();
+    let mut pos = 8;
+    while pos < data.len() {
+        let start = pos;
+        let id = data[pos];
+        pos += 1;
+        let size = read_leb128(data, &mut pos)? as usize;
+        let end = pos
+            .checked_add(size)
+            .filter(|&end| end <= data.len())
+            .ok_or_else(|| format!("section at offset {} extends past the end", start))?;
+        sections.push(Section {
+            id,
+            range: start..end,
+            contents: pos..end,
+        });
+        pos = end;
+    }
+    Ok(sections)
+}
+
+/// Gets the name of a custom section, and the offset of its payload.
+fn custom_section_name<'a>(data: &'a [u8], section: &Section) -> Option<(&'a [u8], usize)> {
+    if section.id != CUSTOM_SECTION {
+        return None;
+    }
+    let mut pos = section.contents.start;
+    let len = read_leb128(data, &mut pos).ok()? as usize;
+    let name = data.get(pos..pos.checked_add(len)?)?;
+    Some((name, pos + len))
+}
+
+/// Gets the payload of the custom section with the given name, if there is one.
+pub fn custom_section<'a>(data: &'a [u8], name: &str) -> Result<Option<&'a [u8]>, String> {
+    for section in sections(data)? {
+        if let Some((section_name, payload)) = custom_section_name(data, &section)
+            && section_name == name.as_bytes()
+        {
+            return Ok(Some(&data[payload..section.contents.end]));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the module with the custom section `name` set to `payload`, replacing an
+/// existing one or appending it at the end.
+pub fn set_custom_section(data: &[u8], name: &str, payload: &[u8]) -> Result<Vec<u8>, String> {
+    let mut contents = Vec::with_capacity(5 + name.len() + payload.len());
+    write_leb128(&mut contents, name.len() as u32);
+    contents.extend_from_slice(name.as_bytes());
+    contents.extend_from_slice(payload);
+    let mut section = vec![CUSTOM_SECTION];
+    write_leb128(&mut section, contents.len() as u32);
+    section.extend(contents);
+
+    let existing = sections(data)?.into_iter().find(|s| {
+        custom_section_name(data, s)
+            .is_some_and(|(section_name, _)| section_name == name.as_bytes())
+    });
+    let mut out = Vec::with_capacity(data.len() + section.len());
+    match existing {
+        Some(existing) => {
+            out.extend_from_slice(&data[..existing.range.start]);
+            out.extend(section);
+            out.extend_from_slice(&data[existing.range.end..]);
+        }
+        None => {
+            out.extend_from_slice(data);
+            out.extend(section);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, SECTION_SIZE};
+    use crate::locator;
+    use crate::{LinkSection, Member, SectionData};
+    use std::fs;
+
+    #[test]
+    fn leb128() {
+        for value in [0, 1, 127, 128, 300, 624_485, u32::MAX] {
+            let mut out = Vec::new();
+            write_leb128(&mut out, value);
+            let mut pos = 0;
+            assert_eq!(read_leb128(&out, &mut pos), Ok(value));
+            assert_eq!(pos, out.len());
+        }
+        assert!(read_leb128(&[0x80, 0x80], &mut 0).is_err());
+        assert!(read_leb128(&[0xff; 6], &mut 0).is_err());
+    }
+
+    #[test]
+    fn custom_sections() {
+        let module = fixtures::wasm_module();
+        assert_eq!(
+            custom_section(&module, "producers").unwrap(),
+            Some(&b"\0rustc"[..])
+        );
+        assert_eq!(custom_section(&module, WASM_SECTION_NAME).unwrap(), None);
+
+        // A new section is appended, and an existing one replaced in place
+        let added = set_custom_section(&module, WASM_SECTION_NAME, &[1; 200]).unwrap();
+        assert!(added.starts_with(&module));
+        let replaced = set_custom_section(&added, WASM_SECTION_NAME, b"short").unwrap();
+        assert_eq!(
+            custom_section(&replaced, WASM_SECTION_NAME).unwrap(),
+            Some(&b"short"[..])
+        );
+        assert_eq!(sections(&replaced).unwrap().len(), 3);
+        let replaced = set_custom_section(&replaced, "producers", b"").unwrap();
+        assert_eq!(
+            custom_section(&replaced, "producers").unwrap(),
+            Some(&b""[..])
+        );
+        let ids: Vec<u8> = sections(&replaced).unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(ids, [CUSTOM_SECTION, 11, CUSTOM_SECTION]);
+    }
+
+    #[test]
+    fn malformed() {
+        let module = fixtures::wasm_module();
+        assert!(sections(&module[..4]).is_err());
+        let Err(err) = sections(&module[..module.len() - 1]) else {
+            panic!("truncated module was accepted");
+        };
+        assert!(err.contains("extends past the end"), "{}", err);
+        assert!(set_custom_section(b"\x7fELF", WASM_SECTION_NAME, b"").is_err());
+    }
+
+    #[test]
+    fn patch_module() {
+        let dir = fixtures::temp_dir("wasm");
+        let input = dir.join("app.wasm");
+        let module = fixtures::wasm_module();
+        fs::write(&input, &module).unwrap();
+
+        let patch = |input: &Path, custom: &str| {
+            let output = dir.join(format!("{}.wasm", custom));
+            LinkSection::new()
+                .with_custom(custom)
+                .patch_into(input)
+                .try_write_to(&output)
+                .unwrap();
+            fs::read(&output).unwrap()
+        };
+        let patched = patch(&input, "first");
+        let location = locator::find(&patched).unwrap().unwrap();
+        assert_eq!(location.size, SECTION_SIZE);
+        let buffer = &patched[location.offset..location.offset + location.size];
+        assert_eq!(
+            custom_section(&patched, WASM_SECTION_NAME).unwrap(),
+            Some(buffer)
+        );
+        let section = SectionData::parse(buffer).unwrap();
+        assert_eq!(section.get(Member::Custom), Some("first"));
+        // The data segment keeps its place, and the custom section is appended
+        assert_eq!(patched[..location.offset], module[..location.offset]);
+        assert_eq!(sections(&patched).unwrap().len(), 3);
+
+        // Patching again replaces both copies
+        fs::write(&input, &patched).unwrap();
+        let repatched = patch(&input, "second");
+        assert_eq!(sections(&repatched).unwrap().len(), 3);
+        let output = dir.join("second.wasm");
+        let section = SectionData::from_binary(&output).unwrap();
+        assert_eq!(section.get(Member::Custom), Some("second"));
+        let location = locator::find(&repatched).unwrap().unwrap();
+        assert_eq!(
+            custom_section(&repatched, WASM_SECTION_NAME).unwrap(),
+            Some(&repatched[location.offset..location.offset + location.size])
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
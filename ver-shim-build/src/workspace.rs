@@ -0,0 +1,178 @@
+//! Workspace member names and versions, and the hash of `Cargo.lock`, from `cargo metadata`,
+//! and the dependency graph of a package, from `cargo tree`.
+//!
+//! This doesn't use a JSON parser. With `--no-deps`, the packages of `cargo metadata` are
+//! exactly the workspace members, and each package object starts with its name and
+//! version, neither of which can contain characters that need escaping.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use sha2::{Digest, Sha256};
+
+use crate::cargo_helpers::cargo_rerun_if;
+
+/// Gets the name and version of each member of the workspace of the current directory.
+///
+/// In a build script, this also asks cargo to rerun it when `Cargo.lock` changes, which
+/// happens whenever the version of a member changes.
+pub fn members() -> Result<BTreeMap<String, String>, String> {
+    let metadata = metadata()?;
+    let members = parse_members(&metadata);
+    if members.is_empty() {
+        return Err("no packages found in cargo metadata output".to_string());
+    }
+    cargo_rerun_if(&format!("changed={}", lockfile_path(&metadata)?.display()));
+    Ok(members)
+}
+
+/// Gets the SHA-256 of the `Cargo.lock` of the workspace of the current directory, as hex.
+///
+/// In a build script, this also asks cargo to rerun it when `Cargo.lock` changes.
+pub fn lockfile_hash() -> Result<String, String> {
+    let lockfile = lockfile_path(&metadata()?)?;
+    let data =
+        fs::read(&lockfile).map_err(|e| format!("failed to read {}: {}", lockfile.display(), e))?;
+    cargo_rerun_if(&format!("changed={}", lockfile.display()));
+    Ok(Sha256::digest(&data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Gets the names of the packages in the dependency graph of `package`, including itself.
+///
+/// Build dependencies are included, so that an application which is an artifact
+/// dependency of the package is too, as are the dependencies of every target platform.
+/// In a build script, this also asks cargo to rerun it when `Cargo.lock` changes.
+pub fn dependencies(package: &str) -> Result<BTreeSet<String>, String> {
+    let tree = cargo(&[
+        "tree",
+        "--package",
+        package,
+        "--edges",
+        "normal,build",
+        "--target",
+        "all",
+        "--prefix",
+        "none",
+        "--format",
+        "{p}",
+    ])?;
+    let packages = parse_tree(&tree);
+    if packages.is_empty() {
+        return Err(format!(
+            "no packages found in cargo tree output for {}",
+            package
+        ));
+    }
+    cargo_rerun_if(&format!(
+        "changed={}",
+        lockfile_path(&metadata()?)?.display()
+    ));
+    Ok(packages)
+}
+
+/// Runs `cargo metadata --no-deps` in the current directory, and returns its output.
+fn metadata() -> Result<String, String> {
+    cargo(&["metadata", "--no-deps", "--format-version", "1"])
+}
+
+/// Runs a cargo command in the current directory, and returns its output.
+fn cargo(args: &[&str]) -> Result<String, String> {
+    let output = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run cargo {}: {}", args[0], e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "cargo {} failed ({}): {}",
+            args[0],
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Gets the package names from `cargo tree --prefix none --format {p}` output.
+///
+/// Each line is a package, as `name v1.2.3` followed by its path or `(*)` if it was
+/// already listed, except for the `[build-dependencies]` headings.
+fn parse_tree(tree: &str) -> BTreeSet<String> {
+    tree.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|name| !name.starts_with('['))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Gets the path of `Cargo.lock`, in the workspace root.
+fn lockfile_path(metadata: &str) -> Result<PathBuf, String> {
+    let root = json_string_field(metadata, "workspace_root")
+        .ok_or_else(|| "workspace_root not found in cargo metadata output".to_string())?;
+    Ok(Path::new(&root).join("Cargo.lock"))
+}
+
+/// Finds the `{"name":"...","version":"..."` at the start of each package object.
+///
+/// Dependencies and targets have a name too, but it isn't followed by a version.
+fn parse_members(metadata: &str) -> BTreeMap<String, String> {
+    const NAME: &str = "{\"name\":\"";
+    const VERSION: &str = "\",\"version\":\"";
+    let mut members = BTreeMap::new();
+    let mut rest = metadata;
+    while let Some(start) = rest.find(NAME) {
+        rest = &rest[start + NAME.len()..];
+        let Some((name, after_name)) = rest.split_once('"') else {
+            break;
+        };
+        if let Some(after_version) = rest[name.len()..].strip_prefix(VERSION)
+            && let Some((version, _)) = after_version.split_once('"')
+        {
+            members.insert(name.to_string(), version.to_string());
+        }
+        rest = after_name;
+    }
+    members
+}
+
+/// Gets a string field from JSON output, without a JSON parser.
+///
+/// This only handles the escapes which can occur in a path, which is enough for the
+/// `workspace_root` of `cargo metadata`.
+fn json_string_field(json: &str, field: &str) -> Option<String> {
+    let start = json.find(&format!("\"{}\":\"", field))? + field.len() + 4;
+    let mut value = String::new();
+    let mut chars = json[start..].chars();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => value.push(chars.next()?),
+            c => value.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tree_packages() {
+        let tree = "my-app v0.1.0 (/src/my-app)\n\
+                    my-lib v0.2.0 (/src/my-lib)\n\
+                    serde v1.0.228\n\
+                    [build-dependencies]\n\
+                    ver-shim-build v0.2.1\n\
+                    serde v1.0.228 (*)\n\
+                    \n";
+        let packages: Vec<String> = parse_tree(tree).into_iter().collect();
+        assert_eq!(
+            packages,
+            ["my-app", "my-lib", "serde", "ver-shim-build"].map(String::from)
+        );
+    }
+}
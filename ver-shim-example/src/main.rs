@@ -1,4 +1,6 @@
 fn main() {
+    println!("{}", ver_shim::version_banner!());
+    println!();
     println!(
         "git sha:         {}",
         ver_shim::git_sha().unwrap_or("(not set)")
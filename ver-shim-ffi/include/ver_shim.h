@@ -0,0 +1,74 @@
+/*
+ * C API for reading version data injected with ver-shim.
+ *
+ * Link the static library of the ver-shim-ffi crate into a program to read its own
+ * version data with ver_shim_get(), or load the shared library to read that of any
+ * binary with ver_shim_read_file(). See ver-shim-ffi/src/lib.rs for details.
+ *
+ * Both functions copy the data into buf like snprintf(): at most len - 1 bytes,
+ * followed by a NUL byte. They return the length of the data, so a return value of len
+ * or more means it was truncated, VER_SHIM_ABSENT if the member is not present, or
+ * VER_SHIM_ERROR on errors.
+ */
+
+#ifndef VER_SHIM_H
+#define VER_SHIM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The member IDs, the index of each member in the section header, as in ver_shim::Member. */
+enum ver_shim_member {
+    VER_SHIM_GIT_SHA = 0,
+    VER_SHIM_GIT_DESCRIBE = 1,
+    VER_SHIM_GIT_BRANCH = 2,
+    VER_SHIM_GIT_COMMIT_TIMESTAMP = 3,
+    VER_SHIM_GIT_COMMIT_DATE = 4,
+    VER_SHIM_GIT_COMMIT_MSG = 5,
+    VER_SHIM_BUILD_TIMESTAMP = 6,
+    VER_SHIM_BUILD_DATE = 7,
+    VER_SHIM_CUSTOM = 8,
+    VER_SHIM_LICENSE_INFO = 9,
+    VER_SHIM_CRATE_ENTRIES = 10,
+    VER_SHIM_GIT_COMMIT_AUTHOR = 11,
+    VER_SHIM_GIT_COMMITTER = 12,
+    VER_SHIM_CUSTOM_KV = 13,
+    VER_SHIM_PKG_VERSION = 14,
+    VER_SHIM_PKG_NAME = 15,
+    VER_SHIM_TARGET_TRIPLE = 16,
+    VER_SHIM_GIT_TAG = 17,
+    VER_SHIM_GIT_DIRTY = 18,
+    VER_SHIM_WORKSPACE_MEMBERS = 19,
+    VER_SHIM_LOCKFILE_HASH = 20,
+    VER_SHIM_SBOM_DIGEST = 21,
+    VER_SHIM_SBOM = 22,
+    VER_SHIM_CI_INFO = 23,
+    VER_SHIM_BUILD_HOST = 24,
+    VER_SHIM_BUILD_PROFILE = 25,
+    VER_SHIM_GIT_SUBMODULES = 26,
+    VER_SHIM_GIT_REMOTE = 27,
+    VER_SHIM_SIGNATURE = 28,
+    VER_SHIM_SELF_HASH = 29,
+};
+
+/* Returned if the member is not present, or the member ID is unknown. */
+#define VER_SHIM_ABSENT (-1)
+
+/* Returned if the file can't be read or has no version section, or on other errors. */
+#define VER_SHIM_ERROR (-2)
+
+/* Copies the data of a member of the version data of this program into buf. */
+ptrdiff_t ver_shim_get(uint16_t member, char *buf, size_t len);
+
+/* Copies the data of a member of the version data of the binary at path into buf. */
+ptrdiff_t ver_shim_read_file(const char *path, uint16_t member, char *buf, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* VER_SHIM_H */
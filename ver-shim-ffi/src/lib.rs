@@ -0,0 +1,171 @@
+//! C API for reading version data injected with `ver-shim`, declared in
+//! `include/ver_shim.h`.
+//!
+//! This crate builds a static library and a shared library:
+//!
+//! - Linking the static library into a C or C++ program gives it a `.ver_shim_data`
+//!   section, which is patched like that of a Rust binary. `ver_shim_get()` reads it.
+//! - Loading the shared library, e.g. with `dlopen()` or Python's `ctypes`, gives tools
+//!   `ver_shim_read_file()`, which reads the version data of any binary, like
+//!   `ver-shim read`. Passing `/proc/self/exe` reads that of the running process.
+//!
+//! Members are identified by their index in the section header, which never changes, see
+//! `ver_shim::Member`. Map members, like custom key-value pairs, are returned in their
+//! encoded form: NUL-terminated strings, alternating between keys and values. Use the
+//! returned length rather than the terminating NUL byte to read them.
+
+use std::ffi::{CStr, c_char};
+use std::panic::{self, UnwindSafe};
+
+use ver_shim::Member;
+
+/// Returned if the member is not present, or the member ID is unknown.
+const ABSENT: isize = -1;
+
+/// Returned if the file can't be read or has no version section, or on any other error.
+const ERROR: isize = -2;
+
+/// Copies the data of a member of the version data of this program into `buf`.
+///
+/// Returns the length of the data, or `-1` if the member is absent. Like `snprintf()`,
+/// at most `len - 1` bytes are copied, followed by a NUL byte, so a return value of
+/// `len` or more means the data was truncated. `buf` may be null if `len` is 0, to get
+/// the length. Returns `-2` if the section is malformed.
+///
+/// # Safety
+///
+/// `buf` must be valid for writes of `len` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ver_shim_get(member: u16, buf: *mut c_char, len: usize) -> isize {
+    catch(|| {
+        let Some(&member) = Member::ALL.get(member as usize) else {
+            return ABSENT;
+        };
+        match ver_shim::iter().find(|(m, _)| *m == member) {
+            // SAFETY: forwarded from the caller
+            Some((_, data)) => unsafe { copy_out(data.as_bytes(), buf, len) },
+            None => ABSENT,
+        }
+    })
+}
+
+/// Copies the data of a member of the version data of the binary at `path` into `buf`.
+///
+/// The return value and `buf` are as for `ver_shim_get()`, and `-2` means that the file
+/// can't be read, has no version section, or the section is malformed.
+///
+/// This reads and parses the whole file for each call.
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string, and `buf` must be valid for writes of `len`
+/// bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ver_shim_read_file(
+    path: *const c_char,
+    member: u16,
+    buf: *mut c_char,
+    len: usize,
+) -> isize {
+    catch(|| {
+        if path.is_null() {
+            return ERROR;
+        }
+        // SAFETY: forwarded from the caller
+        let path = unsafe { CStr::from_ptr(path) };
+        let Ok(path) = path.to_str() else {
+            return ERROR;
+        };
+        let Ok(data) = ver_shim_build::read_from_file(path) else {
+            return ERROR;
+        };
+        let Some(&member) = Member::ALL.get(member as usize) else {
+            return ABSENT;
+        };
+        match data.get(member) {
+            // SAFETY: forwarded from the caller
+            Some(data) => unsafe { copy_out(data.as_bytes(), buf, len) },
+            None => ABSENT,
+        }
+    })
+}
+
+/// Copies `data` into `buf` like `snprintf()`, and returns its length.
+///
+/// # Safety
+///
+/// `buf` must be valid for writes of `len` bytes.
+unsafe fn copy_out(data: &[u8], buf: *mut c_char, len: usize) -> isize {
+    if len > 0 && !buf.is_null() {
+        let n = data.len().min(len - 1);
+        // SAFETY: n + 1 <= len, and the caller guarantees that buf is valid for len bytes
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), buf.cast::<u8>(), n);
+            *buf.add(n) = 0;
+        }
+    }
+    isize::try_from(data.len()).unwrap_or(ERROR)
+}
+
+/// Runs `f`, returning `-2` if it panics, since a panic can't unwind into C.
+fn catch(f: impl FnOnce() -> isize + UnwindSafe) -> isize {
+    panic::catch_unwind(f).unwrap_or(ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Copies `data` into a buffer of `len` bytes filled with `0xff`.
+    fn copy(data: &[u8], len: usize) -> (isize, Vec<u8>) {
+        let mut buf = vec![0xffu8; len];
+        // SAFETY: buf is valid for len bytes
+        let ret = unsafe { copy_out(data, buf.as_mut_ptr().cast(), len) };
+        (ret, buf)
+    }
+
+    #[test]
+    fn copy_out_fits() {
+        assert_eq!(copy(b"abc", 8), (3, b"abc\0\xff\xff\xff\xff".to_vec()));
+        assert_eq!(copy(b"abc", 4), (3, b"abc\0".to_vec()));
+        assert_eq!(copy(b"", 1), (0, b"\0".to_vec()));
+    }
+
+    #[test]
+    fn copy_out_truncates() {
+        // A return value of len or more means the data was truncated
+        assert_eq!(copy(b"abc", 3), (3, b"ab\0".to_vec()));
+        assert_eq!(copy(b"abcdef", 2), (6, b"a\0".to_vec()));
+        assert_eq!(copy(b"abc", 1), (3, b"\0".to_vec()));
+    }
+
+    #[test]
+    fn copy_out_length_only() {
+        assert_eq!(copy(b"abc", 0), (3, vec![]));
+        // SAFETY: nothing is written with a null buffer
+        assert_eq!(unsafe { copy_out(b"abc", std::ptr::null_mut(), 0) }, 3);
+        assert_eq!(unsafe { copy_out(b"abc", std::ptr::null_mut(), 16) }, 3);
+    }
+
+    #[test]
+    fn absent_and_errors() {
+        // The test binary is not patched, and member IDs past the known ones are absent
+        let mut buf = [0 as c_char; 16];
+        // SAFETY: buf is valid for its length
+        unsafe {
+            assert_eq!(
+                ver_shim_get(Member::GitSha as u16, buf.as_mut_ptr(), 16),
+                ABSENT
+            );
+            assert_eq!(ver_shim_get(u16::MAX, buf.as_mut_ptr(), 16), ABSENT);
+            assert_eq!(
+                ver_shim_read_file(std::ptr::null(), 0, buf.as_mut_ptr(), 16),
+                ERROR
+            );
+            assert_eq!(
+                ver_shim_read_file(c"/nonexistent/binary".as_ptr(), 0, buf.as_mut_ptr(), 16),
+                ERROR
+            );
+        }
+    }
+}
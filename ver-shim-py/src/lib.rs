@@ -0,0 +1,141 @@
+//! Python bindings for reading and patching version data, with the same format logic as
+//! `ver-shim-build` and the `ver-shim` CLI tool.
+//!
+//! Members are named like the keys of `ver-shim read --json`, e.g. `git_sha`.
+
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyUserWarning, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use ver_shim::Member;
+use ver_shim_build::{Diagnostic, Level, LinkSection};
+
+create_exception!(
+    ver_shim_py,
+    VerShimError,
+    PyException,
+    "Reading or patching a binary failed, e.g. because it has no version section."
+);
+
+/// Reads the version data of a binary, without running it.
+///
+/// Returns a dict from member names to values, e.g. `{"git_sha": "1a2b...", ...}`.
+/// Members which hold a map, like `custom_kv`, are dicts, and `git_dirty` is a bool.
+/// Absent members are left out.
+#[pyfunction]
+fn read_version_info(py: Python<'_>, path: PathBuf) -> PyResult<Bound<'_, PyDict>> {
+    let data = ver_shim_build::read_from_file(&path).map_err(to_py_err)?;
+    let info = PyDict::new(py);
+    for (member, value) in data.iter() {
+        if member.is_map() {
+            info.set_item(member.name(), data.get_map(member))?;
+        } else if member == Member::GitDirty {
+            info.set_item(member.name(), value == "true")?;
+        } else {
+            info.set_item(member.name(), value)?;
+        }
+    }
+    Ok(info)
+}
+
+/// Patches version data into a binary, and returns the path of the patched binary.
+///
+/// `values` maps member names to strings, e.g. `{"git_sha": sha, "pkg_version": "1.4.0"}`,
+/// with a bool for `git_dirty` and a dict for `custom_kv`. The binary is patched in
+/// place, unless `output` is given. Nothing is collected from git or the environment.
+///
+/// Warnings from patching are issued as `UserWarning`s, and other messages are logged
+/// to the `ver_shim_py` logger.
+#[pyfunction]
+#[pyo3(signature = (path, values, output = None))]
+fn patch_version_info(
+    py: Python<'_>,
+    path: PathBuf,
+    values: &Bound<'_, PyDict>,
+    output: Option<PathBuf>,
+) -> PyResult<PathBuf> {
+    let diagnostics = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&diagnostics);
+    let mut section = LinkSection::new()
+        .with_diagnostics(move |diag: &Diagnostic| sink.lock().unwrap().push(diag.clone()));
+    for (name, value) in values.iter() {
+        let name: String = name.extract()?;
+        let member = Member::from_name(&name)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown member: {}", name)))?;
+        if member == Member::CustomKv {
+            let pairs: BTreeMap<String, String> = value.extract()?;
+            for (key, value) in pairs {
+                check_no_nul(&key)?;
+                check_no_nul(&value)?;
+                section = section.with_custom_kv(key, value);
+            }
+            continue;
+        }
+        if member.is_map() || matches!(member, Member::Sbom | Member::Signature | Member::SelfHash)
+        {
+            return Err(PyValueError::new_err(format!("{} can't be set", name)));
+        }
+        let value = match value.extract::<bool>() {
+            Ok(value) if member == Member::GitDirty => value.to_string(),
+            _ => value.extract::<String>()?,
+        };
+        if member == Member::GitDirty && value != "true" && value != "false" {
+            return Err(PyValueError::new_err("git_dirty must be a bool"));
+        }
+        check_no_nul(&value)?;
+        section = section.with_member_value(member, value);
+    }
+    let output = output.unwrap_or_else(|| path.clone());
+    let result = section
+        .try_collect()
+        .and_then(|section| section.patch_into(&path).try_write_to(&output));
+    report(py, &diagnostics.lock().unwrap())?;
+    result.map_err(to_py_err)
+}
+
+/// Passes the diagnostics of patching on to Python: warnings as `UserWarning`s, and
+/// info messages and errors to the `ver_shim_py` logger.
+fn report(py: Python<'_>, diagnostics: &[Diagnostic]) -> PyResult<()> {
+    let logger = py
+        .import("logging")?
+        .call_method1("getLogger", ("ver_shim_py",))?;
+    for diag in diagnostics {
+        match diag.level {
+            Level::Info => {
+                logger.call_method1("info", (&diag.message,))?;
+            }
+            Level::Warning => {
+                let message = CString::new(diag.message.as_str())?;
+                PyErr::warn(py, py.get_type::<PyUserWarning>().as_any(), &message, 1)?;
+            }
+            Level::Error => {
+                logger.call_method1("error", (&diag.message,))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_no_nul(value: &str) -> PyResult<()> {
+    if value.contains('\0') {
+        return Err(PyValueError::new_err("values must not contain NUL bytes"));
+    }
+    Ok(())
+}
+
+fn to_py_err(e: ver_shim_build::VerShimError) -> PyErr {
+    VerShimError::new_err(e.to_string())
+}
+
+#[pymodule]
+fn ver_shim_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(read_version_info, m)?)?;
+    m.add_function(wrap_pyfunction!(patch_version_info, m)?)?;
+    m.add("VerShimError", m.py().get_type::<VerShimError>())?;
+    Ok(())
+}
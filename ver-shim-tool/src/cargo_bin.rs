@@ -0,0 +1,140 @@
+//! Building and locating a binary of the current cargo workspace, for `--bin`.
+
+use conf::Conf;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Selects a binary target of the current cargo workspace, instead of a path.
+#[derive(Debug, Conf)]
+pub struct CargoBin {
+    /// Name of a binary target in the current cargo workspace, instead of a path.
+    /// patch builds it with cargo first.
+    #[conf(long)]
+    pub bin: Option<String>,
+
+    /// Package of the binary (with --bin), if the name is ambiguous
+    #[conf(short, long)]
+    pub package: Option<String>,
+
+    /// Use the release profile (with --bin)
+    #[conf(long)]
+    pub release: bool,
+
+    /// Use this cargo profile (with --bin)
+    #[conf(long)]
+    pub profile: Option<String>,
+
+    /// Target triple (with --bin), when cross-compiling
+    #[conf(long)]
+    pub target: Option<String>,
+}
+
+impl CargoBin {
+    /// Runs `cargo build` for the binary.
+    pub fn build(&self, bin: &str) -> Result<(), String> {
+        let mut command = cargo();
+        command.args(["build", "--bin", bin]);
+        if let Some(ref package) = self.package {
+            command.args(["--package", package]);
+        }
+        if self.release {
+            command.arg("--release");
+        }
+        if let Some(ref profile) = self.profile {
+            command.args(["--profile", profile]);
+        }
+        if let Some(ref target) = self.target {
+            command.args(["--target", target]);
+        }
+        let status = command
+            .status()
+            .map_err(|e| format!("failed to run cargo build: {e}"))?;
+        if !status.success() {
+            return Err(format!("cargo build failed ({status})"));
+        }
+        Ok(())
+    }
+
+    /// Gets the path of the binary in the target directory, e.g.
+    /// `target/release/my-bin`, without building it.
+    pub fn path(&self, bin: &str) -> Result<PathBuf, String> {
+        // Custom profiles get a directory of their own, the built-in ones don't
+        let profile_dir = match self.profile.as_deref() {
+            Some("dev" | "test") => "debug",
+            Some("release" | "bench") => "release",
+            Some(profile) => profile,
+            None if self.release => "release",
+            None => "debug",
+        };
+
+        let mut path = target_dir()?;
+        if let Some(ref target) = self.target {
+            path.push(target);
+        }
+        path.push(profile_dir);
+
+        let is_windows = match self.target.as_deref() {
+            Some(target) => target.contains("windows"),
+            None => cfg!(windows),
+        };
+        path.push(if is_windows {
+            format!("{bin}.exe")
+        } else {
+            bin.to_string()
+        });
+
+        if !path.exists() {
+            return Err(format!(
+                "{} does not exist, build it with cargo first",
+                path.display()
+            ));
+        }
+        Ok(path)
+    }
+}
+
+/// Creates a command running cargo, the same one if we were run by cargo.
+fn cargo() -> Command {
+    Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+}
+
+/// Gets the target directory of the current workspace from `cargo metadata`.
+fn target_dir() -> Result<PathBuf, String> {
+    let output = cargo()
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .output()
+        .map_err(|e| format!("failed to run cargo metadata: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "cargo metadata failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    let metadata = String::from_utf8_lossy(&output.stdout);
+    json_string_field(&metadata, "target_directory")
+        .map(PathBuf::from)
+        .ok_or_else(|| "target_directory not found in cargo metadata output".to_string())
+}
+
+/// Gets a string field from JSON output, without a JSON parser.
+///
+/// This only handles the escapes which can occur in a path, which is enough for the
+/// `target_directory` of `cargo metadata`.
+fn json_string_field(json: &str, field: &str) -> Option<String> {
+    let start = json.find(&format!("\"{field}\":\""))? + field.len() + 4;
+    let mut value = String::new();
+    let mut chars = json[start..].chars();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                '"' => value.push('"'),
+                '\\' => value.push('\\'),
+                '/' => value.push('/'),
+                _ => return None,
+            },
+            c => value.push(c),
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! `cargo ver-shim`: runs the `ver-shim` tool as a cargo subcommand.
+//!
+//! Cargo runs `cargo-ver-shim ver-shim <args>`, so this drops the subcommand name and runs
+//! the `ver-shim` binary installed next to it with the remaining arguments, e.g.
+//! `cargo ver-shim --all-git patch --bin my-bin --release`.
+
+use std::ffi::OsString;
+use std::process::{Command, exit};
+
+fn main() {
+    let mut args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "ver-shim") {
+        args.remove(0);
+    }
+
+    // Prefer the ver-shim installed along with this binary, over another one on PATH
+    let ver_shim = std::env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name(format!("ver-shim{}", std::env::consts::EXE_SUFFIX)))
+        .filter(|path| path.exists())
+        .map_or_else(|| OsString::from("ver-shim"), OsString::from);
+
+    match Command::new(&ver_shim).args(&args).status() {
+        Ok(status) => exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("error: failed to run {}: {e}", ver_shim.to_string_lossy());
+            exit(1);
+        }
+    }
+}
@@ -1,6 +1,14 @@
 use conf::{Conf, Subcommands};
-use std::path::PathBuf;
-use ver_shim_build::LinkSection;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+mod cargo_bin;
+use cargo_bin::CargoBin;
+use ver_shim_build::{
+    BranchSource, CollectedSection, DescribeOpts, EmailPrivacy, GitTimestampSource, Level,
+    LinkSection, Member, OnShallow, SectionData, TimestampFormat, UpdateSectionCommand,
+    compare_binaries,
+};
 
 /// Inject git and build metadata into binaries via the .ver_shim_data linker section.
 ///
@@ -12,29 +20,92 @@ use ver_shim_build::LinkSection;
 /// 2. Patch a binary directly (recommended):
 ///      ver-shim --all-git --build-timestamp patch target/release/my-bin
 ///
+///    or build a binary of the current workspace and patch it, also as a cargo subcommand:
+///      cargo ver-shim --all-git patch --bin my-bin --release
+///
 /// The patch command produces a new binary with .bin extension containing the version info.
 ///
 /// For reproducible builds:
 /// - VER_SHIM_IDEMPOTENT: If set, build timestamp/date are never included (always None)
 /// - VER_SHIM_BUILD_TIME: Override build timestamp with a fixed value (unix or RFC 3339)
+/// - SOURCE_DATE_EPOCH: Same, if VER_SHIM_BUILD_TIME is not set
+/// - --reproducible: Never use the wall clock, take build time from VER_SHIM_BUILD_TIME
+///   or SOURCE_DATE_EPOCH (or omit it), use UTC, and fail on git errors. Can't be combined
+///   with --build-host or --ci-info
 #[derive(Debug, Conf)]
 struct Args {
     /// Include git SHA (git rev-parse HEAD)
     #[conf(long)]
     git_sha: bool,
 
+    /// Include git SHA abbreviated to this many hex digits (e.g. 12), instead of the full
+    /// SHA
+    #[conf(long)]
+    git_short_sha: Option<usize>,
+
+    /// Describe the last commit touching this path rather than HEAD, for monorepos. A git
+    /// pathspec relative to the current directory, e.g. "services/api" or ":/services/api"
+    /// from the repository root
+    #[conf(long)]
+    git_path_scope: Option<String>,
+
     /// Include git describe (git describe --always --dirty)
     #[conf(long)]
     git_describe: bool,
 
+    /// Also use lightweight tags for git describe, like git describe --tags
+    #[conf(long)]
+    describe_tags: bool,
+
+    /// Only use tags matching this glob for git describe, e.g. "v*"
+    #[conf(long)]
+    describe_match: Option<String>,
+
+    /// Number of hex digits of the hash in git describe
+    #[conf(long)]
+    describe_abbrev: Option<u32>,
+
+    /// Suffix of git describe for a dirty work tree (default "-dirty")
+    #[conf(long)]
+    describe_dirty_suffix: Option<String>,
+
     /// Include git branch (git rev-parse --abbrev-ref HEAD)
     #[conf(long)]
     git_branch: bool,
 
+    /// Where the git branch comes from, tried in the order given: "head", "ci",
+    /// "points-at", or "name-rev" (may be repeated, default is all of them in that order)
+    #[conf(repeat, long)]
+    git_branch_source: Vec<String>,
+
+    /// Include git tag pointing at HEAD, if any (git tag --points-at HEAD), not included
+    /// by --all-git
+    #[conf(long)]
+    git_tag: bool,
+
+    /// Include whether the work tree has uncommitted changes, not included by --all-git
+    #[conf(long)]
+    git_dirty: bool,
+
+    /// What to do if the git repository is a shallow clone, which lacks the tags for
+    /// describe: "warn" (default), "fetch" (git fetch --unshallow --tags), or "fail"
+    #[conf(long)]
+    on_shallow: Option<String>,
+
     /// Include git commit timestamp
     #[conf(long)]
     git_commit_timestamp: bool,
 
+    /// Format of the git commit timestamp: "rfc3339" (default), "unix", or a strftime
+    /// format like "%Y%m%d%H%M%S"
+    #[conf(long)]
+    git_commit_timestamp_format: Option<String>,
+
+    /// Which date of HEAD the git commit timestamp/date hold: "author" (default, %aI) or
+    /// "committer" (%cI)
+    #[conf(long)]
+    git_timestamp_source: Option<String>,
+
     /// Include git commit date
     #[conf(long)]
     git_commit_date: bool,
@@ -43,6 +114,28 @@ struct Args {
     #[conf(long)]
     git_commit_msg: bool,
 
+    /// Include git commit author (Name <email>), not included by --all-git
+    #[conf(long)]
+    git_commit_author: bool,
+
+    /// Include git committer (Name <email>), not included by --all-git
+    #[conf(long)]
+    git_committer: bool,
+
+    /// Include the path and SHA of each git submodule, not included by --all-git
+    #[conf(long)]
+    git_submodules: bool,
+
+    /// Include the URL of the origin remote, with credentials stripped, not included by
+    /// --all-git
+    #[conf(long)]
+    git_remote_url: bool,
+
+    /// How to embed author/committer emails: "full" (default), "username" (the part
+    /// before the @), or "hash:SALT" (a salted hash of the email)
+    #[conf(long)]
+    email_privacy: Option<String>,
+
     /// Include all git information
     #[conf(long)]
     all_git: bool,
@@ -51,6 +144,11 @@ struct Args {
     #[conf(long)]
     build_timestamp: bool,
 
+    /// Format of the build timestamp: "rfc3339" (default), "unix", or a strftime format
+    /// like "%Y%m%d%H%M%S"
+    #[conf(long)]
+    build_timestamp_format: Option<String>,
+
     /// Include build date
     #[conf(long)]
     build_date: bool,
@@ -63,6 +161,101 @@ struct Args {
     #[conf(long)]
     custom: Option<String>,
 
+    /// Custom key-value pair to include, as KEY=VALUE (may be repeated, e.g.
+    /// --custom-kv ci_job=$CI_JOB_URL --custom-kv builder=$(hostname))
+    #[conf(repeat, long)]
+    custom_kv: Vec<String>,
+
+    /// Same as --custom-kv (may be repeated, e.g. --set ci_job=$CI_JOB_URL)
+    #[conf(repeat, long)]
+    set: Vec<String>,
+
+    /// Load member values from a dotenv (GIT_SHA=...) or JSON ({"git_sha": ...}) file,
+    /// e.g. one written with --build-args or read --json by an earlier build stage.
+    /// They replace collected values.
+    #[conf(long)]
+    from_file: Option<PathBuf>,
+
+    /// License/copyright string to include
+    #[conf(long)]
+    license_info: Option<String>,
+
+    /// Report the members and their sizes, and what would be written or patched, without
+    /// writing any files
+    #[conf(long)]
+    dry_run: bool,
+
+    /// Include entries registered by library crates, from this directory
+    /// (e.g. target/release/ver_shim_entries). Every file in it is included
+    #[conf(long)]
+    crate_entries: Option<PathBuf>,
+
+    /// Include the name and version of each member of the cargo workspace of the current
+    /// directory
+    #[conf(long)]
+    workspace_members: bool,
+
+    /// Include the SHA-256 of the Cargo.lock of the cargo workspace of the current directory
+    #[conf(long)]
+    lockfile_hash: bool,
+
+    /// Include the format and SHA-256 of this SBOM file (CycloneDX or SPDX)
+    #[conf(long)]
+    sbom_digest: Option<PathBuf>,
+
+    /// Also embed the SBOM file of --sbom-digest, compressed, if it fits
+    #[conf(long)]
+    embed_sbom: bool,
+
+    /// Include the CI provider, and the URL and ID of the run (GitHub Actions, GitLab CI,
+    /// Buildkite, or Jenkins)
+    #[conf(long)]
+    ci_info: bool,
+
+    /// Include the hostname, OS, and architecture of the build machine
+    #[conf(long)]
+    build_host: bool,
+
+    /// Include the cargo profile, from PROFILE, OPT_LEVEL, and DEBUG
+    #[conf(long)]
+    build_profile: bool,
+
+    /// Sign the version data with the ed25519 secret key in this file (32 bytes, as is or
+    /// in hex), for ver_shim::verify_signature()
+    #[conf(long)]
+    signing_key: Option<PathBuf>,
+
+    /// Sign the version data with the ed25519 secret key in hex in this environment
+    /// variable, or skip signing with a warning if it is not set
+    #[conf(long)]
+    signing_key_env: Option<String>,
+
+    /// Reproducible mode: build time only from VER_SHIM_BUILD_TIME or SOURCE_DATE_EPOCH
+    /// (omitted if neither is set), timestamps in UTC, and git failures are errors. Can't be
+    /// combined with --build-host or --ci-info
+    #[conf(long)]
+    reproducible: bool,
+
+    /// Check timestamps for obviously wrong values (in the future, before the commit,
+    /// or from 1970) and either warn or fail: one of "warn", "fail"
+    #[conf(long)]
+    check_timestamps: Option<String>,
+
+    /// Patch this link section instead of .ver_shim_data, for a section declared
+    /// with ver_shim::version_section!
+    #[conf(long)]
+    section_name: Option<String>,
+
+    /// Also write the collected values as KEY=value lines (e.g. GIT_SHA=...) to this file,
+    /// for docker build --build-arg
+    #[conf(long)]
+    build_args: Option<PathBuf>,
+
+    /// Also write the collected values as OCI image labels (e.g.
+    /// org.opencontainers.image.revision=...) to this file, for docker build --label
+    #[conf(long)]
+    oci_labels: Option<PathBuf>,
+
     /// Output path (writes to this path, or {path}/ver_shim_data if it's a directory).
     /// Mutually exclusive with subcommands.
     #[conf(short, long)]
@@ -82,16 +275,284 @@ enum Command {
     /// the requested version info, and writes the result to {input}.bin
     /// (or to the specified output path).
     ///
-    /// Requires llvm-tools: rustup component add llvm-tools
+    /// Instead of a path, --bin my-bin builds that binary of the current workspace
+    /// with cargo, and patches it. --dir dist/ patches all the binaries in a directory
+    /// with the same data.
+    ///
+    /// Uses llvm-objcopy (rustup component add llvm-tools) if installed, or else GNU
+    /// objcopy.
     Patch {
         /// Path to the binary to patch (e.g., target/release/my-bin)
         #[conf(pos)]
+        input: Option<PathBuf>,
+
+        #[conf(flatten)]
+        cargo: CargoBin,
+
+        /// Patch every binary in this directory which has a version section, instead of
+        /// a single one. Files named {name}.bin next to {name} are skipped, since they
+        /// are the output of an earlier run.
+        #[conf(long)]
+        dir: Option<PathBuf>,
+
+        /// Output directory or file path. If a directory, writes {input_name}.bin there.
+        /// Defaults to the input file's parent directory. Must be a directory with --dir.
+        #[conf(short, long)]
+        output: Option<PathBuf>,
+
+        /// After patching, split the debug info into this file (or {output_name}.debug
+        /// if it's a directory) and strip the patched binary. The GNU build-id of both
+        /// files is checked to match.
+        #[conf(long)]
+        split_debuginfo: Option<PathBuf>,
+
+        /// Write an in-toto provenance statement (SLSA Provenance v1) for the patched
+        /// binary to this file, or to {output_name}.intoto.jsonl if it's a directory
+        #[conf(long)]
+        provenance: Option<PathBuf>,
+
+        /// Record the SHA-256 of the patched binary in the section, for
+        /// ver_shim::verify_self_hash(). Only for ELF and PE binaries.
+        #[conf(long)]
+        self_hash: bool,
+
+        /// Patch ELF binaries in place instead of with llvm-objcopy, so their layout can't
+        /// change. This is always done for Android shared libraries.
+        #[conf(long)]
+        preserve_layout: bool,
+
+        /// Fail unless the patched ELF binary can be loaded with this page size, e.g.
+        /// 16384 for Android devices with 16 KiB pages
+        #[conf(long)]
+        page_size: Option<String>,
+    },
+
+    /// Patch version info given on the command line into a binary, without running git.
+    ///
+    /// Example: ver-shim stamp --git-sha "$SHA" --git-describe v1.2.0 target/release/my-bin
+    ///
+    /// For hermetic build systems which already know the values and can't run git in
+    /// their sandbox. The options before the subcommand still apply, e.g. --custom-kv,
+    /// but git only runs if one of them asks for git info. --value sets any other member,
+    /// named like the keys of read --json, e.g. --value pkg_name=my-bin. Writes
+    /// {input}.bin, like patch.
+    Stamp {
+        /// Path to the binary to patch
+        #[conf(pos)]
         input: PathBuf,
 
         /// Output directory or file path. If a directory, writes {input_name}.bin there.
         /// Defaults to the input file's parent directory.
         #[conf(short, long)]
         output: Option<PathBuf>,
+
+        /// The git commit SHA
+        #[conf(long)]
+        git_sha: Option<String>,
+
+        /// The git describe output, e.g. v1.2.0-3-gabc1234
+        #[conf(long)]
+        git_describe: Option<String>,
+
+        /// The git branch
+        #[conf(long)]
+        git_branch: Option<String>,
+
+        /// The git tag
+        #[conf(long)]
+        git_tag: Option<String>,
+
+        /// Whether the working tree had changes: "true" or "false"
+        #[conf(long)]
+        git_dirty: Option<String>,
+
+        /// The build timestamp, stored as given
+        #[conf(long)]
+        build_timestamp: Option<String>,
+
+        /// The package version
+        #[conf(long)]
+        pkg_version: Option<String>,
+
+        /// Set another member, as MEMBER=VALUE (may be repeated)
+        #[conf(repeat, long)]
+        value: Vec<String>,
+    },
+
+    /// Patch version info into an Intel HEX image.
+    ///
+    /// Example: ver-shim --all-git patch-hex firmware.hex --address 0x08010000
+    ///
+    /// The address is the load address of the .ver_shim_data section, which can be
+    /// found with llvm-readobj --sections on the ELF, or in the linker map file.
+    /// Writes {input_stem}-patched.hex (or to the specified output path).
+    PatchHex {
+        /// Path to the Intel HEX image to patch (e.g., firmware.hex)
+        #[conf(pos)]
+        input: PathBuf,
+
+        /// Load address of the .ver_shim_data section (decimal, or hex with 0x prefix)
+        #[conf(long)]
+        address: String,
+
+        /// Output directory or file path. If a directory, writes {input_stem}-patched.hex there.
+        /// Defaults to the input file's parent directory.
+        #[conf(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Patch version info into a Motorola S-record image.
+    ///
+    /// Example: ver-shim --all-git patch-srec firmware.s19 --address 0x08010000
+    ///
+    /// Like patch-hex, for .srec, .s19, .s28, .s37, or .mot files. Writes
+    /// {input_stem}-patched.{ext} (or to the specified output path).
+    PatchSrec {
+        /// Path to the S-record image to patch (e.g., firmware.s19)
+        #[conf(pos)]
+        input: PathBuf,
+
+        /// Load address of the .ver_shim_data section (decimal, or hex with 0x prefix)
+        #[conf(long)]
+        address: String,
+
+        /// Output directory or file path. If a directory, writes {input_stem}-patched.{ext}
+        /// there. Defaults to the input file's parent directory.
+        #[conf(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Patch version info into a UF2 image.
+    ///
+    /// Example: ver-shim --all-git patch-uf2 firmware.uf2 --address 0x10010000
+    ///
+    /// Like patch-hex, for the UF2 images of e.g. RP2040 bootloaders. Writes
+    /// {input_stem}-patched.uf2 (or to the specified output path).
+    PatchUf2 {
+        /// Path to the UF2 image to patch (e.g., firmware.uf2)
+        #[conf(pos)]
+        input: PathBuf,
+
+        /// Load address of the .ver_shim_data section (decimal, or hex with 0x prefix)
+        #[conf(long)]
+        address: String,
+
+        /// Output directory or file path. If a directory, writes {input_stem}-patched.uf2
+        /// there. Defaults to the input file's parent directory.
+        #[conf(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Patch version info into a raw binary image, e.g. from objcopy -O binary.
+    ///
+    /// Example: ver-shim --all-git patch-raw firmware.bin --offset 0x10000
+    ///
+    /// The offset is that of the .ver_shim_data section in the image: its load address
+    /// minus the load address of the image. Writes {input_stem}-patched.{ext} (or to the
+    /// specified output path).
+    PatchRaw {
+        /// Path to the raw image to patch (e.g., firmware.bin)
+        #[conf(pos)]
+        input: PathBuf,
+
+        /// Offset of the .ver_shim_data section in the image (decimal, or hex with 0x prefix)
+        #[conf(long)]
+        offset: String,
+
+        /// If the image ends before the section, extend it with this byte (e.g. 0xff)
+        /// instead of failing
+        #[conf(long)]
+        pad: Option<String>,
+
+        /// Output directory or file path. If a directory, writes {input_stem}-patched.{ext}
+        /// there. Defaults to the input file's parent directory.
+        #[conf(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Compare the version info of two binaries.
+    ///
+    /// Example: ver-shim diff old/my-bin new/my-bin
+    ///
+    /// Prints each member that differs, and exits with status 1 if any differ
+    /// (like diff), so scripts can tell whether an upgrade is a no-op.
+    Diff {
+        /// The first binary
+        #[conf(pos)]
+        a: PathBuf,
+
+        /// The second binary
+        #[conf(pos)]
+        b: PathBuf,
+
+        /// Don't compare this member, e.g. build-timestamp (may be repeated)
+        #[conf(repeat, long)]
+        ignore: Vec<String>,
+
+        /// Print a JSON array of the differing members instead, with their old and new
+        /// data (null if absent)
+        #[conf(long)]
+        json: bool,
+    },
+
+    /// Print the version info embedded in a binary.
+    ///
+    /// Example: ver-shim read target/release/my-bin
+    ///
+    /// Works with ELF, Mach-O and PE binaries, and static library archives,
+    /// without running the binary. Exits with status 1 if no version section is found.
+    /// Instead of a path, --bin my-bin reads that binary of the current workspace,
+    /// e.g. target/debug/my-bin.bin after patch --bin my-bin.
+    Read {
+        /// The binary to read
+        #[conf(pos)]
+        input: Option<PathBuf>,
+
+        #[conf(flatten)]
+        cargo: CargoBin,
+
+        /// Print a JSON object instead of one member per line, same as --format json
+        #[conf(long)]
+        json: bool,
+
+        /// Output format: "text" (default, one member per line), "json", "toml", "yaml",
+        /// or "env" (VER_SHIM_GIT_SHA='...' lines for eval in a shell)
+        #[conf(long)]
+        format: Option<String>,
+
+        /// Read all version sections, including those declared with
+        /// ver_shim::version_section!, instead of only .ver_shim_data. With --json, prints
+        /// an object with one entry per section name.
+        #[conf(long)]
+        all: bool,
+
+        /// Print the SBOM embedded with --embed-sbom, decompressed, instead of the members
+        #[conf(long)]
+        sbom: bool,
+    },
+
+    /// Check that a binary carries the expected version info.
+    ///
+    /// Example: ver-shim verify dist/my-bin --expect-describe v1.2.0
+    ///
+    /// Exits with status 1 if any expected value doesn't match (or is absent),
+    /// e.g. to check in a release pipeline that the binary was built from the right tag.
+    Verify {
+        /// The binary to check
+        #[conf(pos)]
+        input: PathBuf,
+
+        /// Expected git SHA. An abbreviated SHA matches if it is a prefix of the embedded one.
+        #[conf(long)]
+        expect_sha: Option<String>,
+
+        /// Expected git describe output
+        #[conf(long)]
+        expect_describe: Option<String>,
+
+        /// Expected git branch
+        #[conf(long)]
+        expect_branch: Option<String>,
     },
 }
 
@@ -111,6 +572,12 @@ fn build_section(args: &Args) -> LinkSection {
         if args.git_branch {
             section = section.with_git_branch();
         }
+        if args.git_tag {
+            section = section.with_git_tag();
+        }
+        if args.git_dirty {
+            section = section.with_git_dirty();
+        }
         if args.git_commit_timestamp {
             section = section.with_git_commit_timestamp();
         }
@@ -122,6 +589,32 @@ fn build_section(args: &Args) -> LinkSection {
         }
     }
 
+    if !args.git_branch_source.is_empty() {
+        let sources: Vec<BranchSource> = args
+            .git_branch_source
+            .iter()
+            .map(|source| match source.as_str() {
+                "head" => BranchSource::Head,
+                "ci" => BranchSource::Ci,
+                "points-at" => BranchSource::PointsAt,
+                "name-rev" => BranchSource::NameRev,
+                other => {
+                    eprintln!(
+                        "error: invalid --git-branch-source: {other} (expected head, ci, points-at, or name-rev)"
+                    );
+                    std::process::exit(1);
+                }
+            })
+            .collect();
+        section = section.with_git_branch_sources(&sources);
+    }
+    if let Some(len) = args.git_short_sha {
+        section = section.with_git_short_sha(len);
+    }
+    if let Some(ref path) = args.git_path_scope {
+        section = section.with_git_path_scope(path);
+    }
+
     // Build time options
     if args.all_build_time {
         section = section.with_all_build_time();
@@ -134,14 +627,331 @@ fn build_section(args: &Args) -> LinkSection {
         }
     }
 
+    if let Some(ref format) = args.git_commit_timestamp_format {
+        section = section.with_git_commit_timestamp_format(parse_timestamp_format(format));
+    }
+    let describe_opts = DescribeOpts {
+        tags: args.describe_tags,
+        match_pattern: args.describe_match.clone(),
+        abbrev: args.describe_abbrev,
+        dirty_suffix: args.describe_dirty_suffix.clone(),
+    };
+    if describe_opts != DescribeOpts::default() {
+        section = section.with_git_describe_opts(describe_opts);
+    }
+    if let Some(ref source) = args.git_timestamp_source {
+        let source = match source.as_str() {
+            "author" => GitTimestampSource::Author,
+            "committer" => GitTimestampSource::Committer,
+            other => {
+                eprintln!(
+                    "error: invalid --git-timestamp-source: {other} (expected author or committer)"
+                );
+                std::process::exit(1);
+            }
+        };
+        section = section.with_git_timestamp_source(source);
+    }
+    if let Some(ref on_shallow) = args.on_shallow {
+        let on_shallow = match on_shallow.as_str() {
+            "warn" => OnShallow::Warn,
+            "fetch" => OnShallow::Fetch,
+            "fail" => OnShallow::Fail,
+            other => {
+                eprintln!("error: invalid --on-shallow: {other} (expected warn, fetch, or fail)");
+                std::process::exit(1);
+            }
+        };
+        section = section.on_shallow_clone(on_shallow);
+    }
+    if let Some(ref format) = args.build_timestamp_format {
+        section = section.with_build_timestamp_format(parse_timestamp_format(format));
+    }
+
     // Custom string
     if let Some(ref custom) = args.custom {
         section = section.with_custom(custom);
     }
 
+    let custom_kvs = args.custom_kv.iter().map(|kv| ("--custom-kv", kv));
+    for (flag, kv) in custom_kvs.chain(args.set.iter().map(|kv| ("--set", kv))) {
+        let Some((key, value)) = kv.split_once('=') else {
+            eprintln!("error: invalid {flag}: {kv} (expected KEY=VALUE)");
+            std::process::exit(1);
+        };
+        section = section.with_custom_kv(key, value);
+    }
+
+    if let Some(ref path) = args.from_file {
+        section = section.with_env_file(path);
+    }
+
+    if let Some(ref license_info) = args.license_info {
+        section = section.with_license_info_str(license_info);
+    }
+
+    if let Some(ref crate_entries) = args.crate_entries {
+        section = section.with_crate_entries_from(crate_entries);
+    }
+
+    if args.workspace_members {
+        section = section.with_workspace_members();
+    }
+
+    if args.lockfile_hash {
+        section = section.with_lockfile_hash();
+    }
+
+    if let Some(ref sbom) = args.sbom_digest {
+        section = section.with_sbom_digest(sbom);
+    }
+
+    if args.embed_sbom {
+        section = section.embed_sbom();
+    }
+
+    if args.ci_info {
+        section = section.with_ci_info();
+    }
+
+    if args.build_host {
+        section = section.with_build_host();
+    }
+
+    if args.build_profile {
+        section = section.with_build_profile();
+    }
+
+    if let Some(ref path) = args.signing_key {
+        section = section.with_signing_key(path);
+    }
+    if let Some(ref var) = args.signing_key_env {
+        section = section.with_signing_key_env(var);
+    }
+
+    if args.reproducible {
+        section = section.reproducible();
+    }
+
+    if args.git_commit_author {
+        section = section.with_git_commit_author();
+    }
+    if args.git_committer {
+        section = section.with_git_committer();
+    }
+    if args.git_submodules {
+        section = section.with_git_submodules();
+    }
+    if args.git_remote_url {
+        section = section.with_git_remote_url();
+    }
+    if let Some(ref privacy) = args.email_privacy {
+        let privacy = match privacy.as_str() {
+            "full" => EmailPrivacy::Full,
+            "username" => EmailPrivacy::Username,
+            other => match other.strip_prefix("hash:") {
+                Some(salt) => EmailPrivacy::SaltedHash(salt.to_string()),
+                None => {
+                    eprintln!(
+                        "error: invalid --email-privacy: {other} (expected full, username, or hash:SALT)"
+                    );
+                    std::process::exit(1);
+                }
+            },
+        };
+        section = section.with_email_privacy(privacy);
+    }
+
+    if let Some(ref name) = args.section_name {
+        section = section.with_section_name(name);
+    }
+
+    if args.dry_run {
+        section = section.dry_run();
+    }
+
+    if let Some(ref level) = args.check_timestamps {
+        let level = match level.as_str() {
+            "warn" => Level::Warning,
+            "fail" => Level::Error,
+            _ => {
+                eprintln!("error: invalid --check-timestamps: {level} (expected warn or fail)");
+                std::process::exit(1);
+            }
+        };
+        section = section.check_timestamps(level);
+    }
+
     section
 }
 
+/// Collects the version info once, and writes the build args file if requested,
+/// so that it has the same values as the section.
+fn collect(args: &Args) -> CollectedSection {
+    collect_from(args, build_section(args))
+}
+
+/// Like `collect()`, but for a section which was already configured from the options.
+fn collect_from(args: &Args, section: LinkSection) -> CollectedSection {
+    let section = section.collect();
+    if let Some(ref path) = args.build_args {
+        section.write_build_args(path);
+        if !args.dry_run {
+            eprintln!("ver-shim: wrote build args to {}", path.display());
+        }
+    }
+    if let Some(ref path) = args.oci_labels {
+        section.write_oci_labels(path);
+        if !args.dry_run {
+            eprintln!("ver-shim: wrote OCI labels to {}", path.display());
+        }
+    }
+    section
+}
+
+/// Gets the binary to work on, from the path or from `--bin`, which is built first if
+/// `build` is true.
+fn resolve_input(input: &Option<PathBuf>, cargo: &CargoBin, build: bool) -> PathBuf {
+    let result = match (input, &cargo.bin) {
+        (Some(input), None) => Ok(input.clone()),
+        (None, Some(bin)) => {
+            let built = if build { cargo.build(bin) } else { Ok(()) };
+            built.and_then(|()| cargo.path(bin))
+        }
+        (Some(_), Some(_)) => Err("give either a path or --bin, not both".to_string()),
+        (None, None) => Err("a path or --bin is required".to_string()),
+    };
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    })
+}
+
+/// Patches all binaries in `dir` with the same data, and prints a summary. Exits with
+/// status 1 if any of them failed.
+fn patch_dir(
+    section: &CollectedSection,
+    dir: &Path,
+    output: &Path,
+    configure: &dyn Fn(UpdateSectionCommand) -> UpdateSectionCommand,
+    dry_run: bool,
+) {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap_or_else(|e| {
+            eprintln!("error: failed to read {}: {e}", dir.display());
+            std::process::exit(1);
+        })
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+
+    let (mut patched, mut skipped, mut failed) = (0, 0, 0);
+    for path in &paths {
+        // Skip the output of an earlier run, {name}.bin next to {name}
+        if path.extension().is_some_and(|ext| ext == "bin") && path.with_extension("").is_file() {
+            skipped += 1;
+            continue;
+        }
+        if let Err(e) = SectionData::from_binary(path) {
+            eprintln!("ver-shim: skipping {}: {e}", path.display());
+            skipped += 1;
+            continue;
+        }
+        match configure(section.patch_into(path)).try_write_to(output) {
+            Ok(output_path) => {
+                report_patched(dry_run, path, &output_path);
+                patched += 1;
+            }
+            Err(e) => {
+                eprintln!("error: failed to patch {}: {e}", path.display());
+                failed += 1;
+            }
+        }
+    }
+
+    eprintln!("ver-shim: patched {patched}, skipped {skipped}, failed {failed}");
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Reports a patched binary, or one that would have been patched with --dry-run.
+fn report_patched(dry_run: bool, input: &Path, output: &Path) {
+    let verb = if dry_run { "would patch" } else { "patched" };
+    eprintln!(
+        "ver-shim: {verb} {} -> {}",
+        input.display(),
+        output.display()
+    );
+}
+
+/// Applies the --preserve-layout and --page-size options of the patch command.
+fn layout_options(
+    mut command: UpdateSectionCommand,
+    preserve_layout: bool,
+    page_size: Option<u64>,
+) -> UpdateSectionCommand {
+    if preserve_layout {
+        command = command.preserve_layout();
+    }
+    if let Some(page_size) = page_size {
+        command = command.check_page_alignment(page_size);
+    }
+    command
+}
+
+/// Parses a --*-timestamp-format option. Anything but "rfc3339" and "unix" is a strftime
+/// format, which is checked when the section is collected.
+fn parse_timestamp_format(s: &str) -> TimestampFormat {
+    match s {
+        "rfc3339" => TimestampFormat::Rfc3339,
+        "unix" => TimestampFormat::Unix,
+        custom => TimestampFormat::Custom(custom.to_string()),
+    }
+}
+
+/// Parses a member name, e.g. "build_timestamp", "build-timestamp", or "BuildTimestamp".
+fn parse_member(name: &str) -> Option<Member> {
+    let normalize = |s: &str| s.replace(['-', '_'], "").to_ascii_lowercase();
+    let name = normalize(name);
+    Member::ALL
+        .into_iter()
+        .find(|member| normalize(member.name()) == name)
+}
+
+/// Parses the --page-size option, exiting if it isn't a power of two.
+fn parse_page_size(s: &str) -> u64 {
+    match parse_number(s) {
+        Some(page_size) if page_size.is_power_of_two() => page_size,
+        _ => {
+            eprintln!("error: invalid --page-size: {s}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parses an address, offset, or byte, as decimal or as hex with a 0x prefix.
+fn parse_number(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16).ok(),
+        None => s.replace('_', "").parse().ok(),
+    }
+}
+
+/// Prints the members of a section, one per line, and one line per entry of a map.
+fn print_members(data: &SectionData) {
+    for (member, value) in data.iter() {
+        if member.is_map() {
+            for (key, value) in data.get_map(member) {
+                println!("{:?}: {} = {}", member, key, value);
+            }
+        } else {
+            println!("{:?}: {}", member, value);
+        }
+    }
+}
+
 fn main() {
     // Unset OUT_DIR to prevent LinkSection from trying to use build.rs paths
     // SAFETY: We're single-threaded at this point, before any other code runs
@@ -152,33 +962,413 @@ fn main() {
     // Error if --output is specified with a subcommand
     if args.output.is_some() && args.command.is_some() {
         eprintln!(
-            "error: when using patch command, top-level --output flag is ignored; \
+            "error: when using a subcommand, top-level --output flag is ignored; \
              this is probably not what you intended"
         );
         std::process::exit(1);
     }
 
-    let section = build_section(&args);
-
     match args.command {
-        Some(Command::Patch { ref input, ref output }) => {
+        Some(Command::Patch {
+            ref input,
+            ref cargo,
+            dir: Some(ref dir),
+            ref output,
+            ref split_debuginfo,
+            ref provenance,
+            self_hash,
+            preserve_layout,
+            ref page_size,
+        }) => {
+            let page_size = page_size.as_deref().map(parse_page_size);
+            if input.is_some()
+                || cargo.bin.is_some()
+                || split_debuginfo.is_some()
+                || provenance.is_some()
+            {
+                eprintln!(
+                    "error: --dir can't be used with a path, --bin, --split-debuginfo or --provenance"
+                );
+                std::process::exit(1);
+            }
+            let output_path = output.clone().unwrap_or_else(|| dir.clone());
+            if !output_path.is_dir() {
+                eprintln!("error: {} is not a directory", output_path.display());
+                std::process::exit(1);
+            }
+            let configure = |command| {
+                let command = layout_options(command, preserve_layout, page_size);
+                if self_hash {
+                    command.with_self_hash()
+                } else {
+                    command
+                }
+            };
+            patch_dir(&collect(&args), dir, &output_path, &configure, args.dry_run);
+        }
+        Some(Command::Patch {
+            ref input,
+            ref cargo,
+            dir: None,
+            ref output,
+            ref split_debuginfo,
+            ref provenance,
+            self_hash,
+            preserve_layout,
+            ref page_size,
+        }) => {
+            let page_size = page_size.as_deref().map(parse_page_size);
+            let input = &resolve_input(input, cargo, true);
             let output_path = output
                 .clone()
                 .unwrap_or_else(|| input.parent().unwrap().to_path_buf());
-            section.patch_into(input).write_to(&output_path);
-            eprintln!(
-                "ver-shim: patched {} -> {}",
-                input.display(),
-                output_path.display()
-            );
+            let mut command =
+                layout_options(collect(&args).patch_into(input), preserve_layout, page_size);
+            if let Some(debug_path) = split_debuginfo {
+                command = command.with_split_debuginfo(debug_path);
+            }
+            if let Some(provenance_path) = provenance {
+                command = command.with_provenance_file(provenance_path);
+            }
+            if self_hash {
+                command = command.with_self_hash();
+            }
+            command.write_to(&output_path);
+            report_patched(args.dry_run, input, &output_path);
         }
-        None => {
-            let Some(output) = args.output else {
-                eprintln!("error: --output is required when not using a subcommand");
+        Some(Command::Stamp {
+            ref input,
+            ref output,
+            ref git_sha,
+            ref git_describe,
+            ref git_branch,
+            ref git_tag,
+            ref git_dirty,
+            ref build_timestamp,
+            ref pkg_version,
+            ref value,
+        }) => {
+            let mut values: Vec<(Member, &str)> = [
+                (Member::GitSha, git_sha),
+                (Member::GitDescribe, git_describe),
+                (Member::GitBranch, git_branch),
+                (Member::GitTag, git_tag),
+                (Member::GitDirty, git_dirty),
+                (Member::BuildTimestamp, build_timestamp),
+                (Member::PkgVersion, pkg_version),
+            ]
+            .into_iter()
+            .filter_map(|(member, value)| Some((member, value.as_deref()?)))
+            .collect();
+            for assignment in value {
+                let member = assignment
+                    .split_once('=')
+                    .and_then(|(name, value)| Some((parse_member(name)?, value)));
+                match member {
+                    Some((member, value))
+                        if !member.is_map()
+                            && !matches!(
+                                member,
+                                Member::Sbom | Member::Signature | Member::SelfHash
+                            ) =>
+                    {
+                        values.push((member, value));
+                    }
+                    _ => {
+                        eprintln!(
+                            "error: invalid --value: {assignment} (expected MEMBER=VALUE, e.g. pkg_name=my-bin)"
+                        );
+                        std::process::exit(1);
+                    }
+                }
+            }
+            let mut section = build_section(&args);
+            for (member, value) in values {
+                if member == Member::GitDirty && value != "true" && value != "false" {
+                    eprintln!("error: invalid git_dirty: {value} (expected true or false)");
+                    std::process::exit(1);
+                }
+                section = section.with_member_value(member, value);
+            }
+            let output_path = output
+                .clone()
+                .unwrap_or_else(|| input.parent().unwrap().to_path_buf());
+            collect_from(&args, section)
+                .patch_into(input)
+                .write_to(&output_path);
+            report_patched(args.dry_run, input, &output_path);
+        }
+        Some(Command::PatchHex {
+            ref input,
+            ref address,
+            ref output,
+        }) => {
+            let Some(address) = parse_number(address).and_then(|a| u32::try_from(a).ok()) else {
+                eprintln!("error: invalid --address: {address}");
+                std::process::exit(1);
+            };
+            let output_path = output
+                .clone()
+                .unwrap_or_else(|| input.parent().unwrap().to_path_buf());
+            collect(&args)
+                .patch_into_ihex(input, address)
+                .write_to(&output_path);
+            report_patched(args.dry_run, input, &output_path);
+        }
+        Some(Command::PatchSrec {
+            ref input,
+            ref address,
+            ref output,
+        }) => {
+            let Some(address) = parse_number(address).and_then(|a| u32::try_from(a).ok()) else {
+                eprintln!("error: invalid --address: {address}");
+                std::process::exit(1);
+            };
+            let output_path = output
+                .clone()
+                .unwrap_or_else(|| input.parent().unwrap().to_path_buf());
+            collect(&args)
+                .patch_into_srec(input, address)
+                .write_to(&output_path);
+            report_patched(args.dry_run, input, &output_path);
+        }
+        Some(Command::PatchUf2 {
+            ref input,
+            ref address,
+            ref output,
+        }) => {
+            let Some(address) = parse_number(address).and_then(|a| u32::try_from(a).ok()) else {
+                eprintln!("error: invalid --address: {address}");
                 std::process::exit(1);
             };
-            let output_path = section.write_to(&output);
-            eprintln!("ver-shim: wrote {}", output_path.display());
+            let output_path = output
+                .clone()
+                .unwrap_or_else(|| input.parent().unwrap().to_path_buf());
+            collect(&args)
+                .patch_into_uf2(input, address)
+                .write_to(&output_path);
+            report_patched(args.dry_run, input, &output_path);
+        }
+        Some(Command::PatchRaw {
+            ref input,
+            ref offset,
+            ref pad,
+            ref output,
+        }) => {
+            let Some(offset) = parse_number(offset) else {
+                eprintln!("error: invalid --offset: {offset}");
+                std::process::exit(1);
+            };
+            let output_path = output
+                .clone()
+                .unwrap_or_else(|| input.parent().unwrap().to_path_buf());
+            let mut command = collect(&args).patch_into_raw(input, offset);
+            if let Some(pad) = pad {
+                let Some(fill) = parse_number(pad).and_then(|b| u8::try_from(b).ok()) else {
+                    eprintln!("error: invalid --pad: {pad}");
+                    std::process::exit(1);
+                };
+                command = command.with_padding(fill);
+            }
+            command.write_to(&output_path);
+            report_patched(args.dry_run, input, &output_path);
+        }
+        Some(Command::Diff {
+            ref a,
+            ref b,
+            ref ignore,
+            json,
+        }) => {
+            let ignore: Vec<Member> = ignore
+                .iter()
+                .map(|name| {
+                    parse_member(name).unwrap_or_else(|| {
+                        eprintln!("error: invalid --ignore: {name} (expected a member, e.g. build-timestamp)");
+                        std::process::exit(1);
+                    })
+                })
+                .collect();
+            let mut diffs = compare_binaries(a, b);
+            diffs.retain(|diff| !ignore.contains(&diff.member));
+            if json {
+                let entries: Vec<String> = diffs
+                    .iter()
+                    .map(|diff| format!("\n  {}", diff.to_json()))
+                    .collect();
+                if entries.is_empty() {
+                    println!("[]");
+                } else {
+                    println!("[{}\n]", entries.join(","));
+                }
+            } else {
+                for diff in &diffs {
+                    println!(
+                        "{:?}: {} -> {}",
+                        diff.member,
+                        diff.old.as_deref().unwrap_or("(absent)"),
+                        diff.new.as_deref().unwrap_or("(absent)")
+                    );
+                }
+            }
+            if !diffs.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Read {
+            ref input,
+            ref cargo,
+            json,
+            ref format,
+            all,
+            sbom,
+        }) => {
+            let format = match (json, format.as_deref()) {
+                (false, None) => "text",
+                (true, None) => "json",
+                (false, Some(format @ ("text" | "json" | "toml" | "yaml" | "env"))) => format,
+                (true, Some(_)) => {
+                    eprintln!("error: --json and --format are mutually exclusive");
+                    std::process::exit(1);
+                }
+                (false, Some(other)) => {
+                    eprintln!(
+                        "error: invalid --format: {other} (expected text, json, toml, yaml, or env)"
+                    );
+                    std::process::exit(1);
+                }
+            };
+            if all && !matches!(format, "text" | "json") {
+                eprintln!("error: --all only supports --format text or json");
+                std::process::exit(1);
+            }
+            let input = &resolve_input(input, cargo, false);
+            if sbom {
+                let data = SectionData::from_binary(input).unwrap_or_else(|e| {
+                    eprintln!("error: {e}");
+                    std::process::exit(1);
+                });
+                match data.sbom() {
+                    Ok(Some(sbom)) => std::io::stdout().write_all(&sbom).unwrap(),
+                    Ok(None) => {
+                        eprintln!("ver-shim: {} has no embedded SBOM", input.display());
+                        std::process::exit(1);
+                    }
+                    Err(e) => {
+                        eprintln!("error: {e}");
+                        std::process::exit(1);
+                    }
+                }
+            } else if all {
+                let sections = SectionData::all_from_binary(input).unwrap_or_else(|e| {
+                    eprintln!("error: {e}");
+                    std::process::exit(1);
+                });
+                if format == "json" {
+                    let entries: Vec<String> = sections
+                        .iter()
+                        .map(|(name, data)| {
+                            let object = data.to_json().trim_end().replace('\n', "\n  ");
+                            format!("\n  {:?}: {}", name, object)
+                        })
+                        .collect();
+                    println!("{{{}\n}}", entries.join(","));
+                } else {
+                    for (idx, (name, data)) in sections.iter().enumerate() {
+                        if idx > 0 {
+                            println!();
+                        }
+                        println!("[{}]", name);
+                        if data.is_empty() {
+                            println!("(no version info)");
+                        }
+                        print_members(data);
+                    }
+                }
+            } else {
+                let data = SectionData::from_binary(input).unwrap_or_else(|e| {
+                    eprintln!("error: {e}");
+                    std::process::exit(1);
+                });
+                match format {
+                    "json" => print!("{}", data.to_json()),
+                    "toml" => print!("{}", data.to_toml()),
+                    "yaml" => print!("{}", data.to_yaml()),
+                    "env" => print!("{}", data.to_env()),
+                    _ if data.is_empty() => {
+                        eprintln!("ver-shim: {} has no version info", input.display());
+                    }
+                    _ => print_members(&data),
+                }
+            }
+        }
+        Some(Command::Verify {
+            ref input,
+            ref expect_sha,
+            ref expect_describe,
+            ref expect_branch,
+        }) => {
+            let expectations = [
+                (Member::GitSha, expect_sha),
+                (Member::GitDescribe, expect_describe),
+                (Member::GitBranch, expect_branch),
+            ];
+            if expectations.iter().all(|(_, expected)| expected.is_none()) {
+                eprintln!(
+                    "error: verify needs at least one of --expect-sha, --expect-describe, --expect-branch"
+                );
+                std::process::exit(1);
+            }
+
+            let data = SectionData::from_binary(input).unwrap_or_else(|e| {
+                eprintln!("error: {e}");
+                std::process::exit(1);
+            });
+            let mut ok = true;
+            for (member, expected) in expectations {
+                let Some(expected) = expected else {
+                    continue;
+                };
+                let actual = data.get(member);
+                let matches = match actual {
+                    // Allow abbreviated SHAs
+                    Some(actual) if member == Member::GitSha => {
+                        expected.len() >= 4
+                            && actual
+                                .to_ascii_lowercase()
+                                .starts_with(&expected.to_ascii_lowercase())
+                    }
+                    Some(actual) => actual == expected.as_str(),
+                    None => false,
+                };
+                if !matches {
+                    eprintln!(
+                        "ver-shim: {:?} mismatch: expected {}, found {}",
+                        member,
+                        expected,
+                        actual.unwrap_or("(absent)")
+                    );
+                    ok = false;
+                }
+            }
+            if !ok {
+                std::process::exit(1);
+            }
+            eprintln!("ver-shim: {} matches", input.display());
+        }
+        None => {
+            if args.output.is_none() && args.build_args.is_none() && args.oci_labels.is_none() {
+                eprintln!(
+                    "error: --output, --build-args or --oci-labels is required when not using a subcommand"
+                );
+                std::process::exit(1);
+            }
+            let section = collect(&args);
+            if let Some(ref output) = args.output {
+                let output_path = section.write_to(output);
+                if !args.dry_run {
+                    eprintln!("ver-shim: wrote {}", output_path.display());
+                }
+            }
         }
     }
 }
@@ -0,0 +1,39 @@
+//! The cargo profile of the build, see [`profile()`](crate::profile).
+
+use crate::kv;
+
+/// The cargo profile which the binary was built with.
+///
+/// These are the `PROFILE`, `OPT_LEVEL`, and `DEBUG` variables that cargo passes to build
+/// scripts, stored as key-value pairs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BuildProfile {
+    /// `release` or `debug`. Custom profiles show up as the profile they inherit from.
+    pub profile: &'static str,
+    /// The optimization level, `0` to `3`, `s`, or `z`, if known.
+    pub opt_level: Option<&'static str>,
+    /// Whether debug info was enabled, `true` or `false`, if known.
+    pub debug: Option<&'static str>,
+}
+
+impl BuildProfile {
+    /// Returns true if this is an unoptimized build, e.g. a debug build that was shipped
+    /// by mistake.
+    pub fn is_unoptimized(&self) -> bool {
+        match self.opt_level {
+            Some(opt_level) => opt_level == "0",
+            None => self.profile == "debug",
+        }
+    }
+
+    /// Reads the data of the `BuildProfile` member.
+    ///
+    /// Returns `None` if the profile is missing.
+    pub(crate) fn parse(data: &'static str) -> Option<Self> {
+        Some(Self {
+            profile: kv::get(data, "profile")?,
+            opt_level: kv::get(data, "opt_level"),
+            debug: kv::get(data, "debug"),
+        })
+    }
+}
@@ -0,0 +1,58 @@
+//! CRC-32 of the section data, to detect a section clobbered by another tool.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Computes the CRC-32 (IEEE) of `data`.
+///
+/// This is the bitwise version without a lookup table, which keeps the binary small. The
+/// runtime only computes it once per section, see [`ChecksumCache`].
+#[doc(hidden)]
+pub const fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    let mut i = 0;
+    while i < data.len() {
+        crc ^= data[i] as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        i += 1;
+    }
+    !crc
+}
+
+const UNKNOWN: u8 = 0;
+const VALID: u8 = 1;
+const INVALID: u8 = 2;
+
+/// Remembers whether the checksum of a section matched, so that it is only computed on
+/// the first access.
+pub(crate) struct ChecksumCache(AtomicU8);
+
+impl ChecksumCache {
+    pub(crate) const fn new() -> Self {
+        Self(AtomicU8::new(UNKNOWN))
+    }
+
+    /// Returns whether the checksum matches, calling `verify` if it is not known yet.
+    ///
+    /// Several threads may race to call `verify`, which is harmless since they all get the
+    /// same answer.
+    pub(crate) fn is_valid(&self, verify: impl FnOnce() -> bool) -> bool {
+        match self.0.load(Ordering::Relaxed) {
+            VALID => true,
+            INVALID => false,
+            _ => {
+                let valid = verify();
+                self.0
+                    .store(if valid { VALID } else { INVALID }, Ordering::Relaxed);
+                valid
+            }
+        }
+    }
+}
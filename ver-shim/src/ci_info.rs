@@ -0,0 +1,31 @@
+//! The CI environment of the build, see [`ci_info()`](crate::ci_info).
+
+use crate::kv;
+
+/// The CI run which built the binary.
+///
+/// This is stored as key-value pairs, so that more fields can be added later without a
+/// new member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CiInfo {
+    /// The CI provider: `github-actions`, `gitlab-ci`, `buildkite`, or `jenkins`.
+    pub provider: &'static str,
+    /// The URL of the run or job which built the binary, if known.
+    pub run_url: Option<&'static str>,
+    /// The ID of the run, pipeline, or build, if known.
+    pub pipeline_id: Option<&'static str>,
+}
+
+impl CiInfo {
+    /// Reads the data of the `CiInfo` member.
+    ///
+    /// Returns `None` if the provider is missing, e.g. if the data was written by a newer
+    /// version of `ver-shim-build` which renamed it.
+    pub(crate) fn parse(data: &'static str) -> Option<Self> {
+        Some(Self {
+            provider: kv::get(data, "provider")?,
+            run_url: kv::get(data, "run_url"),
+            pipeline_id: kv::get(data, "pipeline_id"),
+        })
+    }
+}
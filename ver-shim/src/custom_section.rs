@@ -0,0 +1,107 @@
+//! Version data in a link section chosen by the application, see [`version_section!`].
+//!
+//! The functions at the crate root read the `.ver_shim_data` section, of which there is
+//! only one per binary. If several components linked into one binary (e.g. plugins, or
+//! crates from different workspaces) each need their own version data, each can declare
+//! its own section with [`version_section!`], and patch it with
+//! `LinkSection::with_section_name()` in `ver-shim-build`.
+
+use crate::{
+    ChecksumCache, Member, Members, VersionInfo, get_member_in, header_size, written_size_in,
+};
+
+/// Version data in a custom link section, declared with [`version_section!`].
+pub struct VersionSection {
+    buffer: &'static [u8],
+    checksum: ChecksumCache,
+}
+
+impl VersionSection {
+    #[doc(hidden)]
+    pub const fn new(buffer: &'static [u8]) -> Self {
+        assert!(
+            buffer.len() > header_size(Member::COUNT),
+            "the buffer of a version section must be larger than the section header"
+        );
+        Self {
+            buffer,
+            checksum: ChecksumCache::new(),
+        }
+    }
+
+    /// Returns the data of a member, if present.
+    ///
+    /// This is the equivalent of the functions at the crate root, e.g.
+    /// `section.get(Member::GitSha)` instead of [`git_sha()`](crate::git_sha).
+    pub fn get(&self, member: Member) -> Option<&'static str> {
+        get_member_in(self.buffer, &self.checksum, member)
+    }
+
+    /// Returns an iterator over the members of this section that are present, like
+    /// [`iter()`](crate::iter).
+    pub fn iter(&self) -> Members<'_> {
+        Members::new(self.buffer, &self.checksum)
+    }
+
+    /// Returns the size of the buffer that the data of this section was written for, if
+    /// known. See [`written_buffer_size()`](crate::written_buffer_size).
+    pub fn written_buffer_size(&self) -> Option<usize> {
+        written_size_in(self.buffer, &self.checksum)
+    }
+
+    /// Verifies the signature over the data of this section, like
+    /// [`verify_signature()`](crate::verify_signature). Requires the `signature` feature.
+    #[cfg(feature = "signature")]
+    pub fn verify_signature(&self, public_key: &[u8; 32]) -> Result<(), crate::SignatureError> {
+        crate::signature::verify_in(self.buffer, &self.checksum, public_key)
+    }
+
+    /// Hashes the running executable and compares it with the self hash of this section,
+    /// like [`verify_self_hash()`](crate::verify_self_hash). Requires the `self-hash`
+    /// feature.
+    #[cfg(feature = "self-hash")]
+    pub fn verify_self_hash(&self) -> Result<(), crate::SelfHashError> {
+        crate::self_hash::verify_in(self.buffer, &self.checksum)
+    }
+
+    /// Reads all members of this section, like [`VersionInfo::collect()`].
+    pub fn version_info(&self) -> VersionInfo {
+        VersionInfo::read(|member| self.get(member))
+    }
+}
+
+/// Declares a [`VersionSection`] static, placed in a link section with the given name.
+///
+/// ```ignore
+/// ver_shim::version_section! {
+///     /// Version data of the plugin.
+///     pub static PLUGIN_VERSION = ".plugin_version";
+/// }
+///
+/// let sha = PLUGIN_VERSION.get(ver_shim::Member::GitSha);
+/// ```
+///
+/// The buffer size defaults to `BUFFER_SIZE`, and can be set explicitly with
+/// `pub static PLUGIN_VERSION = ".plugin_version", 1024;`.
+///
+/// The section name is passed to the linker as is, so it must be valid for the target:
+/// on Apple targets it has the form `__DATA,__plugin_version`, and on Windows it should be
+/// at most 8 bytes long. Patch it by passing the same name to
+/// `LinkSection::with_section_name()` in `ver-shim-build`.
+#[macro_export]
+macro_rules! version_section {
+    ($(#[$attr:meta])* $vis:vis static $name:ident = $section:literal;) => {
+        $crate::version_section! {
+            $(#[$attr])* $vis static $name = $section, $crate::BUFFER_SIZE;
+        }
+    };
+    ($(#[$attr:meta])* $vis:vis static $name:ident = $section:literal, $size:expr;) => {
+        $(#[$attr])*
+        $vis static $name: $crate::VersionSection = {
+            #[unsafe(link_section = $section)]
+            #[used]
+            static BUFFER: [u8; $size] = $crate::unpatched_buffer();
+            $crate::VersionSection::new(&BUFFER)
+        };
+    };
+}
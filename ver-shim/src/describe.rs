@@ -0,0 +1,61 @@
+//! Parsing of `git describe` output into a semver version, for the `semver` feature.
+
+use alloc::vec::Vec;
+use semver::{BuildMetadata, Version};
+
+/// Parses `git describe` output like `v1.2.3-4-gabcdef-dirty` into a version.
+///
+/// The leading `v` of the tag is optional. The commits since the tag, the hash and the
+/// `-dirty` suffix become build metadata, e.g. `1.2.3+4.gabcdef.dirty`. Mercurial
+/// describe output (`v1.2.3-4-mabcdef`) is understood as well.
+///
+/// Returns `None` if the tag is not a semver version, or if there is no tag at all, in
+/// which case `git describe --always` outputs only the hash.
+pub(crate) fn parse(describe: &str) -> Option<Version> {
+    let (describe, dirty) = match describe.strip_suffix("-dirty") {
+        Some(rest) => (rest, true),
+        None => (describe, false),
+    };
+    let (tag, since_tag) = split_since_tag(describe);
+    let tag = tag.strip_prefix('v').unwrap_or(tag);
+    let mut version = Version::parse(tag).ok()?;
+
+    // A tag which already has build metadata is kept as is
+    if version.build.is_empty() {
+        let mut metadata: Vec<&str> = Vec::new();
+        if let Some((n, hash)) = since_tag {
+            metadata.extend([n, hash]);
+        }
+        if dirty {
+            metadata.push("dirty");
+        }
+        if !metadata.is_empty() {
+            version.build = BuildMetadata::new(&metadata.join(".")).ok()?;
+        }
+    }
+    Some(version)
+}
+
+/// Splits `tag-N-gHASH` into the tag and `(N, gHASH)`, if it has that form.
+///
+/// The tag itself may contain `-`, e.g. `v1.2.3-rc.1`, so this only splits off a suffix
+/// which looks like the number of commits and the hash.
+fn split_since_tag(describe: &str) -> (&str, Option<(&str, &str)>) {
+    let mut parts = describe.rsplitn(3, '-');
+    if let (Some(hash), Some(n), Some(tag)) = (parts.next(), parts.next(), parts.next())
+        && !n.is_empty()
+        && n.bytes().all(|b| b.is_ascii_digit())
+        && is_hash(hash)
+    {
+        return (tag, Some((n, hash)));
+    }
+    (describe, None)
+}
+
+/// Returns true for `g` (git) or `m` (Mercurial) followed by a hex hash.
+fn is_hash(s: &str) -> bool {
+    match s.strip_prefix('g').or_else(|| s.strip_prefix('m')) {
+        Some(hex) => !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit()),
+        None => false,
+    }
+}
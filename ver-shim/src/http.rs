@@ -0,0 +1,21 @@
+//! Serving the version data from an HTTP endpoint, for the `http` and `axum` features.
+
+use crate::VersionInfo;
+
+/// Returns the version data as JSON, e.g. for a `/healthz/version` endpoint.
+///
+/// This is [`VersionInfo`] serialized with `serde_json`: an object with one entry per
+/// field, where absent members are `null`.
+pub fn version_json() -> serde_json::Value {
+    serde_json::to_value(VersionInfo::collect()).expect("VersionInfo is always valid JSON")
+}
+
+/// An `axum` handler which returns [`version_json()`], for the `axum` feature.
+///
+/// ```ignore
+/// let app = Router::new().route("/healthz/version", get(ver_shim::http::version_handler));
+/// ```
+#[cfg(feature = "axum")]
+pub async fn version_handler() -> axum::Json<serde_json::Value> {
+    axum::Json(version_json())
+}
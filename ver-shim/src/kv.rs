@@ -0,0 +1,36 @@
+//! Key-value encoding used by members which hold a map.
+//!
+//! The member data is a sequence of NUL-terminated strings, alternating between
+//! keys and values: `key1\0value1\0key2\0value2\0`. Keys are sorted and unique.
+
+/// Gets the value of a key in an encoded member.
+pub(crate) fn get(data: &'static str, key: &str) -> Option<&'static str> {
+    Pairs::new(Some(data))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
+
+/// Iterator over the key-value pairs of an encoded member.
+#[derive(Clone)]
+pub(crate) struct Pairs {
+    rest: &'static str,
+}
+
+impl Pairs {
+    pub(crate) fn new(data: Option<&'static str>) -> Self {
+        Self {
+            rest: data.unwrap_or(""),
+        }
+    }
+}
+
+impl Iterator for Pairs {
+    type Item = (&'static str, &'static str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, rest) = self.rest.split_once('\0')?;
+        let (value, rest) = rest.split_once('\0')?;
+        self.rest = rest;
+        Some((key, value))
+    }
+}
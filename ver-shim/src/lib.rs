@@ -3,26 +3,106 @@
 //! This crate provides a way to access git version information that has been
 //! injected into the binary via the `.ver_shim_data` link section.
 //!
-//! The section format is:
-//! - First byte: number of members in the section (for forward compatibility)
+//! The section format (version 2) is:
+//! - First byte: zero
+//! - Next 4 bytes: the magic `VSHM`
+//! - Next byte: the format version, 2
+//! - Next byte: number of members in the section (for forward compatibility)
+//! - Next 2 bytes: the size of the buffer the section was written for (u16, little-endian)
+//! - Next 4 bytes: CRC-32 of the end offsets and the string data (u32, little-endian)
 //! - Next `num_members * 2` bytes: array of end offsets (u16, little-endian, relative to header)
 //! - Remaining bytes: concatenated string data
 //!
-//! Header size = 1 + num_members * 2
+//! Header size = 13 + num_members * 2
 //!
 //! For member N:
 //! - start = header_size + end[N-1] if N > 0, else header_size
 //! - end = header_size + end[N]
 //! - If start == end, the member is not present.
-//! - If N >= num_members (from first byte), the member is not present.
+//! - If N >= num_members, the member is not present.
 //!
-//! Using relative offsets means a zero-initialized buffer reads as "all members absent".
+//! Using relative offsets means a buffer with zero members reads as "all members absent".
 //! The num_members byte enables forward and backwards compatibility: old sections can be read by new code
 //! which has more members added in the future, and new sections can be read by old code as well,
 //! as long as we never change the index of any existing member.
+//!
+//! The checksum is verified on the first access to the section. If it doesn't match, e.g.
+//! because another post-processing tool overwrote the section, all members read as absent.
+//!
+//! The unpatched buffer already holds the header, with zero members, so that `ver-shim-build`
+//! can tell which format version the binary expects before patching it.
+//!
+//! Version 1, which is still read, has no magic or format version: the first byte is the
+//! number of members, followed by the end offsets and the string data (header size =
+//! 1 + num_members * 2). It may be followed by the size of the buffer (u16, little-endian)
+//! after the string data. A version 1 reader sees the zero first byte of a version 2
+//! section as "no members", so old binaries read newer sections as absent, not as garbage.
 
 #![no_std]
 
+#[cfg(any(feature = "clap", feature = "semver"))]
+extern crate alloc;
+
+#[cfg(feature = "self-hash")]
+extern crate std;
+
+mod kv;
+
+/// Parsing of `git describe` output.
+#[cfg(feature = "semver")]
+mod describe;
+
+/// The CI environment of the build.
+mod ci_info;
+pub use ci_info::CiInfo;
+
+/// The cargo profile of the build.
+mod build_profile;
+pub use build_profile::BuildProfile;
+
+/// CRC-32 of the section data.
+mod checksum;
+use checksum::ChecksumCache;
+#[doc(hidden)]
+pub use checksum::crc32;
+
+/// Version text for `clap`.
+#[cfg(feature = "clap")]
+mod long_version;
+
+/// A Prometheus `build_info` metric.
+mod prometheus;
+pub use prometheus::BuildInfoLine;
+
+/// Logging the version data with `tracing`.
+#[cfg(feature = "tracing")]
+pub mod tracing;
+
+/// Serving the version data over HTTP.
+#[cfg(feature = "http")]
+pub mod http;
+
+/// Version data in a link section chosen by the application.
+mod custom_section;
+pub use custom_section::VersionSection;
+
+/// All version data at once.
+mod version_info;
+pub use version_info::{Banner, VersionInfo};
+
+/// Verifying the signature over the version data.
+#[cfg(feature = "signature")]
+mod signature;
+#[cfg(feature = "signature")]
+pub use signature::SignatureError;
+
+/// The hash of the executable.
+mod self_hash;
+#[cfg(feature = "self-hash")]
+pub use self_hash::SelfHashError;
+#[doc(hidden)]
+pub use self_hash::{find_buffer as find_self_hash_buffer, mask_image as mask_self_hash_image};
+
 // Size of the version data buffer in bytes.
 // Can be overridden by setting VER_SHIM_BUFFER_SIZE env var at compile time.
 // Parsed as u16 since offsets in the header are u16 (max buffer size is 65535).
@@ -35,76 +115,345 @@ pub const BUFFER_SIZE: usize = match option_env!("VER_SHIM_BUFFER_SIZE") {
     None => 512,
 };
 
+// Magic bytes after the zero first byte of a version 2 section.
+#[doc(hidden)]
+pub const SECTION_MAGIC: [u8; 4] = *b"VSHM";
+
+// Prefix of the message signed by `LinkSection::with_signing_key()`, so that the
+// signature can't be passed off as one over anything else.
+#[doc(hidden)]
+pub const SIGNATURE_CONTEXT: &[u8] = b"ver-shim signature v1\0";
+
+// The latest section format version, which this crate reads and expects.
+#[doc(hidden)]
+pub const FORMAT_VERSION: u8 = 2;
+
+// Length of the fixed part of a version 2 header, before the end offsets:
+// zero byte, magic, format version, num_members, buffer size, checksum.
+#[doc(hidden)]
+pub const PREAMBLE_LEN: usize = 1 + SECTION_MAGIC.len() + 1 + 1 + 2 + 4;
+
 // Calculate header size for a given number of members.
-// Header = 1 byte (num_members) + 2 bytes per member (end offsets).
+// Header = preamble + 2 bytes per member (end offsets).
 #[doc(hidden)]
 pub const fn header_size(num_members: usize) -> usize {
+    PREAMBLE_LEN + num_members * 2
+}
+
+// Calculate header size of a version 1 section for a given number of members.
+// Header = 1 byte (num_members) + 2 bytes per member (end offsets).
+#[doc(hidden)]
+pub const fn header_size_v1(num_members: usize) -> usize {
     1 + num_members * 2
 }
 
-// Compile-time checks for buffer size validity.
-// We use 32 as a minimum threshold because:
-// - The header must fit (currently 19 bytes for 9 members)
-// - There must be room for actual data
-// - Anything smaller than 32 bytes is impractical
-// - We want to give clear error messages, so a simpler condition is better.
-const _: () = assert!(
-    header_size(Member::COUNT) <= 32,
-    "header_size(Member::COUNT) exceeds 32, these asserts must be updated"
-);
+// The contents of an unpatched buffer: a version 2 header with zero members.
+#[doc(hidden)]
+pub const fn unpatched_buffer<const N: usize>() -> [u8; N] {
+    let mut buf = [0u8; N];
+    let mut i = 0;
+    while i < SECTION_MAGIC.len() {
+        buf[1 + i] = SECTION_MAGIC[i];
+        i += 1;
+    }
+    buf[5] = FORMAT_VERSION;
+    let size = (N as u16).to_le_bytes();
+    buf[7] = size[0];
+    buf[8] = size[1];
+    buf
+}
+
+// Compile-time check for buffer size validity.
+// The header must fit, with room for actual data. This is checked against the header
+// size rather than a round number, so that adding members doesn't raise the minimum
+// any further than the header requires.
 const _: () = assert!(
-    BUFFER_SIZE > 32,
-    "VER_SHIM_BUFFER_SIZE must be greater than 32"
+    BUFFER_SIZE > header_size(Member::COUNT),
+    "VER_SHIM_BUFFER_SIZE is too small, it must be greater than the section header"
 );
 
+// Length of the footer after the string data of a version 1 section, which holds the
+// buffer size the section was written for.
+#[doc(hidden)]
+pub const SIZE_FOOTER_LEN: usize = 2;
+
 /// The section name used for version data.
 #[doc(hidden)]
 pub const SECTION_NAME: &str = ".ver_shim_data";
 
-// Members that can be stored in the version data.
+/// The section name used for version data on Windows targets.
+///
+/// PE images only have room for 8-byte section names, and linkers truncate longer
+/// ones, so `SECTION_NAME` can't be used there.
 #[doc(hidden)]
+pub const COFF_SECTION_NAME: &str = ".vershim";
+
+/// Members that can be stored in the version data.
+///
+/// The discriminant of each member is its index in the section header,
+/// and must never change. New members may be added in any release, so a `match`
+/// on a member needs a wildcard arm.
 #[repr(u16)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Member {
+    /// See [`git_sha()`].
     GitSha = 0,
+    /// See [`git_describe()`].
     GitDescribe = 1,
+    /// See [`git_branch()`].
     GitBranch = 2,
+    /// See [`git_commit_timestamp()`].
     GitCommitTimestamp = 3,
+    /// See [`git_commit_date()`].
     GitCommitDate = 4,
+    /// See [`git_commit_msg()`].
     GitCommitMsg = 5,
+    /// See [`build_timestamp()`].
     BuildTimestamp = 6,
+    /// See [`build_date()`].
     BuildDate = 7,
+    /// See [`custom()`].
     Custom = 8,
+    /// See [`license_info()`].
+    LicenseInfo = 9,
+    /// See [`crate_entries()`].
+    CrateEntries = 10,
+    /// See [`git_commit_author()`].
+    GitCommitAuthor = 11,
+    /// See [`git_committer()`].
+    GitCommitter = 12,
+    /// See [`custom_kv()`].
+    CustomKv = 13,
+    /// See [`pkg_version()`].
+    PkgVersion = 14,
+    /// See [`pkg_name()`].
+    PkgName = 15,
+    /// See [`target_triple()`].
+    TargetTriple = 16,
+    /// See [`git_tag()`].
+    GitTag = 17,
+    /// See [`git_dirty()`].
+    GitDirty = 18,
+    /// See [`workspace_members()`].
+    WorkspaceMembers = 19,
+    /// See [`lockfile_hash()`].
+    LockfileHash = 20,
+    /// See [`sbom_digest()`].
+    SbomDigest = 21,
+    /// See [`embedded_sbom()`].
+    Sbom = 22,
+    /// See [`ci_info()`].
+    CiInfo = 23,
+    /// See [`build_host()`].
+    BuildHost = 24,
+    /// See [`profile()`].
+    BuildProfile = 25,
+    /// See [`submodules()`].
+    GitSubmodules = 26,
+    /// See [`git_remote()`].
+    GitRemote = 27,
+    /// See [`verify_signature()`].
+    Signature = 28,
+    /// See [`self_hash()`].
+    SelfHash = 29,
 }
 
 impl Member {
     /// Number of members in the version data.
     #[doc(hidden)]
-    pub const COUNT: usize = 9;
+    pub const COUNT: usize = 30;
+
+    /// All members, in header order.
+    pub const ALL: [Member; Member::COUNT] = [
+        Member::GitSha,
+        Member::GitDescribe,
+        Member::GitBranch,
+        Member::GitCommitTimestamp,
+        Member::GitCommitDate,
+        Member::GitCommitMsg,
+        Member::BuildTimestamp,
+        Member::BuildDate,
+        Member::Custom,
+        Member::LicenseInfo,
+        Member::CrateEntries,
+        Member::GitCommitAuthor,
+        Member::GitCommitter,
+        Member::CustomKv,
+        Member::PkgVersion,
+        Member::PkgName,
+        Member::TargetTriple,
+        Member::GitTag,
+        Member::GitDirty,
+        Member::WorkspaceMembers,
+        Member::LockfileHash,
+        Member::SbomDigest,
+        Member::Sbom,
+        Member::CiInfo,
+        Member::BuildHost,
+        Member::BuildProfile,
+        Member::GitSubmodules,
+        Member::GitRemote,
+        Member::Signature,
+        Member::SelfHash,
+    ];
+
+    /// The name of this member, e.g. `git_sha`.
+    ///
+    /// This is the key of the member in `ver-shim read --json` and the other output
+    /// formats, and in the Python bindings. In upper case, it names the member in env
+    /// files and build args. It never changes.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Member::GitSha => "git_sha",
+            Member::GitDescribe => "git_describe",
+            Member::GitBranch => "git_branch",
+            Member::GitCommitTimestamp => "git_commit_timestamp",
+            Member::GitCommitDate => "git_commit_date",
+            Member::GitCommitMsg => "git_commit_msg",
+            Member::BuildTimestamp => "build_timestamp",
+            Member::BuildDate => "build_date",
+            Member::Custom => "custom",
+            Member::LicenseInfo => "license_info",
+            Member::CrateEntries => "crate_entries",
+            Member::GitCommitAuthor => "git_commit_author",
+            Member::GitCommitter => "git_committer",
+            Member::CustomKv => "custom_kv",
+            Member::PkgVersion => "pkg_version",
+            Member::PkgName => "pkg_name",
+            Member::TargetTriple => "target_triple",
+            Member::GitTag => "git_tag",
+            Member::GitDirty => "git_dirty",
+            Member::WorkspaceMembers => "workspace_members",
+            Member::LockfileHash => "lockfile_hash",
+            Member::SbomDigest => "sbom_digest",
+            Member::Sbom => "sbom",
+            Member::CiInfo => "ci_info",
+            Member::BuildHost => "build_host",
+            Member::BuildProfile => "build_profile",
+            Member::GitSubmodules => "git_submodules",
+            Member::GitRemote => "git_remote",
+            Member::Signature => "signature",
+            Member::SelfHash => "self_hash",
+        }
+    }
+
+    /// Looks up a member by its [`name()`](Member::name).
+    pub fn from_name(name: &str) -> Option<Member> {
+        Member::ALL.into_iter().find(|member| member.name() == name)
+    }
+
+    /// Returns true if this member holds key-value pairs rather than a single string.
+    pub const fn is_map(self) -> bool {
+        matches!(
+            self,
+            Member::CrateEntries
+                | Member::CustomKv
+                | Member::WorkspaceMembers
+                | Member::CiInfo
+                | Member::BuildProfile
+                | Member::GitSubmodules
+        )
+    }
 }
 
+/// Magic bytes preceding the buffer when the `locator` feature is enabled.
+#[doc(hidden)]
+pub const LOCATOR_MAGIC: [u8; 14] = *b"\xffVER_SHIM_DATA";
+
+/// Length of the locator prefix: the magic bytes, then the buffer size (u16, little-endian).
+#[doc(hidden)]
+pub const LOCATOR_PREFIX_LEN: usize = LOCATOR_MAGIC.len() + 2;
+
 /// Static buffer for version data, placed in a custom link section.
 //
 // Note: We use "links" in the cargo toml for this crate to try to ensure that
 // only one version of this crate appears in the build graph, and so only one
 // version of the BUFFER exists, and BUFFER_SIZE = section size.
-#[unsafe(link_section = ".ver_shim_data")]
+//
+// Mach-O section names are `segment,section`, so on Apple targets the buffer goes in
+// `__DATA,__ver_shim_data` instead. On Windows it goes in `COFF_SECTION_NAME`.
+#[cfg(not(any(feature = "locator", target_family = "wasm")))]
+#[cfg_attr(
+    not(any(target_vendor = "apple", windows)),
+    unsafe(link_section = ".ver_shim_data")
+)]
+#[cfg_attr(windows, unsafe(link_section = ".vershim"))]
+#[cfg_attr(
+    target_vendor = "apple",
+    unsafe(link_section = "__DATA,__ver_shim_data")
+)]
+#[used]
+static BUFFER: [u8; BUFFER_SIZE] = unpatched_buffer();
+
+/// Buffer for version data preceded by a magic prefix, for the `locator` feature.
+#[cfg(any(feature = "locator", target_family = "wasm"))]
+#[repr(C)]
+struct Located {
+    prefix: [u8; LOCATOR_PREFIX_LEN],
+    data: [u8; BUFFER_SIZE],
+}
+
+#[cfg(any(feature = "locator", target_family = "wasm"))]
+const fn locator_prefix() -> [u8; LOCATOR_PREFIX_LEN] {
+    let mut prefix = [0u8; LOCATOR_PREFIX_LEN];
+    let mut i = 0;
+    while i < LOCATOR_MAGIC.len() {
+        prefix[i] = LOCATOR_MAGIC[i];
+        i += 1;
+    }
+    let size = (BUFFER_SIZE as u16).to_le_bytes();
+    prefix[i] = size[0];
+    prefix[i + 1] = size[1];
+    prefix
+}
+
+/// Static buffer for version data, exported as `VER_SHIM_DATA` instead of being placed
+/// in a custom link section.
+//
+// Some linkers rename or merge custom sections. With the `locator` feature, the patcher
+// finds the buffer by scanning the file for the magic prefix instead.
+//
+// On WebAssembly this is always used: a `link_section` there becomes a custom section,
+// which is not part of linear memory and can't be read by the module itself. The
+// buffer is in a data segment instead, whose bytes are stored as is in the `.wasm` file.
+#[cfg(any(feature = "locator", target_family = "wasm"))]
+#[unsafe(no_mangle)]
 #[used]
-static BUFFER: [u8; BUFFER_SIZE] = [0u8; BUFFER_SIZE];
+static VER_SHIM_DATA: Located = Located {
+    prefix: locator_prefix(),
+    data: unpatched_buffer(),
+};
+
+// Whether the checksum of the buffer matched, once it was read.
+static CHECKSUM: ChecksumCache = ChecksumCache::new();
+
+#[cfg(not(any(feature = "locator", target_family = "wasm")))]
+#[inline(always)]
+fn buffer() -> &'static [u8; BUFFER_SIZE] {
+    &BUFFER
+}
+
+#[cfg(any(feature = "locator", target_family = "wasm"))]
+#[inline(always)]
+fn buffer() -> &'static [u8; BUFFER_SIZE] {
+    &VER_SHIM_DATA.data
+}
 
-// Reads a byte from the buffer using volatile read to prevent optimization.
-// This is necessary because the compiler would otherwise inline the zeros
-// since the buffer is initialized to all zeros at compile time.
+// Reads a byte from a buffer using volatile read to prevent optimization.
+// This is necessary because the compiler would otherwise inline the unpatched
+// contents, since the buffer is initialized at compile time.
 #[inline(never)]
-fn read_buffer_byte(index: usize) -> u8 {
-    // SAFETY: index is bounds-checked by caller, BUFFER is static
-    unsafe { core::ptr::read_volatile(BUFFER.as_ptr().add(index)) }
+fn read_buffer_byte(buf: &'static [u8], index: usize) -> u8 {
+    assert!(index < buf.len(), "ver-shim: index {} out of bounds", index);
+    // SAFETY: index is bounds-checked above, the buffer is static
+    unsafe { core::ptr::read_volatile(buf.as_ptr().add(index)) }
 }
 
-// Reads a u16 from the buffer at the given offset (little-endian).
-fn read_buffer_u16(offset: usize) -> u16 {
-    let lo = read_buffer_byte(offset) as u16;
-    let hi = read_buffer_byte(offset + 1) as u16;
+// Reads a u16 from a buffer at the given offset (little-endian).
+fn read_buffer_u16(buf: &'static [u8], offset: usize) -> u16 {
+    let lo = read_buffer_byte(buf, offset) as u16;
+    let hi = read_buffer_byte(buf, offset + 1) as u16;
     lo | (hi << 8)
 }
 
@@ -117,36 +466,161 @@ fn read_buffer_u16(offset: usize) -> u16 {
 // Panics:
 // - If end < start (invalid range)
 // - If end > BUFFER_SIZE (out of bounds)
+// - If the section was written for a larger buffer
+// - If the section has a format version which this crate doesn't know
 // - If the data is not valid UTF-8
 fn get_member(member: Member) -> Option<&'static str> {
-    let idx = member as usize;
+    get_member_in(buffer(), &CHECKSUM, member)
+}
+
+// The header of a section, in either format version.
+struct Header {
+    // Number of members in the section
+    num_members: usize,
+    // Position of the end offsets
+    offsets_pos: usize,
+    // Size of the header, which the end offsets are relative to
+    size: usize,
+    // The buffer size the section was written for, if known
+    written_size: Option<usize>,
+}
+
+// Reads the header of a version buffer.
+//
+// Returns `None` if the section has no members, e.g. because it is unpatched, or if its
+// checksum doesn't match. Panics if the section has a format version which this crate
+// doesn't know.
+fn read_header(buf: &'static [u8], checksum: &ChecksumCache) -> Option<Header> {
+    let first = read_buffer_byte(buf, 0) as usize;
+
+    // Version 1: the first byte is the number of members
+    if first != 0 {
+        let size = header_size_v1(first);
+        let written_size = written_size_v1(buf, first, size);
+        return Some(Header {
+            num_members: first,
+            offsets_pos: 1,
+            size,
+            written_size,
+        });
+    }
+
+    // An all zero buffer, e.g. a version 1 buffer that was never patched
+    let mut i = 0;
+    while i < SECTION_MAGIC.len() {
+        if read_buffer_byte(buf, 1 + i) != SECTION_MAGIC[i] {
+            return None;
+        }
+        i += 1;
+    }
 
-    // Read the actual number of members from the first byte
-    let actual_num_members = read_buffer_byte(0) as usize;
+    let version = read_buffer_byte(buf, 5);
+    if version != FORMAT_VERSION {
+        panic!(
+            "ver-shim: section format version {} is not supported by this version of ver-shim, \
+             which reads version {}. Check that ver-shim and ver-shim-build are up to date.",
+            version, FORMAT_VERSION
+        );
+    }
+    let num_members = read_buffer_byte(buf, 6) as usize;
+    if num_members == 0 {
+        return None;
+    }
+    let header = Header {
+        num_members,
+        offsets_pos: PREAMBLE_LEN,
+        size: header_size(num_members),
+        written_size: Some(read_buffer_u16(buf, 7) as usize),
+    };
+
+    // Data written for a larger buffer doesn't fit, `get_member_in()` panics for that
+    // with a better message than a checksum mismatch
+    if header.written_size.is_some_and(|size| size > buf.len())
+        || checksum.is_valid(|| checksum_matches(buf, &header))
+    {
+        Some(header)
+    } else {
+        None
+    }
+}
+
+// Checks the CRC-32 of the end offsets and the string data of a version 2 section.
+fn checksum_matches(buf: &'static [u8], header: &Header) -> bool {
+    if header.size > buf.len() {
+        return false;
+    }
+    let expected = read_buffer_u16(buf, 9) as u32 | (read_buffer_u16(buf, 11) as u32) << 16;
+    let last_end_pos = header.offsets_pos + (header.num_members - 1) * 2;
+    let end = header.size + read_buffer_u16(buf, last_end_pos) as usize;
+    if end > buf.len() {
+        return false;
+    }
+    // Use black_box so that the compiler can't compute this from the unpatched contents
+    let bytes = core::hint::black_box(&buf[header.offsets_pos..end]);
+    crc32(bytes) == expected
+}
 
-    // If first byte is 0, section is uninitialized (all zeros)
-    if actual_num_members == 0 {
+// Reads the buffer size a version 1 section was written for, from the footer after the
+// string data.
+//
+// Returns `None` if the section was written by a version of ver-shim-build which didn't
+// write the footer. Such a version may also have filled the buffer up to the end, leaving
+// no room for the footer.
+fn written_size_v1(buf: &'static [u8], num_members: usize, header_size: usize) -> Option<usize> {
+    let footer = header_size + read_buffer_u16(buf, 1 + (num_members - 1) * 2) as usize;
+    if footer + SIZE_FOOTER_LEN > buf.len() {
         return None;
     }
+    match read_buffer_u16(buf, footer) as usize {
+        0 => None,
+        size => Some(size),
+    }
+}
+
+// Reads a member from a version buffer, see `get_member()`.
+fn get_member_in(
+    buf: &'static [u8],
+    checksum: &ChecksumCache,
+    member: Member,
+) -> Option<&'static str> {
+    let header = read_header(buf, checksum)?;
+    let bytes = member_bytes(buf, &header, member as usize)?;
+    match core::str::from_utf8(bytes) {
+        Ok(s) => Some(s),
+        Err(e) => panic!("ver-shim: invalid UTF-8 for {:?}: {:?}", member as u16, e),
+    }
+}
 
+// Reads the data of the member with index `idx`, which may be a member that this crate
+// doesn't know yet. See `get_member()` for when this panics.
+fn member_bytes(buf: &'static [u8], header: &Header, idx: usize) -> Option<&'static [u8]> {
     // Forward compatibility: if requested member >= actual num_members, return None
-    if idx >= actual_num_members {
+    if idx >= header.num_members {
         return None;
     }
 
-    // Compute header size based on actual number of members in the section
-    let actual_header_size = header_size(actual_num_members);
+    // Data written for a larger buffer may have been cut off
+    if let Some(size) = header.written_size
+        && size > buf.len()
+    {
+        panic!(
+            "ver-shim: section was written for a {} byte buffer, but the buffer is {} bytes. \
+             Check that VER_SHIM_BUFFER_SIZE matches the buffer size used when patching.",
+            size,
+            buf.len()
+        );
+    }
 
-    // Read end offset for this member (stored at byte 1 + idx * 2, relative to header)
-    let end_offset_pos = 1 + idx * 2;
-    let end = actual_header_size + read_buffer_u16(end_offset_pos) as usize;
+    // Read end offset for this member (relative to header)
+    let end_offset_pos = header.offsets_pos + idx * 2;
+    let end = header.size + read_buffer_u16(buf, end_offset_pos) as usize;
 
-    // Calculate start: header_size + previous member's end, or header_size for member 0
+    // Calculate start: header size + previous member's end, or header size for member 0
     let start = if idx == 0 {
-        actual_header_size
+        header.size
     } else {
-        let prev_end_pos = 1 + (idx - 1) * 2;
-        actual_header_size + read_buffer_u16(prev_end_pos) as usize
+        let prev_end_pos = header.offsets_pos + (idx - 1) * 2;
+        header.size + read_buffer_u16(buf, prev_end_pos) as usize
     };
 
     // If start == end, member is not present
@@ -158,29 +632,142 @@ fn get_member(member: Member) -> Option<&'static str> {
     if end < start {
         panic!(
             "ver-shim: invalid range for {:?}: start={}, end={}",
-            member as u16, start, end
+            idx, start, end
         );
     }
-    if end > BUFFER_SIZE {
+    if end > buf.len() {
         panic!(
             "ver-shim: end offset {} exceeds buffer size {} for {:?}",
-            end, BUFFER_SIZE, member as u16
+            end,
+            buf.len(),
+            idx
         );
     }
 
-    // Get the slice and convert to UTF-8.
     // Use black_box to prevent the compiler from optimizing away the read,
-    // since the buffer is initialized to zeros at compile time, but changed at link time.
-    let bytes = core::hint::black_box(&BUFFER[start..end]);
-    match core::str::from_utf8(bytes) {
-        Ok(s) => Some(s),
-        Err(e) => panic!("ver-shim: invalid UTF-8 for {:?}: {:?}", member as u16, e),
+    // since the buffer is initialized at compile time, but changed at link time.
+    Some(core::hint::black_box(&buf[start..end]))
+}
+
+// Reads the buffer size the section was written for, if known.
+fn written_size_in(buf: &'static [u8], checksum: &ChecksumCache) -> Option<usize> {
+    read_header(buf, checksum)?.written_size
+}
+
+/// Returns the size of the buffer that the version data was written for, if known.
+///
+/// `ver-shim-build` records this in the section, so that a mismatch between the buffer
+/// size it used (`LinkSection::with_buffer_size()` or `VER_SHIM_BUFFER_SIZE`) and the one
+/// this crate was built with (`VER_SHIM_BUFFER_SIZE`, 512 by default) can be detected.
+/// If it is larger, the data may have been cut off, and reading members panics.
+///
+/// Returns `None` if the section was never patched, was patched by an older
+/// `ver-shim-build`, or its checksum doesn't match.
+pub fn written_buffer_size() -> Option<usize> {
+    written_size_in(buffer(), &CHECKSUM)
+}
+
+/// Verifies the signature over the version data with an ed25519 public key.
+///
+/// Sign the data at build time with `LinkSection::with_signing_key()`, which reports the
+/// public key of the signing key. Then this confirms that the version data wasn't
+/// changed after the build, e.g. by patching the binary with other data. Requires the
+/// `signature` feature.
+#[cfg(feature = "signature")]
+pub fn verify_signature(public_key: &[u8; 32]) -> Result<(), SignatureError> {
+    signature::verify_in(buffer(), &CHECKSUM, public_key)
+}
+
+/// Hashes the running executable and compares it with [`self_hash()`].
+///
+/// This reads the executable from [`std::env::current_exe()`], so a binary can detect
+/// that it was modified after it was patched, e.g. as an anti-tamper check at startup.
+/// It reads the whole file, so call it once rather than on every request. Requires the
+/// `self-hash` feature, which needs `std`.
+#[cfg(feature = "self-hash")]
+pub fn verify_self_hash() -> Result<(), SelfHashError> {
+    self_hash::verify_in(buffer(), &CHECKSUM)
+}
+
+/// Returns multi-line version text for `clap::Command::long_version()`.
+///
+/// The first line is the package version, git describe output or git SHA, whichever is
+/// present first, followed by the git describe output, SHA, branch and build date on
+/// separate lines, e.g.
+///
+/// ```text
+/// 1.2.0
+/// describe  v1.2.0-3-g1a2b3c4
+/// commit    1a2b3c4d5e6f...
+/// branch    main
+/// built     2024-06-01
+/// ```
+///
+/// `long_version()` takes a `String` with clap's `string` feature, otherwise leak it with
+/// `String::leak()`, which is fine since it's only done once. Requires the `clap`
+/// feature, which only needs `alloc`, not `clap` itself.
+#[cfg(feature = "clap")]
+pub fn clap_long_version() -> alloc::string::String {
+    long_version::format(&VersionInfo::collect())
+}
+
+/// Returns a Prometheus `build_info` metric line with the version data as labels.
+///
+/// Add it to a metrics endpoint with `writeln!(out, "{}", ...)`, e.g.
+/// `prometheus_build_info_line("myapp_build_info")` gives
+/// `myapp_build_info{version="1.2.0",git_sha="1a2b...",branch="main"} 1`.
+/// See [`BuildInfoLine`] for the labels.
+pub fn prometheus_build_info_line(metric_name: &str) -> BuildInfoLine<'_> {
+    VersionInfo::collect().prometheus_build_info(metric_name)
+}
+
+/// Returns an iterator over the members that are present, in header order.
+///
+/// This is useful to print all the version data without listing the members, and
+/// members added in future versions show up automatically. Members which hold a map
+/// (see [`Member::is_map()`]) yield their encoded data, use [`custom_kvs()`],
+/// [`crate_entries()`], [`workspace_members()`], [`submodules()`], [`ci_info()`], and
+/// [`profile()`] to read those.
+pub fn iter() -> Members<'static> {
+    Members::new(buffer(), &CHECKSUM)
+}
+
+/// Iterator returned by [`iter()`] and [`VersionSection::iter()`].
+#[derive(Clone)]
+pub struct Members<'a> {
+    buf: &'static [u8],
+    checksum: &'a ChecksumCache,
+    next: usize,
+}
+
+impl<'a> Members<'a> {
+    fn new(buf: &'static [u8], checksum: &'a ChecksumCache) -> Self {
+        Self {
+            buf,
+            checksum,
+            next: 0,
+        }
+    }
+}
+
+impl Iterator for Members<'_> {
+    type Item = (Member, &'static str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&member) = Member::ALL.get(self.next) {
+            self.next += 1;
+            if let Some(data) = get_member_in(self.buf, self.checksum, member) {
+                return Some((member, data));
+            }
+        }
+        None
     }
 }
 
 /// Returns the git SHA, if present.
 ///
-/// This is the full SHA from `git rev-parse HEAD`.
+/// This is the full SHA from `git rev-parse HEAD`, or its first few digits with
+/// `LinkSection::with_git_short_sha()`.
 pub fn git_sha() -> Option<&'static str> {
     get_member(Member::GitSha)
 }
@@ -192,10 +779,26 @@ pub fn git_sha() -> Option<&'static str> {
 /// - Number of commits since that tag
 /// - Abbreviated commit hash
 /// - `-dirty` suffix if there are uncommitted changes
+///
+/// The tags considered, the hash length and the suffix can be changed with
+/// `LinkSection::with_git_describe_opts()`.
 pub fn git_describe() -> Option<&'static str> {
     get_member(Member::GitDescribe)
 }
 
+/// Returns the version from the git describe output, if present and the tag is a semver
+/// version.
+///
+/// A leading `v` is stripped from the tag, and the number of commits since the tag, the
+/// hash and `-dirty` become build metadata, so `v1.2.3-4-gabcdef-dirty` is parsed as
+/// `1.2.3+4.gabcdef.dirty`. Note that semver ignores build metadata when comparing
+/// versions, so this compares equal to the tagged release. Only the default `-dirty`
+/// suffix is understood. Requires the `semver` feature.
+#[cfg(feature = "semver")]
+pub fn semver() -> Option<semver::Version> {
+    git_describe().and_then(describe::parse)
+}
+
 /// Returns the git branch name, if present.
 ///
 /// This is the output of `git rev-parse --abbrev-ref HEAD`.
@@ -203,10 +806,38 @@ pub fn git_branch() -> Option<&'static str> {
     get_member(Member::GitBranch)
 }
 
+/// Returns the git tag pointing at the commit, if present.
+///
+/// This is only present if HEAD was exactly at a tag when the binary was built, so it
+/// distinguishes a release build of `v1.2.3` from a dev snapshot, without parsing
+/// [`git_describe()`]. If several tags point at HEAD, this is the highest version.
+pub fn git_tag() -> Option<&'static str> {
+    get_member(Member::GitTag)
+}
+
+/// Returns whether the git work tree had uncommitted changes, if present.
+///
+/// Like the `-dirty` suffix of [`git_describe()`], this only considers changes to
+/// tracked files. It is stored as `true` or `false`.
+pub fn git_dirty() -> Option<bool> {
+    get_member(Member::GitDirty).map(parse_git_dirty)
+}
+
+// Parses the data of the `GitDirty` member.
+fn parse_git_dirty(value: &str) -> bool {
+    match value {
+        "true" => true,
+        "false" => false,
+        other => panic!("ver-shim: invalid value for git dirty: {:?}", other),
+    }
+}
+
 /// Returns the git commit timestamp, if present.
 ///
 /// This is the author date of HEAD formatted as RFC 3339
-/// (e.g., `2024-01-15T10:30:00+00:00`).
+/// (e.g., `2024-01-15T10:30:00+00:00`), unless another format was chosen with
+/// `LinkSection::with_git_commit_timestamp_format()`. It is the committer date instead
+/// with `LinkSection::with_git_timestamp_source()`.
 pub fn git_commit_timestamp() -> Option<&'static str> {
     get_member(Member::GitCommitTimestamp)
 }
@@ -214,7 +845,7 @@ pub fn git_commit_timestamp() -> Option<&'static str> {
 /// Returns the git commit date, if present.
 ///
 /// This is the author date of HEAD formatted as a date only
-/// (e.g., `2024-01-15`).
+/// (e.g., `2024-01-15`), or the committer date, like [`git_commit_timestamp()`].
 pub fn git_commit_date() -> Option<&'static str> {
     get_member(Member::GitCommitDate)
 }
@@ -227,10 +858,46 @@ pub fn git_commit_msg() -> Option<&'static str> {
     get_member(Member::GitCommitMsg)
 }
 
+/// Returns the author of the git commit, if present.
+///
+/// This is formatted as `Name <email>`. Depending on the build configuration,
+/// the email may be replaced by just its username part, or by a salted hash
+/// (see `LinkSection::with_email_privacy()`).
+pub fn git_commit_author() -> Option<&'static str> {
+    get_member(Member::GitCommitAuthor)
+}
+
+/// Returns the committer of the git commit, if present.
+///
+/// This has the same format as [`git_commit_author()`].
+pub fn git_committer() -> Option<&'static str> {
+    get_member(Member::GitCommitter)
+}
+
+/// Returns the URL of the `origin` remote of the repository, if present.
+///
+/// Set this using `LinkSection::with_git_remote_url()`, e.g.
+/// `https://github.com/org/repo.git` or `git@github.com:org/repo.git`. Credentials are
+/// stripped from the URL at build time. When binaries circulate between forks, this
+/// tells which repository produced one.
+pub fn git_remote() -> Option<&'static str> {
+    get_member(Member::GitRemote)
+}
+
+/// Returns the SHA-256 of the executable, in hex, if present.
+///
+/// Set this using `UpdateSectionCommand::with_self_hash()`. The hash covers the patched
+/// file with the version data zeroed, so it can't be compared with `sha256sum`; use
+/// [`verify_self_hash()`] to check it.
+pub fn self_hash() -> Option<&'static str> {
+    get_member(Member::SelfHash)
+}
+
 /// Returns the build timestamp, if present.
 ///
 /// This is the time the binary was built, formatted as RFC 3339
-/// (e.g., `2024-01-15T10:30:00Z`).
+/// (e.g., `2024-01-15T10:30:00Z`), unless another format was chosen with
+/// `LinkSection::with_build_timestamp_format()`.
 pub fn build_timestamp() -> Option<&'static str> {
     get_member(Member::BuildTimestamp)
 }
@@ -243,6 +910,42 @@ pub fn build_date() -> Option<&'static str> {
     get_member(Member::BuildDate)
 }
 
+/// Returns the git commit timestamp parsed as a `chrono::DateTime`, if present.
+///
+/// See [`git_commit_timestamp()`]. Requires the `chrono` feature. Panics if the timestamp
+/// is neither RFC 3339 nor a unix timestamp.
+#[cfg(feature = "chrono")]
+pub fn git_commit_datetime() -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    git_commit_timestamp().map(|s| parse_datetime(Member::GitCommitTimestamp, s))
+}
+
+/// Returns the build timestamp parsed as a `chrono::DateTime`, if present.
+///
+/// See [`build_timestamp()`]. Requires the `chrono` feature. Panics if the timestamp is
+/// neither RFC 3339 nor a unix timestamp.
+#[cfg(feature = "chrono")]
+pub fn build_datetime() -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    build_timestamp().map(|s| parse_datetime(Member::BuildTimestamp, s))
+}
+
+// Parses the data of a timestamp member, which ver-shim-build writes as RFC 3339 by
+// default, or as a unix timestamp with `TimestampFormat::Unix`.
+#[cfg(feature = "chrono")]
+fn parse_datetime(member: Member, value: &str) -> chrono::DateTime<chrono::FixedOffset> {
+    if let Ok(secs) = value.parse::<i64>()
+        && let Some(datetime) = chrono::DateTime::from_timestamp(secs, 0)
+    {
+        return datetime.fixed_offset();
+    }
+    match chrono::DateTime::parse_from_rfc3339(value) {
+        Ok(datetime) => datetime,
+        Err(e) => panic!(
+            "ver-shim: invalid RFC 3339 timestamp for {:?}: {:?}: {}",
+            member, value, e
+        ),
+    }
+}
+
 /// Returns the custom application-specific string, if present.
 ///
 /// This can be any string your application wants to embed into the binary.
@@ -250,3 +953,221 @@ pub fn build_date() -> Option<&'static str> {
 pub fn custom() -> Option<&'static str> {
     get_member(Member::Custom)
 }
+
+/// Returns the license/copyright string, if present.
+///
+/// This is a short attribution line, such as `MIT OR Apache-2.0`, or an explicit
+/// string like `Copyright (c) 2024 Example Corp. Licensed under MIT.`
+/// Set it using `LinkSection::with_license_info()` in your build script.
+pub fn license_info() -> Option<&'static str> {
+    get_member(Member::LicenseInfo)
+}
+
+/// Returns the cargo package version, if present.
+///
+/// This is `CARGO_PKG_VERSION` of the package whose build script collected the
+/// version data (e.g., `1.2.3`). Set it using `LinkSection::with_cargo_pkg_version()`.
+pub fn pkg_version() -> Option<&'static str> {
+    get_member(Member::PkgVersion)
+}
+
+/// Returns the cargo package name, if present.
+///
+/// This is `CARGO_PKG_NAME` of the package whose build script collected the
+/// version data. Set it using `LinkSection::with_cargo_pkg_name()`.
+pub fn pkg_name() -> Option<&'static str> {
+    get_member(Member::PkgName)
+}
+
+/// Returns the target triple the binary was built for, if present.
+///
+/// This is `TARGET` in the build script (e.g., `x86_64-unknown-linux-gnu`).
+/// Set it using `LinkSection::with_cargo_target_triple()`.
+pub fn target_triple() -> Option<&'static str> {
+    get_member(Member::TargetTriple)
+}
+
+/// Returns the machine which built the binary, if present.
+///
+/// This is the hostname, operating system, and architecture of the build machine, e.g.
+/// `ci-runner-7 (linux x86_64)`, to tell apart binaries built on CI and on a laptop.
+/// It is opt-in, since hostnames can be sensitive. Set it using
+/// `LinkSection::with_build_host()`.
+pub fn build_host() -> Option<&'static str> {
+    get_member(Member::BuildHost)
+}
+
+/// Returns the SHA-256 of the `Cargo.lock` file of the build, if present.
+///
+/// This is 64 lowercase hex digits, as printed by `sha256sum Cargo.lock`. Together with
+/// the git SHA, it pins the exact dependency versions, even if the lockfile isn't
+/// committed. Set it using `LinkSection::with_lockfile_hash()`.
+pub fn lockfile_hash() -> Option<&'static str> {
+    get_member(Member::LockfileHash)
+}
+
+/// Returns the format and SHA-256 of the SBOM generated for the binary, if present.
+///
+/// This has the form `<format> sha256:<hex>`, where the format is one of
+/// `cyclonedx-json`, `cyclonedx-xml`, `spdx-json`, or `spdx-tag-value`, so that security
+/// tooling can find the SBOM of a binary. Set it using `LinkSection::with_sbom_digest()`.
+pub fn sbom_digest() -> Option<&'static str> {
+    get_member(Member::SbomDigest)
+}
+
+/// Returns the SBOM embedded in the binary, if present.
+///
+/// This is the SBOM file compressed with raw DEFLATE and encoded as base64. Decompress it
+/// with `ver-shim read --sbom`, or `SectionData::sbom()` in `ver-shim-build`. Embed it
+/// using `LinkSection::embed_sbom()`.
+pub fn embedded_sbom() -> Option<&'static str> {
+    get_member(Member::Sbom)
+}
+
+/// Returns the value of a custom key-value pair, if present.
+///
+/// Set these using `LinkSection::with_custom_kv()` in your build script, e.g. for the
+/// build host or a CI job URL. Unlike [`custom()`], any number of them can be embedded,
+/// so they also serve as application-defined members, e.g. `custom_kv("deploy_env")`.
+pub fn custom_kv(key: &str) -> Option<&'static str> {
+    custom_kvs().find(|(k, _)| *k == key).map(|(_, v)| v)
+}
+
+/// Returns the value of an application-defined member, if present.
+///
+/// This is the same as [`custom_kv()`], for members added with
+/// `LinkSection::with_member()`, e.g. `member("deploy_env")`.
+pub fn member(key: &str) -> Option<&'static str> {
+    custom_kv(key)
+}
+
+/// Returns an iterator over all custom key-value pairs, sorted by key.
+///
+/// The iterator is empty if no pairs are present.
+pub fn custom_kvs() -> CustomKvs {
+    CustomKvs {
+        pairs: kv::Pairs::new(get_member(Member::CustomKv)),
+    }
+}
+
+/// Iterator returned by [`custom_kvs()`].
+#[derive(Clone)]
+pub struct CustomKvs {
+    pairs: kv::Pairs,
+}
+
+impl Iterator for CustomKvs {
+    type Item = (&'static str, &'static str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pairs.next()
+    }
+}
+
+/// Returns an iterator over the members of the workspace that the binary was built in,
+/// as `(name, version)` tuples, sorted by name.
+///
+/// Set this using `LinkSection::with_workspace_members()`, so that support engineers can
+/// tell exactly which versions of the internal crates went into a build. The iterator
+/// is empty if the member list is not present.
+pub fn workspace_members() -> WorkspaceMembers {
+    WorkspaceMembers {
+        pairs: kv::Pairs::new(get_member(Member::WorkspaceMembers)),
+    }
+}
+
+/// Iterator returned by [`workspace_members()`].
+#[derive(Clone)]
+pub struct WorkspaceMembers {
+    pairs: kv::Pairs,
+}
+
+impl Iterator for WorkspaceMembers {
+    type Item = (&'static str, &'static str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pairs.next()
+    }
+}
+
+/// Returns an iterator over the git submodules, as `(path, sha)` tuples sorted by path.
+///
+/// Set this using `LinkSection::with_git_submodules()`. The SHA is the commit checked
+/// out in the submodule, abbreviated if `LinkSection::with_git_short_sha()` is used. The
+/// iterator is empty if the submodule list is not present.
+pub fn submodules() -> Submodules {
+    Submodules {
+        pairs: kv::Pairs::new(get_member(Member::GitSubmodules)),
+    }
+}
+
+/// Iterator returned by [`submodules()`].
+#[derive(Clone)]
+pub struct Submodules {
+    pairs: kv::Pairs,
+}
+
+impl Iterator for Submodules {
+    type Item = (&'static str, &'static str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pairs.next()
+    }
+}
+
+/// Returns the CI run which built the binary, if present.
+///
+/// Set this using `LinkSection::with_ci_info()`, which detects GitHub Actions, GitLab CI,
+/// Buildkite, and Jenkins. It is absent for a build outside of CI.
+pub fn ci_info() -> Option<CiInfo> {
+    get_member(Member::CiInfo).and_then(CiInfo::parse)
+}
+
+/// Returns the cargo profile which the binary was built with, if present.
+///
+/// Set this using `LinkSection::with_build_profile()`. A deploy pipeline can check
+/// [`BuildProfile::is_unoptimized()`] to refuse debug builds.
+pub fn profile() -> Option<BuildProfile> {
+    get_member(Member::BuildProfile).and_then(BuildProfile::parse)
+}
+
+/// Returns the value of an entry registered by a crate in the dependency graph, if present.
+///
+/// Library crates register entries in their build script using
+/// `ver_shim_build::CrateEntries`, and the final patch step merges them into the
+/// binary with `LinkSection::with_crate_entries()`.
+pub fn crate_entry(crate_name: &str, key: &str) -> Option<&'static str> {
+    crate_entries()
+        .find(|(c, k, _)| *c == crate_name && *k == key)
+        .map(|(_, _, v)| v)
+}
+
+/// Returns an iterator over all entries registered by crates in the dependency graph,
+/// as `(crate_name, key, value)` tuples, sorted by crate name and then key.
+///
+/// The iterator is empty if no entries are present.
+pub fn crate_entries() -> CrateEntries {
+    CrateEntries {
+        pairs: kv::Pairs::new(get_member(Member::CrateEntries)),
+    }
+}
+
+/// Iterator returned by [`crate_entries()`].
+#[derive(Clone)]
+pub struct CrateEntries {
+    pairs: kv::Pairs,
+}
+
+impl Iterator for CrateEntries {
+    type Item = (&'static str, &'static str, &'static str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Keys are stored as `crate_name:key`. Crate names never contain ':'.
+        for (key, value) in self.pairs.by_ref() {
+            if let Some((crate_name, key)) = key.split_once(':') {
+                return Some((crate_name, key, value));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,36 @@
+//! Multi-line version text for `--version`, for the `clap` feature.
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::VersionInfo;
+
+/// Formats the long version text, see [`clap_long_version()`](crate::clap_long_version).
+pub(crate) fn format(info: &VersionInfo) -> String {
+    let mut out = String::new();
+    let first = info
+        .pkg_version
+        .or(info.git_describe)
+        .or(info.git_sha)
+        .unwrap_or("(no version info)");
+    out.push_str(first);
+
+    let lines = [
+        ("describe", info.git_describe),
+        ("commit", info.git_sha),
+        ("branch", info.git_branch),
+        ("built", info.build_date),
+    ];
+    for (label, value) in lines {
+        // Don't repeat the first line
+        if let Some(value) = value
+            && value != first
+        {
+            let _ = write!(out, "\n{:<9} {}", label, value);
+        }
+    }
+    if info.git_dirty == Some(true) {
+        out.push_str("\nuncommitted changes");
+    }
+    out
+}
@@ -0,0 +1,62 @@
+//! A Prometheus `build_info` metric, in the text exposition format.
+
+use core::fmt;
+
+use crate::VersionInfo;
+
+/// A `build_info` metric line, returned by
+/// [`prometheus_build_info_line()`](crate::prometheus_build_info_line) and
+/// [`VersionInfo::prometheus_build_info()`].
+///
+/// The `Display` impl prints e.g.
+/// `build_info{version="1.2.0",git_sha="1a2b...",branch="main"} 1`, with a label for
+/// each of the package version, git SHA, git describe output, branch and build date that
+/// is present. The line has no trailing newline.
+#[derive(Clone, Copy, Debug)]
+pub struct BuildInfoLine<'a> {
+    pub(crate) metric_name: &'a str,
+    pub(crate) info: VersionInfo,
+}
+
+impl fmt::Display for BuildInfoLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = &self.info;
+        let labels = [
+            ("version", info.pkg_version),
+            ("git_sha", info.git_sha),
+            ("git_describe", info.git_describe),
+            ("branch", info.git_branch),
+            ("build_date", info.build_date),
+        ];
+
+        f.write_str(self.metric_name)?;
+        let mut first = true;
+        for (label, value) in labels {
+            let Some(value) = value else {
+                continue;
+            };
+            f.write_str(if first { "{" } else { "," })?;
+            first = false;
+            write!(f, "{}=\"", label)?;
+            write_escaped(f, value)?;
+            f.write_str("\"")?;
+        }
+        if !first {
+            f.write_str("}")?;
+        }
+        f.write_str(" 1")
+    }
+}
+
+// Escapes a label value: backslash, double quote and line feed.
+fn write_escaped(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    for c in value.chars() {
+        match c {
+            '\\' => f.write_str("\\\\")?,
+            '"' => f.write_str("\\\"")?,
+            '\n' => f.write_str("\\n")?,
+            c => fmt::Write::write_char(f, c)?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! The hash of the executable, see [`verify_self_hash()`](crate::verify_self_hash).
+//!
+//! `UpdateSectionCommand::with_self_hash()` records the SHA-256 of the patched file, with
+//! the version buffer zeroed so that writing the hash into it doesn't change the hash.
+//! For PE images, the `CheckSum` field of the optional header is zeroed too, since it is
+//! updated after patching. The buffer is found by its contents, which are the same in
+//! memory and in the file.
+
+use core::ops::Range;
+
+#[cfg(feature = "self-hash")]
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "self-hash")]
+use crate::{ChecksumCache, Member, get_member_in};
+
+/// Offset of `e_lfanew` (the offset of the PE signature) in the DOS header.
+const E_LFANEW_OFFSET: usize = 0x3c;
+
+/// Offset of `CheckSum` from the PE signature: the signature, the COFF file header, and
+/// the fields of the optional header before it (the same for PE32 and PE32+).
+const PE_CHECKSUM_OFFSET: usize = 4 + 20 + 64;
+
+/// Zeroes the parts of an executable which the self hash doesn't cover: the version
+/// buffer at `buffer`, and the checksum of a PE image.
+#[doc(hidden)]
+pub fn mask_image(image: &mut [u8], buffer: Range<usize>) {
+    image[buffer].fill(0);
+    if image.starts_with(b"MZ")
+        && let Some(&[a, b, c, d]) = image.get(E_LFANEW_OFFSET..E_LFANEW_OFFSET + 4)
+    {
+        let pe_offset = u32::from_le_bytes([a, b, c, d]) as usize;
+        if image.get(pe_offset..pe_offset + 4) == Some(b"PE\0\0")
+            && let Some(checksum) =
+                image.get_mut(pe_offset + PE_CHECKSUM_OFFSET..pe_offset + PE_CHECKSUM_OFFSET + 4)
+        {
+            checksum.fill(0);
+        }
+    }
+}
+
+/// Finds the version buffer in the contents of an executable.
+#[doc(hidden)]
+pub fn find_buffer(image: &[u8], buffer: &[u8]) -> Option<Range<usize>> {
+    let offset = image
+        .windows(buffer.len())
+        .position(|window| window == buffer)?;
+    Some(offset..offset + buffer.len())
+}
+
+/// Why [`verify_self_hash()`](crate::verify_self_hash) failed.
+#[cfg(feature = "self-hash")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelfHashError {
+    /// The section has no self hash, e.g. because the binary was patched without
+    /// `with_self_hash()`, or its checksum doesn't match.
+    Missing,
+    /// The executable couldn't be read, e.g. on WebAssembly, or because it was deleted
+    /// after it was started.
+    Unreadable,
+    /// The version buffer wasn't found in the executable, e.g. because it was compressed
+    /// by a packer.
+    NotFound,
+    /// The hash of the executable doesn't match, so it was changed after patching.
+    Mismatch,
+}
+
+#[cfg(feature = "self-hash")]
+impl core::fmt::Display for SelfHashError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Missing => "the version data has no self hash",
+            Self::Unreadable => "the executable couldn't be read",
+            Self::NotFound => "the version data wasn't found in the executable",
+            Self::Mismatch => "the executable was changed after it was patched",
+        })
+    }
+}
+
+/// Hashes the running executable and compares it with the self hash in a version buffer,
+/// see `verify_self_hash()`.
+#[cfg(feature = "self-hash")]
+pub(crate) fn verify_in(buf: &'static [u8], checksum: &ChecksumCache) -> Result<(), SelfHashError> {
+    let expected = get_member_in(buf, checksum, Member::SelfHash).ok_or(SelfHashError::Missing)?;
+    let mut image = std::env::current_exe()
+        .and_then(std::fs::read)
+        .map_err(|_| SelfHashError::Unreadable)?;
+    // Use black_box so that the compiler can't use the unpatched contents
+    let buffer = core::hint::black_box(buf);
+    let range = find_buffer(&image, buffer).ok_or(SelfHashError::NotFound)?;
+    mask_image(&mut image, range);
+
+    let mut hex = [0u8; 64];
+    for (pair, byte) in hex.chunks_exact_mut(2).zip(Sha256::digest(&image)) {
+        pair[0] = HEX_DIGITS[(byte >> 4) as usize];
+        pair[1] = HEX_DIGITS[(byte & 0xf) as usize];
+    }
+    if expected.as_bytes() == hex {
+        Ok(())
+    } else {
+        Err(SelfHashError::Mismatch)
+    }
+}
+
+#[cfg(feature = "self-hash")]
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
@@ -0,0 +1,79 @@
+//! Verifying the signature over the version data, for the `signature` feature.
+//!
+//! `LinkSection::with_signing_key()` signs the SHA-256 of [`SIGNATURE_CONTEXT`] followed
+//! by each present member except the signature itself, in header order, as its index
+//! (1 byte), its length (u16, little-endian) and its data. Members which this crate
+//! doesn't know yet are covered too, so that a newer `ver-shim-build` can add members
+//! without breaking verification.
+
+use core::fmt;
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+use crate::{ChecksumCache, Member, SIGNATURE_CONTEXT, member_bytes, read_header};
+
+/// Why [`verify_signature()`](crate::verify_signature) failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The section has no signature, e.g. because it was built without a signing key,
+    /// or its checksum doesn't match.
+    Missing,
+    /// The signature or the public key is malformed.
+    Malformed,
+    /// The signature doesn't match the version data and the public key, so the data was
+    /// changed after signing, or signed with another key.
+    Invalid,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Missing => "the version data is not signed",
+            Self::Malformed => "the signature or public key is malformed",
+            Self::Invalid => "the signature of the version data is invalid",
+        })
+    }
+}
+
+/// Verifies the signature of a version buffer, see `verify_signature()`.
+pub(crate) fn verify_in(
+    buf: &'static [u8],
+    checksum: &ChecksumCache,
+    public_key: &[u8; 32],
+) -> Result<(), SignatureError> {
+    let header = read_header(buf, checksum).ok_or(SignatureError::Missing)?;
+    let signature =
+        member_bytes(buf, &header, Member::Signature as usize).ok_or(SignatureError::Missing)?;
+    let signature = Signature::from_bytes(&decode_hex(signature)?);
+    let public_key = VerifyingKey::from_bytes(public_key).map_err(|_| SignatureError::Malformed)?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(SIGNATURE_CONTEXT);
+    for idx in 0..header.num_members {
+        if idx == Member::Signature as usize {
+            continue;
+        }
+        if let Some(data) = member_bytes(buf, &header, idx) {
+            hasher.update([idx as u8]);
+            hasher.update((data.len() as u16).to_le_bytes());
+            hasher.update(data);
+        }
+    }
+    public_key
+        .verify(&hasher.finalize(), &signature)
+        .map_err(|_| SignatureError::Invalid)
+}
+
+/// Decodes the 64 byte signature from hex.
+fn decode_hex(hex: &[u8]) -> Result<[u8; 64], SignatureError> {
+    if hex.len() != 128 {
+        return Err(SignatureError::Malformed);
+    }
+    let mut bytes = [0u8; 64];
+    for (byte, pair) in bytes.iter_mut().zip(hex.chunks_exact(2)) {
+        let digit = |c: u8| (c as char).to_digit(16).ok_or(SignatureError::Malformed);
+        *byte = (digit(pair[0])? << 4 | digit(pair[1])?) as u8;
+    }
+    Ok(bytes)
+}
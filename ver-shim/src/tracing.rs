@@ -0,0 +1,69 @@
+//! Logging the version data with `tracing`, for the `tracing` feature.
+//!
+//! The field names are the same as those of [`VersionInfo`], so that the version data
+//! looks the same in the logs of every service.
+
+use crate::VersionInfo;
+
+/// Emits an `info` event with the version data as fields, e.g. at startup.
+///
+/// Absent members are left out of the event.
+pub fn log_build_info() {
+    let info = VersionInfo::collect();
+    ::tracing::info!(
+        git_sha = info.git_sha,
+        git_describe = info.git_describe,
+        git_branch = info.git_branch,
+        git_tag = info.git_tag,
+        git_dirty = info.git_dirty,
+        git_commit_timestamp = info.git_commit_timestamp,
+        git_commit_date = info.git_commit_date,
+        git_commit_msg = info.git_commit_msg,
+        git_commit_author = info.git_commit_author,
+        git_committer = info.git_committer,
+        git_remote = info.git_remote,
+        build_timestamp = info.build_timestamp,
+        build_date = info.build_date,
+        custom = info.custom,
+        license_info = info.license_info,
+        pkg_version = info.pkg_version,
+        pkg_name = info.pkg_name,
+        target_triple = info.target_triple,
+        lockfile_hash = info.lockfile_hash,
+        sbom_digest = info.sbom_digest,
+        build_host = info.build_host,
+        "build info"
+    );
+}
+
+/// Records the version data as fields of the current span.
+///
+/// `tracing` only records fields which the span declared when it was created, so declare
+/// the ones you want as empty, e.g.
+/// `info_span!("service", git_sha = field::Empty, git_describe = field::Empty)`, and then
+/// call this inside the span. Absent members are left empty.
+pub fn record_build_info() {
+    let info = VersionInfo::collect();
+    let span = ::tracing::Span::current();
+    span.record("git_sha", info.git_sha);
+    span.record("git_describe", info.git_describe);
+    span.record("git_branch", info.git_branch);
+    span.record("git_tag", info.git_tag);
+    span.record("git_dirty", info.git_dirty);
+    span.record("git_commit_timestamp", info.git_commit_timestamp);
+    span.record("git_commit_date", info.git_commit_date);
+    span.record("git_commit_msg", info.git_commit_msg);
+    span.record("git_commit_author", info.git_commit_author);
+    span.record("git_committer", info.git_committer);
+    span.record("git_remote", info.git_remote);
+    span.record("build_timestamp", info.build_timestamp);
+    span.record("build_date", info.build_date);
+    span.record("custom", info.custom);
+    span.record("license_info", info.license_info);
+    span.record("pkg_version", info.pkg_version);
+    span.record("pkg_name", info.pkg_name);
+    span.record("target_triple", info.target_triple);
+    span.record("lockfile_hash", info.lockfile_hash);
+    span.record("sbom_digest", info.sbom_digest);
+    span.record("build_host", info.build_host);
+}
@@ -0,0 +1,226 @@
+//! All version data at once, e.g. for logging at startup.
+
+use core::fmt;
+
+use crate::{BuildInfoLine, Member};
+
+/// All members of the version data, read from the section.
+///
+/// Since the data lives in the binary itself, the fields are `&'static str`
+/// (or `bool`) and this is cheap to copy around. Crate entries are not included, use
+/// [`crate_entries()`](crate::crate_entries) for those, and neither is the embedded SBOM.
+///
+/// The `Display` impl prints the present members on one line, as
+/// `git_sha: ..., git_describe: ..., ...`, which is convenient for logs.
+///
+/// With the `serde` feature, this implements `serde::Serialize`, as a map with one
+/// entry per field (absent members are `null`). For a `/version` endpoint, see
+/// `http::version_json()` with the `http` feature.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VersionInfo {
+    /// See [`git_sha()`](crate::git_sha).
+    pub git_sha: Option<&'static str>,
+    /// See [`git_describe()`](crate::git_describe).
+    pub git_describe: Option<&'static str>,
+    /// See [`git_branch()`](crate::git_branch).
+    pub git_branch: Option<&'static str>,
+    /// See [`git_tag()`](crate::git_tag).
+    pub git_tag: Option<&'static str>,
+    /// See [`git_dirty()`](crate::git_dirty).
+    pub git_dirty: Option<bool>,
+    /// See [`git_commit_timestamp()`](crate::git_commit_timestamp).
+    pub git_commit_timestamp: Option<&'static str>,
+    /// See [`git_commit_date()`](crate::git_commit_date).
+    pub git_commit_date: Option<&'static str>,
+    /// See [`git_commit_msg()`](crate::git_commit_msg).
+    pub git_commit_msg: Option<&'static str>,
+    /// See [`git_commit_author()`](crate::git_commit_author).
+    pub git_commit_author: Option<&'static str>,
+    /// See [`git_committer()`](crate::git_committer).
+    pub git_committer: Option<&'static str>,
+    /// See [`git_remote()`](crate::git_remote).
+    pub git_remote: Option<&'static str>,
+    /// See [`build_timestamp()`](crate::build_timestamp).
+    pub build_timestamp: Option<&'static str>,
+    /// See [`build_date()`](crate::build_date).
+    pub build_date: Option<&'static str>,
+    /// See [`custom()`](crate::custom).
+    pub custom: Option<&'static str>,
+    /// See [`license_info()`](crate::license_info).
+    pub license_info: Option<&'static str>,
+    /// See [`pkg_version()`](crate::pkg_version).
+    pub pkg_version: Option<&'static str>,
+    /// See [`pkg_name()`](crate::pkg_name).
+    pub pkg_name: Option<&'static str>,
+    /// See [`target_triple()`](crate::target_triple).
+    pub target_triple: Option<&'static str>,
+    /// See [`lockfile_hash()`](crate::lockfile_hash).
+    pub lockfile_hash: Option<&'static str>,
+    /// See [`sbom_digest()`](crate::sbom_digest).
+    pub sbom_digest: Option<&'static str>,
+    /// See [`build_host()`](crate::build_host).
+    pub build_host: Option<&'static str>,
+}
+
+impl VersionInfo {
+    /// Reads all members from the version section.
+    pub fn collect() -> Self {
+        Self::read(crate::get_member)
+    }
+
+    /// Reads all members with the given accessor.
+    pub(crate) fn read(get: impl Fn(Member) -> Option<&'static str>) -> Self {
+        Self {
+            git_sha: get(Member::GitSha),
+            git_describe: get(Member::GitDescribe),
+            git_branch: get(Member::GitBranch),
+            git_tag: get(Member::GitTag),
+            git_dirty: get(Member::GitDirty).map(crate::parse_git_dirty),
+            git_commit_timestamp: get(Member::GitCommitTimestamp),
+            git_commit_date: get(Member::GitCommitDate),
+            git_commit_msg: get(Member::GitCommitMsg),
+            git_commit_author: get(Member::GitCommitAuthor),
+            git_committer: get(Member::GitCommitter),
+            git_remote: get(Member::GitRemote),
+            build_timestamp: get(Member::BuildTimestamp),
+            build_date: get(Member::BuildDate),
+            custom: get(Member::Custom),
+            license_info: get(Member::LicenseInfo),
+            pkg_version: get(Member::PkgVersion),
+            pkg_name: get(Member::PkgName),
+            target_triple: get(Member::TargetTriple),
+            lockfile_hash: get(Member::LockfileHash),
+            sbom_digest: get(Member::SbomDigest),
+            build_host: get(Member::BuildHost),
+        }
+    }
+
+    /// Returns a one-line banner for the program `name`, e.g. for `--version` output.
+    ///
+    /// See [`Banner`] for the format, and [`version_banner!`](crate::version_banner) for a
+    /// shorthand which uses the name of the calling package.
+    pub fn banner<'a>(&self, name: &'a str) -> Banner<'a> {
+        Banner { name, info: *self }
+    }
+
+    /// Returns a Prometheus `build_info` metric line, see [`BuildInfoLine`].
+    pub fn prometheus_build_info<'a>(&self, metric_name: &'a str) -> BuildInfoLine<'a> {
+        BuildInfoLine {
+            metric_name,
+            info: *self,
+        }
+    }
+
+    /// Returns true if no member is present, e.g. if the section was never patched.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn fields(&self) -> [(&'static str, Option<&'static str>); 21] {
+        [
+            ("git_sha", self.git_sha),
+            ("git_describe", self.git_describe),
+            ("git_branch", self.git_branch),
+            ("git_tag", self.git_tag),
+            (
+                "git_dirty",
+                self.git_dirty.map(|d| if d { "true" } else { "false" }),
+            ),
+            ("git_commit_timestamp", self.git_commit_timestamp),
+            ("git_commit_date", self.git_commit_date),
+            ("git_commit_msg", self.git_commit_msg),
+            ("git_commit_author", self.git_commit_author),
+            ("git_committer", self.git_committer),
+            ("git_remote", self.git_remote),
+            ("build_timestamp", self.build_timestamp),
+            ("build_date", self.build_date),
+            ("custom", self.custom),
+            ("license_info", self.license_info),
+            ("pkg_version", self.pkg_version),
+            ("pkg_name", self.pkg_name),
+            ("target_triple", self.target_triple),
+            ("lockfile_hash", self.lockfile_hash),
+            ("sbom_digest", self.sbom_digest),
+            ("build_host", self.build_host),
+        ]
+    }
+}
+
+impl fmt::Display for VersionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("no version info");
+        }
+        let mut first = true;
+        for (name, value) in self.fields() {
+            let Some(value) = value else {
+                continue;
+            };
+            if !first {
+                f.write_str(", ")?;
+            }
+            first = false;
+            write!(f, "{}: {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A one-line version banner, returned by [`VersionInfo::banner()`].
+///
+/// The `Display` impl prints e.g. `myapp 1a2b3c4 (main, dirty) built 2024-06-01`, with
+/// the parts whose members are absent left out: the abbreviated git SHA (or the git
+/// describe output if there is no SHA), the branch and whether the work tree was dirty,
+/// and the build date. If no member is present, it prints `myapp (no version info)`.
+#[derive(Clone, Copy, Debug)]
+pub struct Banner<'a> {
+    name: &'a str,
+    info: VersionInfo,
+}
+
+impl fmt::Display for Banner<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = &self.info;
+        f.write_str(self.name)?;
+        if info.is_empty() {
+            return f.write_str(" (no version info)");
+        }
+
+        let commit = info
+            .git_sha
+            .map(|sha| sha.get(..7).unwrap_or(sha))
+            .or(info.git_describe);
+        if let Some(commit) = commit {
+            write!(f, " {}", commit)?;
+        }
+
+        let dirty = info.git_dirty.and_then(|d| d.then_some("dirty"));
+        match (info.git_branch, dirty) {
+            (Some(branch), Some(dirty)) => write!(f, " ({}, {})", branch, dirty)?,
+            (Some(part), None) | (None, Some(part)) => write!(f, " ({})", part)?,
+            (None, None) => {}
+        }
+
+        let built = info
+            .build_date
+            .or_else(|| info.build_timestamp.and_then(|t| t.get(..10)));
+        if let Some(built) = built {
+            write!(f, " built {}", built)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns a [`Banner`] for the calling package, named by its `CARGO_PKG_NAME`.
+///
+/// ```ignore
+/// println!("{}", ver_shim::version_banner!());
+/// // myapp 1a2b3c4 (main, dirty) built 2024-06-01
+/// ```
+#[macro_export]
+macro_rules! version_banner {
+    () => {
+        $crate::VersionInfo::collect().banner(env!("CARGO_PKG_NAME"))
+    };
+}